@@ -0,0 +1,144 @@
+//! 缓存命中路径的内存分配基准
+//!
+//! 对不同大小的缓存文件重复执行“查缓存 → 构建响应体 → 读出响应体”，统计每次请求的
+//! 分配次数与分配字节数。内容以 `Bytes` 共享，结果应与文件大小无关：8 MB 文件每次请求的
+//! 分配次数与字节数必须与 1 KB 文件相同，否则基准以失败退出。
+//!
+//! 运行：`cargo bench --bench cache_alloc`
+
+use routerway_server::cache::FileCache;
+use routerway_server::eviction::EvictionPolicy;
+use routerway_server::file_body::FileSource;
+use routerway_server::range::ByteRange;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const REQUESTS: u64 = 10_000;
+// 允许的每请求分配字节差异，容纳 Vec 扩容等与文件大小无关的少量波动
+const BYTES_TOLERANCE: f64 = 64.0;
+const SIZES: [(&str, usize); 4] = [
+    ("1 KB", 1024),
+    ("64 KB", 64 * 1024),
+    ("1 MB", 1024 * 1024),
+    ("8 MB", 8 * 1024 * 1024),
+];
+
+fn snapshot() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+async fn serve(cache: &FileCache, key: &str, range: Option<ByteRange>) -> usize {
+    let file = cache.get_fast(key).expect("文件应已缓存");
+    let source = FileSource::Memory(file.get_content());
+    let body = match range {
+        Some(range) => source.range(range).await,
+        None => source.full().await,
+    }
+    .expect("构建响应体失败");
+    hyper::body::to_bytes(body).await.expect("读取响应体失败").len()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("无法创建 tokio 运行时");
+
+    let cache = FileCache::new(
+        PathBuf::from("."),
+        1024 * 1024 * 1024,
+        true,
+        EvictionPolicy::Lru,
+        1024 * 1024 * 1024,
+    );
+
+    runtime.block_on(async {
+        for (name, size) in SIZES {
+            cache
+                .insert_async(
+                    name.to_string(),
+                    vec![b'x'; size],
+                    "application/octet-stream".to_string(),
+                    0,
+                )
+                .await;
+        }
+
+        println!(
+            "{:<8} {:<8} {:>14} {:>16}",
+            "size", "request", "allocs/req", "bytes/req"
+        );
+
+        let mut results = Vec::new();
+        for (name, size) in SIZES {
+            let tail = ByteRange { start: size as u64 / 2, end: size as u64 - 1 };
+            for (label, range) in [("full", None), ("range", Some(tail))] {
+                // 预热，排除首次访问产生的一次性分配
+                serve(&cache, name, range).await;
+
+                let (allocs_before, bytes_before) = snapshot();
+                let mut served = 0;
+                for _ in 0..REQUESTS {
+                    served += serve(&cache, name, range).await;
+                }
+                let (allocs_after, bytes_after) = snapshot();
+
+                assert!(served > 0);
+                let allocs = (allocs_after - allocs_before) as f64 / REQUESTS as f64;
+                let bytes = (bytes_after - bytes_before) as f64 / REQUESTS as f64;
+                println!("{:<8} {:<8} {:>14.2} {:>16.2}", name, label, allocs, bytes);
+                results.push((name, label, allocs, bytes));
+            }
+        }
+
+        // 命中路径不复制内容：最大文件与最小文件的分配情况一致
+        for label in ["full", "range"] {
+            let find = |size: &str| {
+                results
+                    .iter()
+                    .find(|(name, l, _, _)| *name == size && *l == label)
+                    .map(|&(_, _, allocs, bytes)| (allocs, bytes))
+                    .unwrap()
+            };
+            let (small_allocs, small_bytes) = find("1 KB");
+            let (large_allocs, large_bytes) = find("8 MB");
+            assert_eq!(small_allocs, large_allocs, "{}: 分配次数随文件大小变化", label);
+            assert!(
+                (large_bytes - small_bytes).abs() <= BYTES_TOLERANCE,
+                "{}: 8 MB 每次请求分配 {:.2} 字节，1 KB 为 {:.2} 字节",
+                label,
+                large_bytes,
+                small_bytes
+            );
+        }
+    });
+}
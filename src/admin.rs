@@ -0,0 +1,74 @@
+use crate::reload::Reloader;
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::TcpListener;
+use tracing::{error, info};
+
+/// 启动管理接口
+///
+/// - `GET /upstreams`：所有上游的健康状态与请求统计
+/// - `POST /reload`：重新加载配置文件，失败时返回原因并继续使用原配置
+pub fn spawn(listener: TcpListener, reloader: Reloader) -> Result<()> {
+    let make_svc = make_service_fn(move |_conn| {
+        let reloader = reloader.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle_admin_request(req, reloader.clone())
+            }))
+        }
+    });
+
+    let addr = listener.local_addr()?;
+    let server = Server::from_tcp(listener)
+        .with_context(|| format!("无法监听管理接口地址: {}", addr))?
+        .serve(make_svc);
+
+    info!("🛠️ 管理接口: http://{}", addr);
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("管理接口运行错误: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_admin_request(
+    req: Request<Body>,
+    reloader: Reloader,
+) -> Result<Response<Body>, Infallible> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/upstreams") => json_response(
+            StatusCode::OK,
+            serde_json::json!({ "upstreams": reloader.current().upstreams.stats() }),
+        ),
+        (&Method::POST, "/reload") => {
+            // 读取文件与加载证书是阻塞操作
+            let result = tokio::task::spawn_blocking(move || reloader.reload()).await;
+            match result {
+                Ok(Ok(())) => json_response(StatusCode::OK, serde_json::json!({ "status": "reloaded" })),
+                Ok(Err(e)) => json_response(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    serde_json::json!({ "error": format!("{:#}", e) }),
+                ),
+                Err(e) => json_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    serde_json::json!({ "error": e.to_string() }),
+                ),
+            }
+        }
+        _ => json_response(StatusCode::NOT_FOUND, serde_json::json!({ "error": "not found" })),
+    };
+    Ok(response)
+}
+
+fn json_response(status: StatusCode, value: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json; charset=utf-8")
+        .header("Server", "RouterWay")
+        .body(Body::from(value.to_string()))
+        .unwrap()
+}
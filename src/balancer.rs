@@ -0,0 +1,370 @@
+use crate::upstream::Upstream;
+use hyper::header::{HeaderMap, COOKIE};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// 一致性哈希环上每单位权重的虚拟节点数
+const VIRTUAL_NODES: u64 = 160;
+
+/// 上游权重上限，限制调度序列与哈希环的大小
+pub const MAX_WEIGHT: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LoadBalance {
+    #[default]
+    #[serde(rename = "round-robin")]
+    RoundRobin,
+    #[serde(rename = "least-conn")]
+    LeastConn,
+    #[serde(rename = "random-two-choices", alias = "p2c")]
+    RandomTwoChoices,
+    #[serde(rename = "consistent-hash")]
+    ConsistentHash,
+}
+
+/// 一致性哈希的键来源，配置写法为 `ip`、`header:X-User-Id` 或 `cookie:session`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum HashOn {
+    ClientIp,
+    Header(String),
+    Cookie(String),
+}
+
+impl TryFrom<String> for HashOn {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.split_once(':') {
+            None if value == "ip" => Ok(HashOn::ClientIp),
+            Some(("header", name)) if !name.is_empty() => Ok(HashOn::Header(name.to_string())),
+            Some(("cookie", name)) if !name.is_empty() => Ok(HashOn::Cookie(name.to_string())),
+            _ => Err(format!("无效的哈希键: {}，应为 ip、header:<名称> 或 cookie:<名称>", value)),
+        }
+    }
+}
+
+impl From<HashOn> for String {
+    fn from(value: HashOn) -> Self {
+        match value {
+            HashOn::ClientIp => "ip".to_string(),
+            HashOn::Header(name) => format!("header:{}", name),
+            HashOn::Cookie(name) => format!("cookie:{}", name),
+        }
+    }
+}
+
+impl HashOn {
+    /// 从请求中取出哈希键，请求中没有对应的头或 Cookie 时返回 None
+    pub fn key(&self, headers: &HeaderMap, client_ip: IpAddr) -> Option<String> {
+        match self {
+            HashOn::ClientIp => Some(client_ip.to_string()),
+            HashOn::Header(name) => headers
+                .get(name.as_str())
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            HashOn::Cookie(name) => headers
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.to_string()),
+        }
+    }
+}
+
+/// 在一组上游中按策略选择目标，不可用的上游会被跳过
+pub struct Balancer {
+    policy: LoadBalance,
+    // 平滑加权轮询预先展开的调度序列
+    schedule: Vec<usize>,
+    cursor: AtomicUsize,
+    // 一致性哈希环：(哈希值, 上游下标)，按哈希值升序
+    ring: Vec<(u64, usize)>,
+}
+
+impl Balancer {
+    pub fn new(policy: LoadBalance, upstreams: &[Arc<Upstream>]) -> Self {
+        let weights: Vec<u32> = upstreams.iter().map(|u| u.weight()).collect();
+
+        let ring = if policy == LoadBalance::ConsistentHash {
+            let mut ring: Vec<(u64, usize)> = upstreams
+                .iter()
+                .enumerate()
+                .flat_map(|(index, upstream)| {
+                    (0..upstream.weight() as u64 * VIRTUAL_NODES)
+                        .map(move |node| (hash_of(&(upstream.url(), node)), index))
+                })
+                .collect();
+            ring.sort_unstable();
+            ring
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            policy,
+            schedule: smooth_weighted_schedule(&weights),
+            cursor: AtomicUsize::new(0),
+            ring,
+        }
+    }
+
+    pub fn policy(&self) -> LoadBalance {
+        self.policy
+    }
+
+    /// 返回选中的上游下标；`excluded` 中的上游不参与选择，其余上游都不可用时返回 None
+    ///
+    /// 一致性哈希在没有哈希键时退化为加权轮询。
+    pub fn pick(
+        &self,
+        upstreams: &[Arc<Upstream>],
+        hash_key: Option<&str>,
+        excluded: &[usize],
+    ) -> Option<usize> {
+        let candidates = Candidates { upstreams, excluded };
+        match (self.policy, hash_key) {
+            (LoadBalance::RoundRobin, _) | (LoadBalance::ConsistentHash, None) => {
+                self.round_robin(&candidates)
+            }
+            (LoadBalance::LeastConn, _) => self.least_conn(&candidates),
+            (LoadBalance::RandomTwoChoices, _) => random_two_choices(&candidates),
+            (LoadBalance::ConsistentHash, Some(key)) => self.consistent_hash(&candidates, key),
+        }
+    }
+
+    fn round_robin(&self, candidates: &Candidates) -> Option<usize> {
+        let len = self.schedule.len();
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|offset| self.schedule[(start + offset) % len])
+            .find(|&index| candidates.allows(index))
+    }
+
+    // 活跃请求数与权重之比最小者胜出，平局时从轮转的起点开始比较以分散请求
+    fn least_conn(&self, candidates: &Candidates) -> Option<usize> {
+        let upstreams = candidates.upstreams;
+        let len = upstreams.len();
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .filter(|&index| candidates.allows(index))
+            .reduce(|best, index| {
+                if lighter(&upstreams[index], &upstreams[best]) {
+                    index
+                } else {
+                    best
+                }
+            })
+    }
+
+    // 沿哈希环顺时针找到第一个可用的上游
+    fn consistent_hash(&self, candidates: &Candidates, key: &str) -> Option<usize> {
+        let hash = hash_of(&key);
+        let start = self.ring.partition_point(|&(point, _)| point < hash);
+        let len = self.ring.len();
+        (0..len)
+            .map(|offset| self.ring[(start + offset) % len].1)
+            .find(|&index| candidates.allows(index))
+    }
+}
+
+// 参与本次选择的上游：可用且未被排除
+struct Candidates<'a> {
+    upstreams: &'a [Arc<Upstream>],
+    excluded: &'a [usize],
+}
+
+impl Candidates<'_> {
+    fn allows(&self, index: usize) -> bool {
+        self.upstreams[index].is_available() && !self.excluded.contains(&index)
+    }
+}
+
+// 随机选出两个可用上游（按权重），取负载较轻的一个
+fn random_two_choices(candidates: &Candidates) -> Option<usize> {
+    let upstreams = candidates.upstreams;
+    let available: Vec<usize> = (0..upstreams.len())
+        .filter(|&index| candidates.allows(index))
+        .collect();
+
+    let first = weighted_random(upstreams, &available, None)?;
+    match weighted_random(upstreams, &available, Some(first)) {
+        Some(second) if lighter(&upstreams[second], &upstreams[first]) => Some(second),
+        _ => Some(first),
+    }
+}
+
+fn weighted_random(
+    upstreams: &[Arc<Upstream>],
+    candidates: &[usize],
+    exclude: Option<usize>,
+) -> Option<usize> {
+    let total: u64 = candidates
+        .iter()
+        .filter(|&&index| Some(index) != exclude)
+        .map(|&index| upstreams[index].weight() as u64)
+        .sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = fastrand::u64(0..total);
+    for &index in candidates.iter().filter(|&&index| Some(index) != exclude) {
+        let weight = upstreams[index].weight() as u64;
+        if point < weight {
+            return Some(index);
+        }
+        point -= weight;
+    }
+    None
+}
+
+// a 的 活跃数/权重 是否小于 b 的，交叉相乘避免浮点
+fn lighter(a: &Upstream, b: &Upstream) -> bool {
+    (a.active() as u64) * (b.weight() as u64) < (b.active() as u64) * (a.weight() as u64)
+}
+
+// 与 nginx 相同的平滑加权轮询，展开成一个完整周期的调度序列；权重先除以最大公约数以缩短周期
+fn smooth_weighted_schedule(weights: &[u32]) -> Vec<usize> {
+    let divisor = weights.iter().copied().fold(0, gcd).max(1);
+    let weights: Vec<i64> = weights.iter().map(|&w| (w / divisor) as i64).collect();
+    let total: i64 = weights.iter().sum();
+    let mut current = vec![0i64; weights.len()];
+    let mut schedule = Vec::with_capacity(total as usize);
+
+    for _ in 0..total {
+        for (value, &weight) in current.iter_mut().zip(&weights) {
+            *value += weight;
+        }
+        let chosen = (0..current.len())
+            .max_by_key(|&index| (current[index], std::cmp::Reverse(index)))
+            .unwrap_or(0);
+        current[chosen] -= total;
+        schedule.push(chosen);
+    }
+
+    schedule
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// 使用固定密钥的 SipHash，保证同一程序内哈希结果稳定
+fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UpstreamTarget;
+
+    fn upstreams(targets: &[(&str, u32)]) -> Vec<Arc<Upstream>> {
+        targets
+            .iter()
+            .map(|&(url, weight)| {
+                let target = UpstreamTarget {
+                    url: url.to_string(),
+                    weight,
+                };
+                Arc::new(Upstream::new(&target, None).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn smooth_weighted_round_robin_interleaves() {
+        // 与 nginx 对 {5, 1, 1} 的调度结果一致
+        assert_eq!(smooth_weighted_schedule(&[5, 1, 1]), [0, 0, 1, 0, 2, 0, 0]);
+        assert_eq!(smooth_weighted_schedule(&[1, 1]), [0, 1]);
+        // 按最大公约数缩短周期
+        assert_eq!(
+            smooth_weighted_schedule(&[500, 100, 100]),
+            [0, 0, 1, 0, 2, 0, 0]
+        );
+        assert_eq!(smooth_weighted_schedule(&[MAX_WEIGHT, 1]).len(), 1001);
+    }
+
+    #[test]
+    fn round_robin_follows_schedule() {
+        let upstreams = upstreams(&[("http://a", 2), ("http://b", 1)]);
+        let balancer = Balancer::new(LoadBalance::RoundRobin, &upstreams);
+        let picks: Vec<usize> = (0..6)
+            .map(|_| balancer.pick(&upstreams, None, &[]).unwrap())
+            .collect();
+        assert_eq!(picks, [0, 1, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn hash_ring_is_stable_when_an_upstream_is_removed() {
+        let all = upstreams(&[
+            ("http://a", 1),
+            ("http://b", 1),
+            ("http://c", 2),
+            ("http://d", 1),
+        ]);
+        let remaining: Vec<Arc<Upstream>> = all
+            .iter()
+            .filter(|u| u.url() != "http://b")
+            .cloned()
+            .collect();
+        let before = Balancer::new(LoadBalance::ConsistentHash, &all);
+        let after = Balancer::new(LoadBalance::ConsistentHash, &remaining);
+
+        let mut moved = 0;
+        for i in 0..2000 {
+            let key = format!("user-{}", i);
+            let old = all[before.pick(&all, Some(&key), &[]).unwrap()].url();
+            let new = remaining[after.pick(&remaining, Some(&key), &[]).unwrap()].url();
+            if old == "http://b" {
+                moved += 1;
+            } else {
+                assert_eq!(old, new, "{} 不应被重新分配", key);
+            }
+            // 相同的键总是落到相同的上游
+            assert_eq!(
+                new,
+                remaining[after.pick(&remaining, Some(&key), &[]).unwrap()].url()
+            );
+        }
+        // b 的权重占 1/5，大约 400 个键需要迁移
+        assert!((200..600).contains(&moved), "{}", moved);
+    }
+
+    #[test]
+    fn skips_excluded_upstreams() {
+        let upstreams = upstreams(&[("http://a", 1), ("http://b", 1), ("http://c", 1)]);
+        for policy in [
+            LoadBalance::RoundRobin,
+            LoadBalance::LeastConn,
+            LoadBalance::RandomTwoChoices,
+            LoadBalance::ConsistentHash,
+        ] {
+            let balancer = Balancer::new(policy, &upstreams);
+            for _ in 0..20 {
+                assert_eq!(
+                    balancer.pick(&upstreams, Some("key"), &[0, 2]),
+                    Some(1),
+                    "{:?}",
+                    policy
+                );
+            }
+            assert_eq!(balancer.pick(&upstreams, Some("key"), &[0, 1, 2]), None);
+        }
+    }
+}
@@ -1,22 +1,44 @@
+use crate::compression::Encoding;
+use crate::conditional::make_etag;
+use crate::eviction::{self, EvictionPolicy, EvictionQueue, FrequencySketch, Rank};
 use anyhow::{Result, Context};
+use bytes::Bytes;
 use dashmap::DashMap;
-use memmap2::Mmap;
-use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
-use tracing::{info, warn, error};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
 use walkdir::WalkDir;
 
+// 全局逻辑时钟，用于 LRU 排序（秒级时间戳在同一秒内无法区分先后）
+static ACCESS_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+// LFU 命中次数累计到条目数的该倍数时，所有条目的访问计数减半
+const LFU_AGING_FACTOR: u64 = 10;
+
+fn next_tick() -> u64 {
+    ACCESS_CLOCK.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Debug, Clone)]
 pub struct CachedFile {
-    pub content: Arc<Vec<u8>>,
+    // Bytes 克隆只增加引用计数，命中缓存时不复制文件内容
+    pub content: Bytes,
     pub mime_type: String,
     pub last_modified: u64,
+    pub etag: String,
+    // 存在的预压缩兄弟文件（.br/.zst/.gz）位掩码
+    pub precompressed: u8,
+    // 即时压缩后的内容，每种编码只压缩一次，所有克隆共享
+    pub compressed: Arc<DashMap<Encoding, Bytes>>,
     pub access_count: Arc<AtomicUsize>,
     pub last_access: Arc<AtomicU64>,
+    pub last_tick: Arc<AtomicU64>,
+    // W-TinyLFU 下是否仍位于准入窗口区
+    pub in_window: bool,
     pub size: usize,
 }
 
@@ -24,9 +46,12 @@ impl CachedFile {
     pub fn new(content: Vec<u8>, mime_type: String, last_modified: u64) -> Self {
         let size = content.len();
         Self {
-            content: Arc::new(content),
+            etag: make_etag(size as u64, last_modified),
+            content: Bytes::from(content),
             mime_type,
             last_modified,
+            precompressed: 0,
+            compressed: Arc::new(DashMap::new()),
             access_count: Arc::new(AtomicUsize::new(0)),
             last_access: Arc::new(AtomicU64::new(
                 SystemTime::now()
@@ -34,11 +59,13 @@ impl CachedFile {
                     .unwrap()
                     .as_secs()
             )),
+            last_tick: Arc::new(AtomicU64::new(next_tick())),
+            in_window: false,
             size,
         }
     }
 
-    pub fn access(&self) -> Arc<Vec<u8>> {
+    fn touch(&self) {
         self.access_count.fetch_add(1, Ordering::Relaxed);
         self.last_access.store(
             SystemTime::now()
@@ -47,39 +74,80 @@ impl CachedFile {
                 .as_secs(),
             Ordering::Relaxed
         );
-        Arc::clone(&self.content)
+        self.last_tick.store(next_tick(), Ordering::Relaxed);
+    }
+
+    pub fn access(&self) -> Bytes {
+        self.touch();
+        self.content.clone()
     }
 
     // 新增：零拷贝内容获取
-    pub fn get_content(&self) -> Vec<u8> {
-        self.access_count.fetch_add(1, Ordering::Relaxed);
-        self.last_access.store(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            Ordering::Relaxed
-        );
-        (*self.content).clone()
+    pub fn get_content(&self) -> Bytes {
+        self.touch();
+        self.content.clone()
+    }
+
+    /// 原始内容加上所有压缩变体占用的字节数
+    pub fn memory_size(&self) -> u64 {
+        let compressed: usize = self.compressed.iter().map(|entry| entry.value().len()).sum();
+        (self.size + compressed) as u64
     }
 }
 
 pub struct FileCache {
     cache: DashMap<String, CachedFile>,
     total_size: AtomicU64,
-    max_size: u64,
+    // 重新加载配置时可调整，调小后立即淘汰超出的条目
+    max_size: AtomicU64,
+    // 单个文件的缓存大小上限，超过的文件由服务器直接流式发送
+    max_file_size: AtomicU64,
     root_path: PathBuf,
     enabled: bool,
+    policy: EvictionPolicy,
+    // 正在从磁盘加载的路径，同一路径的并发未命中共享一次读取
+    inflight: DashMap<String, Arc<OnceCell<Option<CachedFile>>>>,
+    // 仅 W-TinyLFU 使用：访问频率估计与窗口区大小
+    sketch: Option<FrequencySketch>,
+    window_size: AtomicU64,
+    // 仅 LFU 使用：上次老化以来的命中次数
+    hits: AtomicU64,
+    // 所有插入、替换、删除都在此锁内完成，保证 total_size 精确
+    order: Mutex<EvictionOrder>,
+}
+
+// 淘汰队列：窗口区仅 W-TinyLFU 使用，其余策略的条目都在主区
+#[derive(Default)]
+struct EvictionOrder {
+    window: EvictionQueue,
+    main: EvictionQueue,
 }
 
 impl FileCache {
-    pub fn new(root_path: PathBuf, max_size: u64, enabled: bool) -> Self {
+    pub fn new(
+        root_path: PathBuf,
+        max_size: u64,
+        enabled: bool,
+        policy: EvictionPolicy,
+        max_file_size: u64,
+    ) -> Self {
+        // 按平均 4KB 一个文件估算条目数
+        let sketch = (policy == EvictionPolicy::WTinyLfu)
+            .then(|| FrequencySketch::new((max_size / 4096) as usize));
+
         Self {
             cache: DashMap::new(),
             total_size: AtomicU64::new(0),
-            max_size,
+            max_size: AtomicU64::new(max_size),
+            max_file_size: AtomicU64::new(max_file_size),
             root_path,
             enabled,
+            policy,
+            inflight: DashMap::new(),
+            sketch,
+            window_size: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            order: Mutex::new(EvictionOrder::default()),
         }
     }
 
@@ -102,24 +170,16 @@ impl FileCache {
             if entry.file_type().is_file() {
                 let path = entry.path();
                 
-                // 跳过隐藏文件和临时文件
-                if let Some(filename) = path.file_name() {
-                    let filename_str = filename.to_string_lossy();
-                    if filename_str.starts_with('.') || filename_str.ends_with('~') {
-                        continue;
-                    }
+                if is_ignored(path) {
+                    continue;
                 }
 
-                match self.load_file_to_cache(path).await {
-                    Ok(size) => {
+                // 预热阶段没有访问记录，放不下的文件直接跳过而不是淘汰已加载的文件
+                match self.load_file_to_cache(path, false).await {
+                    Ok(None) => {}
+                    Ok(Some(size)) => {
                         loaded_count += 1;
                         total_size += size;
-                        
-                        // 检查缓存大小限制
-                        if total_size > self.max_size {
-                            warn!("缓存大小超过限制，停止加载更多文件");
-                            break;
-                        }
                     }
                     Err(e) => {
                         warn!("加载文件到缓存失败 {}: {}", path.display(), e);
@@ -128,27 +188,44 @@ impl FileCache {
             }
         }
 
-        info!("文件缓存初始化完成: {} 个文件, 总大小: {} MB", 
-              loaded_count, total_size / 1024 / 1024);
+        let variant_count = self.count_precompressed_variants();
+
+        info!("文件缓存初始化完成: {} 个文件, 总大小: {} MB, 预压缩变体: {} 个", 
+              loaded_count, total_size / 1024 / 1024, variant_count);
         
         Ok(())
     }
 
-    async fn load_file_to_cache(&self, file_path: &Path) -> Result<u64> {
+    // 插入时已通过 link_variant 关联，这里只统计有原文件对应的预压缩变体
+    fn count_precompressed_variants(&self) -> usize {
+        let stems: Vec<String> = self
+            .cache
+            .iter()
+            .filter_map(|entry| {
+                let (stem, ext) = entry.key().rsplit_once('.')?;
+                Encoding::from_extension(ext)?;
+                Some(stem.to_string())
+            })
+            .collect();
+
+        stems.iter().filter(|stem| self.cache.contains_key(stem.as_str())).count()
+    }
+
+    // 返回 None 表示文件未被缓存（过大或空间不足）
+    async fn load_file_to_cache(&self, file_path: &Path, evict: bool) -> Result<Option<u64>> {
         let metadata = fs::metadata(file_path).await
             .with_context(|| format!("无法获取文件元数据: {}", file_path.display()))?;
 
         let file_size = metadata.len();
         
-        // 检查单个文件大小限制（不缓存超过10MB的文件）
-        if file_size > 10 * 1024 * 1024 {
-            return Ok(0);
+        // 检查单个文件大小限制（默认不缓存超过10MB的文件）
+        if file_size > self.max_file_size() {
+            return Ok(None);
         }
 
-        // 检查总缓存大小
-        let current_total = self.total_size.load(Ordering::Relaxed);
-        if current_total + file_size > self.max_size {
-            return Ok(0);
+        // 不允许淘汰时提前检查总缓存大小，避免无谓的磁盘读取
+        if !evict && self.total_size.load(Ordering::Relaxed) + file_size > self.max_size() {
+            return Ok(None);
         }
 
         let content = fs::read(file_path).await
@@ -165,20 +242,117 @@ impl FileCache {
             .unwrap()
             .as_secs();
 
-        // 生成相对路径作为缓存键
-        let cache_key = file_path
+        let cached_file = CachedFile::new(content, mime_type, last_modified);
+        let actual_size = cached_file.size as u64;
+
+        if self.insert_entry(self.cache_key(file_path), cached_file, evict) {
+            Ok(Some(actual_size))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn max_size(&self) -> u64 {
+        self.max_size.load(Ordering::Relaxed)
+    }
+
+    fn max_file_size(&self) -> u64 {
+        self.max_file_size.load(Ordering::Relaxed)
+    }
+
+    /// 调整缓存容量与单文件上限，移除不再符合上限的条目
+    pub fn set_limits(&self, max_size: u64, max_file_size: u64) {
+        let mut order = self.order.lock().unwrap();
+        self.max_size.store(max_size, Ordering::Relaxed);
+        self.max_file_size.store(max_file_size, Ordering::Relaxed);
+
+        let oversized: Vec<String> = self
+            .cache
+            .iter()
+            .filter(|entry| entry.size as u64 > max_file_size)
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in &oversized {
+            self.remove_entry(&mut order, key);
+        }
+        self.make_room(&mut order, 0, "");
+
+        info!("💾 缓存上限已调整: {} 字节 (单文件 {} 字节)，当前占用 {} 字节",
+              max_size, max_file_size, self.total_size.load(Ordering::Relaxed));
+    }
+
+    // 生成相对路径作为缓存键
+    fn cache_key(&self, file_path: &Path) -> String {
+        file_path
             .strip_prefix(&self.root_path)
             .unwrap_or(file_path)
             .to_string_lossy()
-            .replace('\\', "/");
+            .replace('\\', "/")
+    }
 
-        let cached_file = CachedFile::new(content, mime_type, last_modified);
-        let actual_size = cached_file.size as u64;
+    /// 让缓存与磁盘上的路径保持一致：文件重新加载，目录递归加载，已删除的路径移出缓存
+    pub async fn sync_path(&self, path: &Path) {
+        if !self.enabled || is_ignored(path) {
+            return;
+        }
 
-        self.cache.insert(cache_key, cached_file);
-        self.total_size.fetch_add(actual_size, Ordering::Relaxed);
+        let key = self.cache_key(path);
+        match fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => {
+                match self.load_file_to_cache(path, true).await {
+                    Ok(Some(_)) => debug!("文件变更，已重新加载缓存: {}", key),
+                    Ok(None) => {
+                        // 新内容放不进缓存时旧内容也不能继续提供
+                        self.invalidate(&key);
+                    }
+                    Err(e) => {
+                        warn!("重新加载缓存失败 {}: {}", path.display(), e);
+                        self.invalidate(&key);
+                    }
+                }
+            }
+            Ok(metadata) if metadata.is_dir() => {
+                // 新建或移入的目录
+                for entry in WalkDir::new(path)
+                    .follow_links(true)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file() && !is_ignored(e.path()))
+                {
+                    if let Err(e) = self.load_file_to_cache(entry.path(), true).await {
+                        warn!("加载文件到缓存失败 {}: {}", entry.path().display(), e);
+                    }
+                }
+            }
+            _ => {
+                // 路径已删除或被移走，可能是文件也可能是整个目录
+                let prefix = format!("{}/", key);
+                let keys: Vec<String> = self
+                    .cache
+                    .iter()
+                    .filter(|entry| *entry.key() == key || entry.key().starts_with(&prefix))
+                    .map(|entry| entry.key().clone())
+                    .collect();
+                for key in keys {
+                    self.invalidate(&key);
+                    debug!("文件已删除，移出缓存: {}", key);
+                }
+            }
+        }
+    }
+
+    /// 移除缓存条目，预压缩变体被删除时同时清除原文件上的标记
+    pub fn invalidate(&self, key: &str) {
+        let mut order = self.order.lock().unwrap();
+        self.remove_entry(&mut order, key);
 
-        Ok(actual_size)
+        if let Some((stem, ext)) = key.rsplit_once('.') {
+            if let Some(encoding) = Encoding::from_extension(ext) {
+                if let Some(mut original) = self.cache.get_mut(stem) {
+                    original.precompressed &= !encoding.bit();
+                }
+            }
+        }
     }
 
     pub fn get(&self, path: &str) -> Option<CachedFile> {
@@ -187,11 +361,7 @@ impl FileCache {
         }
 
         // 标准化路径
-        let normalized_path = if path.starts_with('/') {
-            &path[1..]
-        } else {
-            path
-        };
+        let normalized_path = path.strip_prefix('/').unwrap_or(path);
 
         // 处理根路径
         let cache_key = if normalized_path.is_empty() || normalized_path == "/" {
@@ -216,36 +386,349 @@ impl FileCache {
             p => p,
         };
 
-        self.cache.get(cache_key).map(|entry| entry.clone())
+        self.record_access(cache_key);
+
+        let entry = self.cache.get(cache_key).map(|entry| entry.clone());
+        if entry.is_some() && self.policy == EvictionPolicy::Lfu {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        entry
     }
 
-    // 新增：异步插入缓存方法
-    pub async fn insert_async(&self, path: String, content: Vec<u8>, mime_type: String) {
-        if !self.enabled || content.len() > 10 * 1024 * 1024 {
-            return;
+    /// 记录一次访问，W-TinyLFU 据此估计访问频率；命中与未命中都应记录且只记录一次
+    pub fn record_access(&self, key: &str) {
+        if let Some(sketch) = &self.sketch {
+            sketch.increment(key);
         }
+    }
 
-        let current_total = self.total_size.load(Ordering::Relaxed);
-        if current_total + content.len() as u64 > self.max_size {
-            return;
+    /// 文件大小是否允许进入缓存
+    pub fn is_cacheable(&self, size: u64) -> bool {
+        self.enabled && size <= self.max_file_size() && size <= self.max_size()
+    }
+
+    /// 缓存未命中时从磁盘加载文件并按策略尝试放入缓存
+    ///
+    /// 同一路径的并发未命中合并为一次磁盘读取（single-flight）。即使文件未被准入缓存，
+    /// 也会返回读取到的内容；文件不存在或读取失败时返回 None。
+    /// 这里不记录访问，调用方应已通过 `get_fast` 或 `record_access` 记录过。
+    pub async fn get_or_load(&self, key: &str, file_path: &Path) -> Option<CachedFile> {
+        if let Some(entry) = self.cache.get(key) {
+            return Some(entry.clone());
+        }
+
+        let cell = self
+            .inflight
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(OnceCell::new()))
+            .clone();
+
+        let result = cell
+            .get_or_init(|| async {
+                debug!("缓存未命中，从磁盘加载: {}", key);
+                self.read_for_miss(key, file_path).await
+            })
+            .await
+            .clone();
+
+        self.inflight.remove_if(key, |_, current| Arc::ptr_eq(current, &cell));
+        result
+    }
+
+    async fn read_for_miss(&self, key: &str, file_path: &Path) -> Option<CachedFile> {
+        let metadata = fs::metadata(file_path).await.ok()?;
+        if !metadata.is_file() {
+            return None;
         }
 
-        let last_modified = SystemTime::now()
+        let content = match fs::read(file_path).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("读取文件失败 {}: {}", file_path.display(), e);
+                return None;
+            }
+        };
+
+        let mime_type = mime_guess::from_path(file_path)
+            .first_or_octet_stream()
+            .to_string();
+        let last_modified = metadata
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH)
             .duration_since(UNIX_EPOCH)
-            .unwrap()
+            .unwrap_or_default()
             .as_secs();
 
+        Some(self.insert_async(key.to_string(), content, mime_type, last_modified).await)
+    }
+
+    // 新增：异步插入缓存方法，返回构建好的条目（无论是否被准入）
+    pub async fn insert_async(
+        &self,
+        path: String,
+        content: Vec<u8>,
+        mime_type: String,
+        last_modified: u64,
+    ) -> CachedFile {
         let cached_file = CachedFile::new(content, mime_type, last_modified);
-        let file_size = cached_file.size as u64;
+        if !self.is_cacheable(cached_file.size as u64) {
+            return cached_file;
+        }
+
+        if !self.insert_entry(path.clone(), cached_file.clone(), true) {
+            return cached_file;
+        }
+
+        // 返回缓存中的条目，使其与后续命中共享访问统计和压缩变体
+        self.cache
+            .get(&path)
+            .map(|entry| entry.clone())
+            .unwrap_or(cached_file)
+    }
+
+    /// 将压缩结果挂到对应的缓存条目上，空间不足时按策略淘汰其他条目，仍放不下则只返回结果
+    ///
+    /// `etag` 为压缩所用原文件的 ETag，压缩期间条目已被新版本替换时不保存，避免旧内容挂到新条目上。
+    pub fn store_compressed(
+        &self,
+        path: &str,
+        etag: &str,
+        encoding: Encoding,
+        content: Vec<u8>,
+    ) -> Bytes {
+        let content = Bytes::from(content);
+        let size = content.len() as u64;
+
+        let mut order = self.order.lock().unwrap();
+        let current = self.cache.get(path).is_some_and(|entry| entry.etag == etag);
+        if !current || !self.make_room(&mut order, size, path) {
+            return content;
+        }
+
+        if let Some(entry) = self.cache.get(path) {
+            match entry.compressed.insert(encoding, content.clone()) {
+                Some(old) => {
+                    self.total_size.fetch_add(size, Ordering::Relaxed);
+                    self.total_size.fetch_sub(old.len() as u64, Ordering::Relaxed);
+                }
+                None => {
+                    self.total_size.fetch_add(size, Ordering::Relaxed);
+                }
+            }
+        }
+
+        content
+    }
+
+    /// 按淘汰策略插入或替换条目，返回条目最终是否留在缓存中
+    fn insert_entry(&self, key: String, mut file: CachedFile, evict: bool) -> bool {
+        let size = file.memory_size();
+        if size > self.max_size() {
+            return false;
+        }
+
+        let mut order = self.order.lock().unwrap();
+
+        // 替换旧条目时先释放其占用
+        self.remove_entry(&mut order, &key);
+        self.link_variant(&key, &mut file);
+
+        if self.policy == EvictionPolicy::WTinyLfu && evict {
+            // 新条目先进入窗口区，窗口溢出的条目再与主区条目比较访问频率
+            file.in_window = true;
+            self.insert_raw(&mut order, key.clone(), file);
+            self.rebalance_window(&mut order);
+            return self.cache.contains_key(&key);
+        }
+
+        let fits = self.total_size.load(Ordering::Relaxed) + size <= self.max_size();
+        if !fits && (!evict || !self.make_room(&mut order, size, &key)) {
+            return false;
+        }
+
+        self.insert_raw(&mut order, key, file);
+        true
+    }
+
+    fn rank(&self, file: &CachedFile) -> Rank {
+        eviction::rank(
+            self.policy,
+            file.access_count.load(Ordering::Relaxed) as u64,
+            file.last_tick.load(Ordering::Relaxed),
+        )
+    }
+
+    fn current_rank(&self, key: &str) -> Option<Rank> {
+        self.cache.get(key).map(|entry| self.rank(&entry))
+    }
+
+    fn insert_raw(&self, order: &mut EvictionOrder, key: String, file: CachedFile) {
+        let size = file.memory_size();
+        let rank = self.rank(&file);
+        if file.in_window {
+            self.window_size.fetch_add(file.size as u64, Ordering::Relaxed);
+            order.window.insert(key.clone(), rank);
+        } else {
+            order.main.insert(key.clone(), rank);
+        }
+        self.total_size.fetch_add(size, Ordering::Relaxed);
+        self.cache.insert(key, file);
+    }
+
+    // 返回释放的字节数
+    fn remove_entry(&self, order: &mut EvictionOrder, key: &str) -> Option<u64> {
+        order.window.remove(key);
+        order.main.remove(key);
+        let (_, file) = self.cache.remove(key)?;
+        let size = file.memory_size();
+        if file.in_window {
+            self.window_size.fetch_sub(file.size as u64, Ordering::Relaxed);
+        }
+        self.total_size.fetch_sub(size, Ordering::Relaxed);
+        Some(size)
+    }
+
+    // 插入时双向关联预压缩兄弟文件
+    fn link_variant(&self, key: &str, file: &mut CachedFile) {
+        for encoding in Encoding::ALL {
+            if self.cache.contains_key(&format!("{}.{}", key, encoding.extension())) {
+                file.precompressed |= encoding.bit();
+            }
+        }
+
+        if let Some((stem, ext)) = key.rsplit_once('.') {
+            if let Some(encoding) = Encoding::from_extension(ext) {
+                if let Some(mut original) = self.cache.get_mut(stem) {
+                    original.precompressed |= encoding.bit();
+                }
+            }
+        }
+    }
+
+    // 按策略淘汰条目直到能再放下 needed 字节，protect 指定的条目不会被淘汰
+    fn make_room(&self, order: &mut EvictionOrder, needed: u64, protect: &str) -> bool {
+        if needed > self.max_size() {
+            return false;
+        }
+        if self.total_size.load(Ordering::Relaxed) + needed <= self.max_size() {
+            return true;
+        }
+        if self.policy == EvictionPolicy::Lfu {
+            self.age_frequencies(order);
+        }
+
+        // 受保护的条目暂时移出队列，淘汰结束后放回原区
+        let protected = order.window.remove(protect) || order.main.remove(protect);
+
+        let mut evicted = 0;
+        while self.total_size.load(Ordering::Relaxed) + needed > self.max_size() {
+            // 主区为空时再淘汰窗口区
+            let victim = match order.main.peek(|key| self.current_rank(key)) {
+                Some(victim) => victim,
+                None => match order.window.peek(|key| self.current_rank(key)) {
+                    Some(victim) => victim,
+                    None => break,
+                },
+            };
+            if self.remove_entry(order, &victim).is_some() {
+                evicted += 1;
+            }
+        }
+        if evicted > 0 {
+            debug!("缓存空间不足，按 {:?} 策略淘汰了 {} 个条目", self.policy, evicted);
+        }
+
+        if protected {
+            if let Some(entry) = self.cache.get(protect) {
+                let queue = if entry.in_window { &mut order.window } else { &mut order.main };
+                queue.insert(protect.to_string(), self.rank(&entry));
+            }
+        }
+
+        self.total_size.load(Ordering::Relaxed) + needed <= self.max_size()
+    }
 
-        self.cache.insert(path, cached_file);
-        self.total_size.fetch_add(file_size, Ordering::Relaxed);
+    // LFU 老化：命中次数足够多后所有访问计数减半，使过去的热点不会永久占据缓存
+    fn age_frequencies(&self, order: &mut EvictionOrder) {
+        let threshold = (self.cache.len() as u64).max(64) * LFU_AGING_FACTOR;
+        if self.hits.load(Ordering::Relaxed) < threshold {
+            return;
+        }
+        self.hits.store(0, Ordering::Relaxed);
+
+        for entry in self.cache.iter() {
+            let _ = entry
+                .access_count
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| Some(count >> 1));
+        }
+        // 排序值变小后队列中的记录不再是下限，需要重建
+        order.main.rebuild(|key| self.current_rank(key));
+        debug!("LFU 访问计数已减半");
+    }
+
+    // W-TinyLFU：窗口区超过 1% 容量时，把窗口中最久未访问的条目作为候选，
+    // 与主区最久未访问的条目比较频率，频率更高者留下。主区使用 LRU 而非 SLRU。
+    fn rebalance_window(&self, order: &mut EvictionOrder) {
+        let sketch = match &self.sketch {
+            Some(sketch) => sketch,
+            None => return,
+        };
+        let window_capacity = (self.max_size() / 100).max(1);
+
+        loop {
+            let over_window = self.window_size.load(Ordering::Relaxed) > window_capacity;
+            let over_total = self.total_size.load(Ordering::Relaxed) > self.max_size();
+            if !over_window && !over_total {
+                break;
+            }
+
+            let candidate = match order.window.peek(|key| self.current_rank(key)) {
+                Some(candidate) => candidate,
+                None => {
+                    // 窗口已空，只能直接淘汰主区条目
+                    match order.main.peek(|key| self.current_rank(key)) {
+                        Some(victim) => {
+                            self.remove_entry(order, &victim);
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+            };
+
+            // 候选条目离开窗口区
+            order.window.remove(&candidate);
+            if let Some(mut entry) = self.cache.get_mut(&candidate) {
+                entry.in_window = false;
+                self.window_size.fetch_sub(entry.size as u64, Ordering::Relaxed);
+            }
+
+            let mut admitted = true;
+            while self.total_size.load(Ordering::Relaxed) > self.max_size() {
+                match order.main.peek(|key| self.current_rank(key)) {
+                    Some(victim) if sketch.frequency(&candidate) > sketch.frequency(&victim) => {
+                        self.remove_entry(order, &victim);
+                    }
+                    _ => {
+                        admitted = false;
+                        break;
+                    }
+                }
+            }
+
+            match self.current_rank(&candidate) {
+                Some(rank) if admitted => order.main.insert(candidate, rank),
+                _ => {
+                    self.remove_entry(order, &candidate);
+                }
+            }
+        }
     }
 
     pub fn get_stats(&self) -> (usize, u64, u64) {
         let count = self.cache.len();
         let total_size = self.total_size.load(Ordering::Relaxed);
-        let max_size = self.max_size;
+        let max_size = self.max_size();
         (count, total_size, max_size)
     }
 
@@ -262,27 +745,42 @@ impl FileCache {
         let mut removed_count = 0;
         let mut freed_size = 0u64;
 
-        self.cache.retain(|_key, cached_file| {
-            let last_access = cached_file.last_access.load(Ordering::Relaxed);
-            let age = current_time.saturating_sub(last_access);
-            
-            if age > max_age_seconds {
-                freed_size += cached_file.size as u64;
+        let expired: Vec<String> = self
+            .cache
+            .iter()
+            .filter(|entry| {
+                let last_access = entry.last_access.load(Ordering::Relaxed);
+                current_time.saturating_sub(last_access) > max_age_seconds
+            })
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        let mut order = self.order.lock().unwrap();
+        for key in expired {
+            if let Some(size) = self.remove_entry(&mut order, &key) {
+                freed_size += size;
                 removed_count += 1;
-                false
-            } else {
-                true
             }
-        });
+        }
 
         if removed_count > 0 {
-            self.total_size.fetch_sub(freed_size, Ordering::Relaxed);
             info!("清理了 {} 个过期缓存条目，释放 {} MB", 
                   removed_count, freed_size / 1024 / 1024);
         }
     }
 }
 
+// 跳过隐藏文件和临时文件
+fn is_ignored(path: &Path) -> bool {
+    match path.file_name() {
+        Some(filename) => {
+            let filename_str = filename.to_string_lossy();
+            filename_str.starts_with('.') || filename_str.ends_with('~')
+        }
+        None => false,
+    }
+}
+
 pub fn get_mime_type(file_path: &str) -> &'static str {
     let path = Path::new(file_path);
     match path.extension().and_then(|ext| ext.to_str()) {
@@ -301,4 +799,241 @@ pub fn get_mime_type(file_path: &str) -> &'static str {
         Some("xml") => "application/xml",
         _ => "application/octet-stream",
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(policy: EvictionPolicy, max_size: u64) -> FileCache {
+        FileCache::new(PathBuf::from("Public"), max_size, true, policy, max_size)
+    }
+
+    fn file(size: usize) -> CachedFile {
+        CachedFile::new(vec![0; size], "text/plain".to_string(), 0)
+    }
+
+    fn insert(cache: &FileCache, key: &str, size: usize) -> bool {
+        cache.insert_entry(key.to_string(), file(size), true)
+    }
+
+    // 逐个条目累加的实际占用，应与 total_size 完全一致
+    fn assert_accounted(cache: &FileCache) {
+        let actual: u64 = cache.cache.iter().map(|entry| entry.memory_size()).sum();
+        assert_eq!(cache.total_size.load(Ordering::Relaxed), actual);
+        let window: u64 = cache
+            .cache
+            .iter()
+            .filter(|entry| entry.in_window)
+            .map(|entry| entry.size as u64)
+            .sum();
+        assert_eq!(cache.window_size.load(Ordering::Relaxed), window);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let cache = cache(EvictionPolicy::Lru, 300);
+        for key in ["a", "b", "c"] {
+            assert!(insert(&cache, key, 100));
+        }
+        cache.get_fast("a").unwrap().get_content();
+
+        assert!(insert(&cache, "d", 100));
+        assert!(cache.cache.contains_key("a"));
+        assert!(!cache.cache.contains_key("b"));
+        assert_accounted(&cache);
+    }
+
+    #[test]
+    fn lfu_evicts_least_frequently_used_and_ages_counts() {
+        let cache = cache(EvictionPolicy::Lfu, 300);
+        for key in ["a", "b", "c"] {
+            assert!(insert(&cache, key, 100));
+        }
+        let hits = 64 * LFU_AGING_FACTOR;
+        for _ in 0..hits {
+            cache.get_fast("a").unwrap().get_content();
+        }
+        cache.get_fast("b").unwrap().get_content();
+
+        // 淘汰前先老化，a 的访问计数减半但仍高于其他条目
+        assert!(insert(&cache, "d", 100));
+        assert!(!cache.cache.contains_key("c"));
+        let a = cache.get("a").unwrap();
+        assert_eq!(a.access_count.load(Ordering::Relaxed) as u64, hits / 2);
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 0);
+        assert_accounted(&cache);
+    }
+
+    #[test]
+    fn w_tinylfu_admits_only_more_frequent_candidates() {
+        // 窗口区容量为 10 字节，每个新条目都会立即离开窗口与主区比较
+        let cache = cache(EvictionPolicy::WTinyLfu, 1000);
+        for i in 0..10 {
+            assert!(insert(&cache, &format!("k{}", i), 100));
+        }
+        for i in 0..10 {
+            for _ in 0..3 {
+                cache.get_fast(&format!("k{}", i));
+            }
+        }
+        assert_accounted(&cache);
+
+        // 只访问过一次的新条目频率低于主区的淘汰候选，不被准入
+        assert!(cache.get_fast("once").is_none());
+        assert!(!insert(&cache, "once", 100));
+        assert_eq!(cache.cache.len(), 10);
+        assert_accounted(&cache);
+
+        // 多次未命中的条目频率更高，替换主区最久未访问的条目
+        for _ in 0..10 {
+            assert!(cache.get_fast("hot").is_none());
+        }
+        assert!(insert(&cache, "hot", 100));
+        assert!(!cache.cache.contains_key("k0"));
+        assert_eq!(cache.cache.len(), 10);
+        assert_accounted(&cache);
+    }
+
+    #[test]
+    fn total_size_tracks_replacements_variants_and_limits() {
+        let cache = cache(EvictionPolicy::Lru, 1000);
+        assert!(insert(&cache, "a.js", 200));
+        assert!(insert(&cache, "a.js.br", 50));
+        assert!(insert(&cache, "b.js", 300));
+        assert_eq!(
+            cache.get("a.js").unwrap().precompressed,
+            Encoding::Brotli.bit()
+        );
+        assert_accounted(&cache);
+
+        // 替换条目
+        assert!(insert(&cache, "b.js", 100));
+        assert_accounted(&cache);
+
+        // 压缩结果挂到条目上并计入占用，源 ETag 不符时不保存
+        let etag = cache.get("b.js").unwrap().etag;
+        cache.store_compressed("b.js", "\"stale\"", Encoding::Gzip, vec![0; 40]);
+        assert_eq!(cache.total_size.load(Ordering::Relaxed), 350);
+        cache.store_compressed("b.js", &etag, Encoding::Gzip, vec![0; 40]);
+        assert_eq!(cache.total_size.load(Ordering::Relaxed), 390);
+        assert_accounted(&cache);
+
+        // 删除预压缩变体时清除原文件上的标记
+        cache.invalidate("a.js.br");
+        assert_eq!(cache.get("a.js").unwrap().precompressed, 0);
+        assert_accounted(&cache);
+
+        // 调小上限后淘汰到不超过新上限
+        cache.set_limits(150, 150);
+        assert!(cache.total_size.load(Ordering::Relaxed) <= 150);
+        assert!(!cache.cache.contains_key("a.js"));
+        assert_accounted(&cache);
+    }
+
+    // 只有一个阻塞线程的运行时，concurrent_misses 借此控制磁盘读取何时完成
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .max_blocking_threads(1)
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    // 每个并发请求都先经 get_fast 未命中（记录一次访问），再调用 get_or_load
+    async fn concurrent_misses(
+        cache: &FileCache,
+        key: &str,
+        path: &Path,
+        n: usize,
+    ) -> Vec<CachedFile> {
+        // 先占住唯一的阻塞线程，让所有请求在第一次读取完成前都加入同一次加载，
+        // 否则读取过快时先到的请求可能已结束，后到的请求会重新读取
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let gate = tokio::task::spawn_blocking(move || blocked.recv());
+
+        let misses = (0..n).map(|_| async {
+            assert!(cache.get_fast(key).is_none());
+            cache.get_or_load(key, path).await.unwrap()
+        });
+        let mut misses = Box::pin(futures_util::future::join_all(misses));
+        assert!(futures_util::poll!(&mut misses).is_pending());
+
+        release.send(()).unwrap();
+        gate.await.unwrap().unwrap();
+        misses.await
+    }
+
+    // 每次磁盘读取都会得到新的缓冲区，所有结果共享同一缓冲区说明只读取了一次
+    fn assert_single_read(files: &[CachedFile], content: &[u8]) {
+        for file in files {
+            assert_eq!(&file.content[..], content);
+            assert_eq!(file.content.as_ptr(), files[0].content.as_ptr());
+        }
+    }
+
+    #[test]
+    fn coalesces_concurrent_misses_into_one_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.html");
+        let content = vec![7u8; 100];
+        std::fs::write(&path, &content).unwrap();
+        let cache = FileCache::new(
+            dir.path().to_path_buf(),
+            1000,
+            true,
+            EvictionPolicy::Lru,
+            1000,
+        );
+
+        let files = runtime().block_on(concurrent_misses(&cache, "page.html", &path, 16));
+        assert_single_read(&files, &content);
+
+        // LRU 在空间足够时直接准入
+        let cached = cache.cache.get("page.html").unwrap().content.clone();
+        assert_eq!(cached.as_ptr(), files[0].content.as_ptr());
+        assert!(cache.inflight.is_empty());
+        assert_eq!(cache.total_size.load(Ordering::Relaxed), 100);
+        assert_accounted(&cache);
+    }
+
+    #[test]
+    fn admits_coalesced_misses_by_frequency() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.html");
+        let content = vec![7u8; 100];
+        std::fs::write(&path, &content).unwrap();
+
+        // 主区已满，淘汰候选的访问频率为 3
+        let cache = FileCache::new(
+            dir.path().to_path_buf(),
+            1000,
+            true,
+            EvictionPolicy::WTinyLfu,
+            1000,
+        );
+        for i in 0..10 {
+            let key = format!("k{}", i);
+            assert!(insert(&cache, &key, 100));
+            for _ in 0..3 {
+                cache.get_fast(&key);
+            }
+        }
+        let sketch = cache.sketch.as_ref().unwrap();
+
+        // 每次未命中只计一次访问，频率 2 不足以替换主区条目，但读取的内容照常返回
+        let runtime = runtime();
+        let files = runtime.block_on(concurrent_misses(&cache, "new.html", &path, 2));
+        assert_single_read(&files, &content);
+        assert_eq!(sketch.frequency("new.html"), 2);
+        assert!(!cache.cache.contains_key("new.html"));
+        assert_accounted(&cache);
+
+        let files = runtime.block_on(concurrent_misses(&cache, "new.html", &path, 4));
+        assert_single_read(&files, &content);
+        assert_eq!(sketch.frequency("new.html"), 6);
+        assert!(cache.cache.contains_key("new.html"));
+        assert_eq!(cache.cache.len(), 10);
+        assert!(cache.inflight.is_empty());
+        assert_accounted(&cache);
+    }
+}
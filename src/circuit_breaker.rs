@@ -0,0 +1,180 @@
+use crate::config::CircuitBreakerConfig;
+use crate::upstream::now_millis;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use tracing::{info, warn};
+
+const CLOSED: u8 = 0;
+const OPEN: u8 = 1;
+const HALF_OPEN: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half-open",
+        }
+    }
+}
+
+/// 单个上游的熔断器
+///
+/// 关闭状态下连续失败达到阈值即打开；打开期间拒绝所有请求，到期后进入半开状态，
+/// 只放行一个试探请求，成功则关闭，失败则重新打开。
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: AtomicU8,
+    failures: AtomicU32,
+    // 打开状态的截止时间（毫秒时间戳）
+    open_until: AtomicU64,
+    // 半开状态下试探请求的发出时间，0 表示尚未发出；超过打开时长仍无结果视为丢失
+    trial_started: AtomicU64,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: AtomicU8::new(CLOSED),
+            failures: AtomicU32::new(0),
+            open_until: AtomicU64::new(0),
+            trial_started: AtomicU64::new(0),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match self.state.load(Ordering::Acquire) {
+            OPEN if now_millis() >= self.open_until.load(Ordering::Relaxed) => BreakerState::HalfOpen,
+            OPEN => BreakerState::Open,
+            HALF_OPEN => BreakerState::HalfOpen,
+            _ => BreakerState::Closed,
+        }
+    }
+
+    /// 是否可能放行请求，不改变状态，供负载均衡筛选使用
+    pub fn can_attempt(&self) -> bool {
+        match self.state() {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => self.trial_available(now_millis()),
+        }
+    }
+
+    /// 为即将发出的请求申请通行，半开状态下只有一个请求能拿到试探名额
+    pub fn try_acquire(&self) -> bool {
+        let now = now_millis();
+        match self.state() {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => {
+                let _ = self.state.compare_exchange(OPEN, HALF_OPEN, Ordering::AcqRel, Ordering::Acquire);
+                let started = self.trial_started.load(Ordering::Acquire);
+                self.trial_available_since(started, now)
+                    && self
+                        .trial_started
+                        .compare_exchange(started, now, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+            }
+        }
+    }
+
+    /// 记录请求结果并据此切换状态
+    pub fn record(&self, success: bool, name: &str) {
+        let state = self.state.load(Ordering::Acquire);
+
+        if success {
+            self.failures.store(0, Ordering::Relaxed);
+            // 打开前发出的请求稍后成功返回不应提前关闭熔断器
+            if state == HALF_OPEN {
+                self.state.store(CLOSED, Ordering::Release);
+                self.trial_started.store(0, Ordering::Release);
+                info!("🔌 熔断器关闭，上游恢复: {}", name);
+            }
+            return;
+        }
+
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if state == HALF_OPEN || (state == CLOSED && failures >= self.config.failure_threshold) {
+            self.open_until
+                .store(now_millis() + self.config.open_ms, Ordering::Relaxed);
+            self.trial_started.store(0, Ordering::Release);
+            self.state.store(OPEN, Ordering::Release);
+            self.failures.store(0, Ordering::Relaxed);
+            warn!("🔌 熔断器打开 {} ms (连续失败 {} 次): {}", self.config.open_ms, failures, name);
+        }
+    }
+
+    fn trial_available(&self, now: u64) -> bool {
+        self.trial_available_since(self.trial_started.load(Ordering::Acquire), now)
+    }
+
+    fn trial_available_since(&self, started: u64, now: u64) -> bool {
+        started == 0 || now.saturating_sub(started) >= self.config.open_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_ms: u64) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_ms,
+        })
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(60_000);
+        breaker.record(false, "test");
+        breaker.record(true, "test");
+        breaker.record(false, "test");
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record(false, "test");
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.can_attempt());
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn half_open_allows_one_trial_then_closes() {
+        let breaker = breaker(200);
+        breaker.record(false, "test");
+        breaker.record(false, "test");
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.can_attempt());
+        assert!(breaker.try_acquire());
+        // 试探请求未返回前不再放行
+        assert!(!breaker.can_attempt());
+        assert!(!breaker.try_acquire());
+
+        breaker.record(true, "test");
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn failed_trial_reopens() {
+        let breaker = breaker(200);
+        breaker.record(false, "test");
+        breaker.record(false, "test");
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert!(breaker.try_acquire());
+
+        breaker.record(false, "test");
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.try_acquire());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::config::Overrides;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// RouterWay 高性能异步 HTTP 服务器
+#[derive(Debug, Parser)]
+#[command(name = "routerway", version)]
+pub struct Cli {
+    /// 配置文件路径
+    #[arg(short, long, global = true, default_value = "config.toml")]
+    pub config: PathBuf,
+
+    /// 校验配置并输出生效的配置后退出，同 `check`，不能与子命令同时使用
+    #[arg(short = 't', long)]
+    pub test_config: bool,
+
+    /// 覆盖 server.port
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// 覆盖 static.root_directory
+    #[arg(long, global = true)]
+    pub root: Option<PathBuf>,
+
+    /// 日志级别或过滤规则，如 debug、routerway_server=trace
+    #[arg(long, global = true, default_value = "info")]
+    pub log_level: String,
+
+    /// 日志格式
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 启动服务器（默认）
+    Serve,
+    /// 校验配置并输出生效的配置
+    Check,
+    /// 通知运行中的实例重新加载配置（通过 server.pid_file）
+    Reload,
+    /// 输出版本号
+    Version,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    /// 完整格式
+    #[default]
+    Full,
+    /// 单行紧凑格式
+    Compact,
+    /// 多行易读格式
+    Pretty,
+}
+
+impl Cli {
+    /// 解析进程的命令行参数，出错时输出用法并退出
+    pub fn parse_args() -> Self {
+        Self::try_parse_args_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// 解析命令行参数；子命令不是普通参数，`-t` 与子命令的冲突无法由 clap 声明，在这里检查
+    pub fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let cli = Self::try_parse_from(args)?;
+        if cli.test_config && cli.command.is_some() {
+            return Err(<Self as CommandFactory>::command().error(
+                ErrorKind::ArgumentConflict,
+                "-t/--test-config 不能与子命令同时使用",
+            ));
+        }
+        Ok(cli)
+    }
+
+    /// 实际执行的子命令，没有子命令时 `-t` 等同于 `check`
+    pub fn command(&self) -> &Command {
+        match &self.command {
+            Some(command) => command,
+            None if self.test_config => &Command::Check,
+            None => &Command::Serve,
+        }
+    }
+
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            port: self.port,
+            root_directory: self.root.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_args_from(std::iter::once("routerway").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_serve() {
+        let cli = parse(&[]);
+        assert!(matches!(cli.command(), Command::Serve));
+        assert_eq!(cli.config, PathBuf::from("config.toml"));
+        assert_eq!(cli.log_level, "info");
+        assert!(matches!(cli.log_format, LogFormat::Full));
+        assert!(cli.overrides().port.is_none());
+        assert!(cli.overrides().root_directory.is_none());
+    }
+
+    #[test]
+    fn parses_subcommands() {
+        assert!(matches!(parse(&["serve"]).command(), Command::Serve));
+        assert!(matches!(parse(&["check"]).command(), Command::Check));
+        assert!(matches!(parse(&["reload"]).command(), Command::Reload));
+        assert!(matches!(parse(&["version"]).command(), Command::Version));
+        assert!(matches!(parse(&["-t"]).command(), Command::Check));
+        assert!(matches!(
+            parse(&["--test-config"]).command(),
+            Command::Check
+        ));
+        assert!(Cli::try_parse_from(["routerway", "restart"]).is_err());
+    }
+
+    #[test]
+    fn rejects_test_config_with_subcommand() {
+        for args in [
+            ["-t", "serve"],
+            ["-t", "reload"],
+            ["--test-config", "version"],
+        ] {
+            let error =
+                Cli::try_parse_args_from(std::iter::once("routerway").chain(args)).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ArgumentConflict, "{:?}", args);
+        }
+        // 子命令不接受 -t
+        assert!(Cli::try_parse_args_from(["routerway", "reload", "-t"]).is_err());
+
+        // 全局选项仍可与 -t 或子命令同时使用
+        let cli = parse(&["--config", "/etc/routerway.toml", "-t"]);
+        assert!(matches!(cli.command(), Command::Check));
+        let cli = parse(&["--port", "8081", "reload"]);
+        assert!(matches!(cli.command(), Command::Reload));
+        assert_eq!(cli.overrides().port, Some(8081));
+    }
+
+    #[test]
+    fn accepts_global_options_after_subcommand() {
+        let cli = parse(&[
+            "check",
+            "--config",
+            "/etc/routerway.toml",
+            "--port",
+            "8081",
+            "--root",
+            "/srv/www",
+            "--log-level",
+            "debug",
+            "--log-format",
+            "compact",
+        ]);
+        assert!(matches!(cli.command(), Command::Check));
+        assert_eq!(cli.config, PathBuf::from("/etc/routerway.toml"));
+        assert_eq!(cli.log_level, "debug");
+        assert!(matches!(cli.log_format, LogFormat::Compact));
+
+        let overrides = cli.overrides();
+        assert_eq!(overrides.port, Some(8081));
+        assert_eq!(overrides.root_directory, Some(PathBuf::from("/srv/www")));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(Cli::try_parse_from(["routerway", "--port", "70000"]).is_err());
+        assert!(Cli::try_parse_from(["routerway", "--log-format", "json"]).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+use crate::config::Config;
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZstdEncoder};
+use async_compression::Level;
+use brotli::enc::BrotliEncoderParams;
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::TryStreamExt;
+use hyper::header::{HeaderMap, ACCEPT_ENCODING, RANGE};
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Encoding {
+    #[serde(rename = "br")]
+    Brotli,
+    #[serde(rename = "zstd")]
+    Zstd,
+    #[serde(rename = "gzip")]
+    Gzip,
+}
+
+impl Encoding {
+    // 服务端偏好顺序：同等 q 值时优先压缩率更高的编码
+    pub const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// 预压缩文件的扩展名，例如 `app.js.br`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.extension() == ext)
+    }
+
+    pub fn bit(&self) -> u8 {
+        match self {
+            Encoding::Brotli => 0b001,
+            Encoding::Zstd => 0b010,
+            Encoding::Gzip => 0b100,
+        }
+    }
+
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            t if t.eq_ignore_ascii_case("br") => Some(Encoding::Brotli),
+            t if t.eq_ignore_ascii_case("zstd") => Some(Encoding::Zstd),
+            t if t.eq_ignore_ascii_case("gzip") || t.eq_ignore_ascii_case("x-gzip") => {
+                Some(Encoding::Gzip)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 解析 `Accept-Encoding`，按 q 值从高到低返回客户端可接受的编码
+pub fn accepted_encodings(headers: &HeaderMap) -> Vec<Encoding> {
+    let value = match headers.get(ACCEPT_ENCODING).and_then(|v| v.to_str().ok()) {
+        Some(value) => value,
+        None => return Vec::new(),
+    };
+
+    let mut explicit: Vec<(Encoding, u16)> = Vec::new();
+    let mut wildcard: Option<u16> = None;
+
+    for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut parts = item.split(';');
+        let token = parts.next().unwrap_or("").trim();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q=").or_else(|| p.trim().strip_prefix("Q=")))
+            .next()
+            .map(parse_qvalue)
+            .unwrap_or(1000);
+
+        if token == "*" {
+            wildcard = Some(q);
+        } else if let Some(encoding) = Encoding::from_token(token) {
+            explicit.push((encoding, q));
+        }
+    }
+
+    let mut weighted: Vec<(Encoding, u16)> = Encoding::ALL
+        .into_iter()
+        .filter_map(|encoding| {
+            let q = explicit
+                .iter()
+                .find(|(e, _)| *e == encoding)
+                .map(|(_, q)| *q)
+                .or(wildcard)?;
+            (q > 0).then_some((encoding, q))
+        })
+        .collect();
+
+    // sort_by_key 是稳定排序，q 值相同时保留服务端偏好顺序
+    weighted.sort_by_key(|(_, q)| std::cmp::Reverse(*q));
+    weighted.into_iter().map(|(e, _)| e).collect()
+}
+
+// q 值按千分制保存，非法值视为 0
+fn parse_qvalue(value: &str) -> u16 {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|q| (0.0..=1.0).contains(q))
+        .map(|q| (q * 1000.0).round() as u16)
+        .unwrap_or(0)
+}
+
+/// 缓存条目一次性压缩时使用的级别；流式压缩（代理响应、未缓存的文件）始终使用最快级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CompressionLevel {
+    #[serde(rename = "fastest")]
+    Fastest,
+    #[default]
+    #[serde(rename = "default")]
+    Default,
+    #[serde(rename = "best")]
+    Best,
+}
+
+// 各级别对应的参数与流式压缩使用的 async-compression 保持一致
+impl CompressionLevel {
+    fn gzip(self) -> Compression {
+        match self {
+            CompressionLevel::Fastest => Compression::fast(),
+            CompressionLevel::Default => Compression::default(),
+            CompressionLevel::Best => Compression::best(),
+        }
+    }
+
+    fn brotli(self) -> i32 {
+        match self {
+            CompressionLevel::Fastest => 0,
+            CompressionLevel::Default => BrotliEncoderParams::default().quality,
+            CompressionLevel::Best => 11,
+        }
+    }
+
+    fn zstd(self) -> i32 {
+        match self {
+            CompressionLevel::Fastest => 1,
+            CompressionLevel::Default => zstd::DEFAULT_COMPRESSION_LEVEL,
+            CompressionLevel::Best => *zstd::compression_level_range().end(),
+        }
+    }
+}
+
+/// 在可用编码（位掩码）中选出客户端最偏好的一个
+pub fn pick(accepted: &[Encoding], available: u8) -> Option<Encoding> {
+    accepted.iter().copied().find(|e| available & e.bit() != 0)
+}
+
+/// 按配置判断是否需要即时压缩，返回选中的编码
+///
+/// `size` 为 `None` 表示长度未知（例如分块传输的代理响应）。
+pub fn negotiate(
+    config: &Config,
+    headers: &HeaderMap,
+    mime_type: &str,
+    size: Option<u64>,
+) -> Option<Encoding> {
+    let compression = config.get_compression();
+    if !compression.enabled || headers.contains_key(RANGE) {
+        return None;
+    }
+    if size.is_some_and(|size| size < config.get_compression_min_size()) {
+        return None;
+    }
+    if !mime_matches(&compression.mime_types, mime_type) {
+        return None;
+    }
+
+    accepted_encodings(headers)
+        .into_iter()
+        .find(|e| compression.algorithms.contains(e))
+}
+
+// 支持 `text/*` 形式的通配，忽略 `; charset=...` 等参数
+fn mime_matches(patterns: &[String], mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or("").trim();
+    patterns.iter().any(|pattern| match pattern.strip_suffix("/*") {
+        Some(prefix) => essence
+            .split_once('/')
+            .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(prefix)),
+        None => essence.eq_ignore_ascii_case(pattern),
+    })
+}
+
+/// 一次性压缩完整内容，用于缓存中的静态文件（结果保存到缓存条目上，只压缩一次）
+///
+/// 压缩是 CPU 密集操作，在阻塞线程池中用同步编码器执行，不占用异步工作线程。
+pub async fn compress(
+    data: Bytes,
+    encoding: Encoding,
+    level: CompressionLevel,
+) -> io::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || encode(&data, encoding, level))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn encode(data: &[u8], encoding: Encoding, level: CompressionLevel) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(data.len() / 2);
+    match encoding {
+        Encoding::Brotli => {
+            let params = BrotliEncoderParams {
+                quality: level.brotli(),
+                ..Default::default()
+            };
+            brotli::BrotliCompress(&mut &data[..], &mut output, &params)?;
+        }
+        Encoding::Zstd => zstd::stream::copy_encode(data, &mut output, level.zstd())?,
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(&mut output, level.gzip());
+            encoder.write_all(data)?;
+            encoder.finish()?;
+        }
+    }
+    Ok(output)
+}
+
+/// 流式压缩响应体，用于代理响应与未进入缓存的文件，不会把整个内容缓冲到内存
+pub fn compress_body(body: Body, encoding: Encoding) -> Body {
+    let reader = StreamReader::new(body.map_err(io::Error::other));
+    match encoding {
+        Encoding::Brotli => Body::wrap_stream(ReaderStream::new(
+            BrotliEncoder::with_quality(reader, Level::Fastest),
+        )),
+        Encoding::Zstd => Body::wrap_stream(ReaderStream::new(
+            ZstdEncoder::with_quality(reader, Level::Fastest),
+        )),
+        Encoding::Gzip => Body::wrap_stream(ReaderStream::new(
+            GzipEncoder::with_quality(reader, Level::Fastest),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+    use std::io::Read;
+
+    const CONFIG: &str = r#"
+[server]
+port = 0
+name = "RouterWay"
+max_cache_size = "1mb"
+cache_enabled = false
+max_connections = 100
+
+[static]
+root_directory = "Public"
+error_pages_directory = "Public/Errors"
+watch = false
+
+[[api]]
+name = "API"
+from = "/api"
+to = "http://127.0.0.1:9"
+"#;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn accepted(value: &'static str) -> Vec<Encoding> {
+        accepted_encodings(&headers(&[("accept-encoding", value)]))
+    }
+
+    #[test]
+    fn orders_accepted_encodings_by_qvalue() {
+        use Encoding::*;
+
+        assert_eq!(
+            accepted("gzip;q=0.5, br;q=0.8, zstd"),
+            vec![Zstd, Brotli, Gzip]
+        );
+        // q 值相同时按服务端偏好
+        assert_eq!(accepted("gzip, zstd, br"), vec![Brotli, Zstd, Gzip]);
+        assert_eq!(accepted("x-gzip;Q=0.3, deflate"), vec![Gzip]);
+        assert_eq!(accepted("*;q=0.2, gzip"), vec![Gzip, Brotli, Zstd]);
+        assert!(accepted_encodings(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn excludes_encodings_with_zero_qvalue() {
+        use Encoding::*;
+
+        assert_eq!(accepted("br;q=0, gzip"), vec![Gzip]);
+        assert_eq!(accepted("*, zstd;q=0"), vec![Brotli, Gzip]);
+        assert_eq!(
+            accepted("gzip;q=0.000, br;q=invalid"),
+            Vec::<Encoding>::new()
+        );
+        assert!(accepted("*;q=0").is_empty());
+        assert_eq!(
+            pick(&[Gzip, Brotli], Brotli.bit() | Zstd.bit()),
+            Some(Brotli)
+        );
+        assert_eq!(pick(&[Gzip], Brotli.bit()), None);
+    }
+
+    #[test]
+    fn negotiates_only_eligible_responses() {
+        let mut config = Config::parse(CONFIG).unwrap();
+        let gzip = headers(&[("accept-encoding", "gzip")]);
+
+        assert_eq!(
+            negotiate(&config, &gzip, "text/html", Some(2048)),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            negotiate(&config, &gzip, "Text/HTML; charset=utf-8", None),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            negotiate(&config, &gzip, "application/json", Some(1024)),
+            Some(Encoding::Gzip)
+        );
+
+        // 类型不在列表中、小于 min_size、客户端不接受
+        assert_eq!(negotiate(&config, &gzip, "image/png", Some(2048)), None);
+        assert_eq!(negotiate(&config, &gzip, "text/html", Some(1023)), None);
+        assert_eq!(
+            negotiate(&config, &HeaderMap::new(), "text/html", Some(2048)),
+            None
+        );
+
+        // Range 请求的偏移基于原始内容
+        let ranged = headers(&[("accept-encoding", "gzip"), ("range", "bytes=0-9")]);
+        assert_eq!(negotiate(&config, &ranged, "text/html", Some(2048)), None);
+
+        // 只使用配置中启用的算法
+        let br_first = headers(&[("accept-encoding", "br, gzip")]);
+        config.compression.algorithms = vec![Encoding::Gzip];
+        assert_eq!(
+            negotiate(&config, &br_first, "text/html", Some(2048)),
+            Some(Encoding::Gzip)
+        );
+
+        config.compression.enabled = false;
+        assert_eq!(negotiate(&config, &gzip, "text/html", Some(2048)), None);
+    }
+
+    #[test]
+    fn compresses_at_each_level() {
+        let data = Bytes::from("RouterWay compression test. ".repeat(200));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        for level in [
+            CompressionLevel::Fastest,
+            CompressionLevel::Default,
+            CompressionLevel::Best,
+        ] {
+            for encoding in Encoding::ALL {
+                let compressed = runtime
+                    .block_on(compress(data.clone(), encoding, level))
+                    .unwrap();
+                assert!(compressed.len() < data.len(), "{:?} {:?}", encoding, level);
+
+                let mut decoded = Vec::new();
+                match encoding {
+                    Encoding::Brotli => {
+                        brotli::BrotliDecompress(&mut &compressed[..], &mut decoded).unwrap();
+                    }
+                    Encoding::Zstd => decoded = zstd::decode_all(&compressed[..]).unwrap(),
+                    Encoding::Gzip => {
+                        flate2::read::GzDecoder::new(&compressed[..])
+                            .read_to_end(&mut decoded)
+                            .unwrap();
+                    }
+                }
+                assert_eq!(decoded, data, "{:?} {:?}", encoding, level);
+            }
+        }
+    }
+}
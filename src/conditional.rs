@@ -0,0 +1,259 @@
+use crate::compression::Encoding;
+use hyper::header::{
+    HeaderMap, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE,
+};
+use hyper::Method;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Precondition {
+    Proceed,
+    NotModified,
+    Failed,
+}
+
+/// 基于文件大小与修改时间生成强 ETag
+pub fn make_etag(size: u64, last_modified: u64) -> String {
+    format!("\"{:x}-{:x}\"", last_modified, size)
+}
+
+/// 压缩变体的 ETag：在原 ETag 后追加编码名，保证不同表示的 ETag 不同
+pub fn variant_etag(etag: &str, encoding: Encoding) -> String {
+    match etag.strip_suffix('"') {
+        Some(prefix) => format!("{}-{}\"", prefix, encoding.as_str()),
+        None => etag.to_string(),
+    }
+}
+
+pub fn http_date(last_modified: u64) -> String {
+    httpdate::fmt_http_date(to_system_time(last_modified))
+}
+
+fn to_system_time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn header_date(headers: &HeaderMap, name: hyper::header::HeaderName) -> Option<u64> {
+    let value = headers.get(name)?.to_str().ok()?;
+    let date = httpdate::parse_http_date(value.trim()).ok()?;
+    date.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// 按 RFC 9110 13.2.2 的顺序评估条件请求头
+pub fn evaluate(
+    method: &Method,
+    headers: &HeaderMap,
+    etag: &str,
+    last_modified: u64,
+) -> Precondition {
+    if let Some(if_match) = headers.get(IF_MATCH).and_then(|v| v.to_str().ok()) {
+        if !etag_list_matches(if_match, etag, false) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = header_date(headers, IF_UNMODIFIED_SINCE) {
+        if last_modified > since {
+            return Precondition::Failed;
+        }
+    }
+
+    let is_read = method == Method::GET || method == Method::HEAD;
+
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        if etag_list_matches(if_none_match, etag, true) {
+            return if is_read {
+                Precondition::NotModified
+            } else {
+                Precondition::Failed
+            };
+        }
+    } else if is_read {
+        if let Some(since) = header_date(headers, IF_MODIFIED_SINCE) {
+            if last_modified <= since {
+                return Precondition::NotModified;
+            }
+        }
+    }
+
+    Precondition::Proceed
+}
+
+/// 判断 `*` 或实体标签列表是否命中当前 ETag，`weak` 为 true 时忽略 W/ 前缀
+pub fn etag_list_matches(value: &str, etag: &str, weak: bool) -> bool {
+    if value.trim() == "*" {
+        return true;
+    }
+
+    parse_etag_list(value).into_iter().any(|(is_weak, tag)| {
+        if weak {
+            tag == etag.trim_start_matches("W/")
+        } else {
+            !is_weak && !etag.starts_with("W/") && tag == etag
+        }
+    })
+}
+
+// 解析逗号分隔的实体标签，返回 (是否弱标签, 带引号的标签值)
+fn parse_etag_list(value: &str) -> Vec<(bool, &str)> {
+    let mut tags = Vec::new();
+    let mut rest = value;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+
+        let is_weak = rest.starts_with("W/");
+        if is_weak {
+            rest = &rest[2..];
+        }
+        if !rest.starts_with('"') {
+            break;
+        }
+        match rest[1..].find('"') {
+            Some(end) => {
+                tags.push((is_weak, &rest[..end + 2]));
+                rest = &rest[end + 2..];
+            }
+            None => break,
+        }
+    }
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{HeaderName, HeaderValue};
+
+    const ETAG: &str = "\"5f-1a\"";
+    const MODIFIED: u64 = 1_700_000_000;
+
+    fn headers(pairs: &[(HeaderName, String)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    fn check(method: Method, pairs: &[(HeaderName, String)]) -> Precondition {
+        evaluate(&method, &headers(pairs), ETAG, MODIFIED)
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        for value in [
+            ETAG.to_string(),
+            format!("W/{}", ETAG),
+            "*".into(),
+            format!("\"x\", {}", ETAG),
+        ] {
+            assert_eq!(
+                check(Method::GET, &[(IF_NONE_MATCH, value.clone())]),
+                Precondition::NotModified,
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            check(Method::GET, &[(IF_NONE_MATCH, "\"x\"".into())]),
+            Precondition::Proceed
+        );
+        // 非安全方法命中 If-None-Match 时返回 412
+        assert_eq!(
+            check(Method::PUT, &[(IF_NONE_MATCH, ETAG.into())]),
+            Precondition::Failed
+        );
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        assert_eq!(
+            check(Method::GET, &[(IF_MATCH, ETAG.into())]),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_MATCH, "*".into())]),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_MATCH, format!("W/{}", ETAG))]),
+            Precondition::Failed
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_MATCH, "\"x\"".into())]),
+            Precondition::Failed
+        );
+    }
+
+    #[test]
+    fn entity_tags_take_precedence_over_dates() {
+        let before = http_date(MODIFIED - 60);
+        let after = http_date(MODIFIED + 60);
+
+        // If-Match 存在时忽略 If-Unmodified-Since
+        assert_eq!(
+            check(
+                Method::GET,
+                &[
+                    (IF_MATCH, ETAG.into()),
+                    (IF_UNMODIFIED_SINCE, before.clone())
+                ]
+            ),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_UNMODIFIED_SINCE, before.clone())]),
+            Precondition::Failed
+        );
+
+        // If-None-Match 存在时忽略 If-Modified-Since
+        assert_eq!(
+            check(
+                Method::GET,
+                &[
+                    (IF_NONE_MATCH, "\"x\"".into()),
+                    (IF_MODIFIED_SINCE, after.clone())
+                ]
+            ),
+            Precondition::Proceed
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_MODIFIED_SINCE, after)]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            check(Method::GET, &[(IF_MODIFIED_SINCE, before)]),
+            Precondition::Proceed
+        );
+
+        // 412 先于 304
+        assert_eq!(
+            check(
+                Method::GET,
+                &[(IF_MATCH, "\"x\"".into()), (IF_NONE_MATCH, ETAG.into())]
+            ),
+            Precondition::Failed
+        );
+    }
+
+    #[test]
+    fn if_modified_since_only_applies_to_reads() {
+        let after = http_date(MODIFIED + 60);
+        assert_eq!(
+            check(Method::HEAD, &[(IF_MODIFIED_SINCE, after.clone())]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            check(Method::POST, &[(IF_MODIFIED_SINCE, after)]),
+            Precondition::Proceed
+        );
+    }
+
+    #[test]
+    fn variant_etag_appends_encoding() {
+        assert_eq!(variant_etag(ETAG, Encoding::Gzip), "\"5f-1a-gzip\"");
+    }
+}
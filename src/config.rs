@@ -1,13 +1,257 @@
+use crate::balancer::{HashOn, LoadBalance, MAX_WEIGHT};
+use crate::compression::{CompressionLevel, Encoding};
+use crate::eviction::EvictionPolicy;
+use crate::file_body::LargeFileMode;
+use crate::forwarding::HostHeader;
+use crate::limits::ConnectionOverflow;
+use crate::tls::TlsVersion;
+use crate::upstream::UpstreamProtocol;
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiConfig {
     pub name: String,
     pub from: String,
+    // 单个上游的简写，与 upstreams 二选一
+    #[serde(default)]
     pub to: String,
+    #[serde(default)]
+    pub upstreams: Vec<UpstreamTarget>,
+    #[serde(default)]
+    pub load_balance: LoadBalance,
+    // 一致性哈希的键，默认为客户端 IP
+    #[serde(default)]
+    pub hash_on: Option<HashOn>,
+    // 每个上游主机保留的最大空闲连接数
+    #[serde(default = "default_pool_max_idle")]
+    pub pool_max_idle: usize,
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    // 从发出请求到收到响应头的最长等待时间，也是响应体两次读取之间的最长间隔（gRPC、SSE 除外）
+    #[serde(default = "default_response_timeout_ms")]
+    pub response_timeout_ms: u64,
+    // WebSocket 隧道两个方向都没有数据时的最长保持时间
+    #[serde(default = "default_websocket_idle_timeout_secs")]
+    pub websocket_idle_timeout_secs: u64,
+    #[serde(default)]
+    pub prefix_mode: PrefixMode,
+    #[serde(default)]
+    pub host_header: HostHeader,
+    // 与上游通信使用的协议：http1、http2（https 经 ALPN，http 为 h2c）或 auto（https 按 ALPN 协商）
+    #[serde(default)]
+    pub upstream_protocol: UpstreamProtocol,
+    // 配置后启用主动健康检查
+    #[serde(default)]
+    pub health_check: Option<HealthCheckConfig>,
+    // 配置后启用被动异常检测
+    #[serde(default)]
+    pub outlier_detection: Option<OutlierDetectionConfig>,
+    // 未配置时不重试
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+    // 配置后为每个上游启用熔断器
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    // https 上游的证书校验与客户端证书，未配置时使用内置根证书校验
+    #[serde(default)]
+    pub tls: Option<UpstreamTlsConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamTlsConfig {
+    // PEM 格式的 CA 证书包，配置后只信任其中的证书
+    pub ca_file: Option<PathBuf>,
+    // 双向 TLS 的客户端证书链与私钥（PEM），需同时配置
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    // 握手时发送并用于校验证书的服务器名称，默认为上游地址中的主机名
+    pub sni: Option<String>,
+    // 跳过证书校验，仅限开发环境使用
+    pub insecure_skip_verify: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    // 包括首次请求在内的最大尝试次数
+    pub max_attempts: u32,
+    pub retry_on_connect_failure: bool,
+    pub retry_on_status: Vec<u16>,
+    // 只重试幂等方法（GET、HEAD、OPTIONS、TRACE、PUT、DELETE）
+    pub idempotent_only: bool,
+    pub backoff_base_ms: u64,
+    pub backoff_max_ms: u64,
+    // 为了重放而缓冲的请求体上限，超过则该请求不再重试
+    pub max_buffer_size: String,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_on_connect_failure: true,
+            retry_on_status: vec![502, 503, 504],
+            idempotent_only: true,
+            backoff_base_ms: 50,
+            backoff_max_ms: 1000,
+            max_buffer_size: "64kb".to_string(),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn get_max_buffer_size(&self) -> u64 {
+        // 加载时已校验过格式
+        Config::parse_cache_size(&self.max_buffer_size).unwrap_or(64 * 1024)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    // 连续失败多少次后打开
+    pub failure_threshold: u32,
+    // 打开多久后进入半开状态
+    pub open_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_ms: 10000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    pub path: String,
+    pub interval_ms: u64,
+    pub timeout_ms: u64,
+    pub expected_status: Vec<u16>,
+    // 连续成功多少次后恢复为健康
+    pub healthy_threshold: u32,
+    // 连续失败多少次后标记为不健康
+    pub unhealthy_threshold: u32,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            path: "/health".to_string(),
+            interval_ms: 5000,
+            timeout_ms: 2000,
+            expected_status: vec![200],
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutlierDetectionConfig {
+    // 连续多少次 5xx 或连接错误后摘除上游
+    pub consecutive_failures: u32,
+    // 摘除后多久重新放回
+    pub cool_down_ms: u64,
+}
+
+impl Default for OutlierDetectionConfig {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 5,
+            cool_down_ms: 30000,
+        }
+    }
+}
+
+/// 转发时如何处理匹配到的 `from` 前缀
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PrefixMode {
+    // 用 `to` 的路径替换前缀，例如 /api/v1/users -> http://host/v1/users（to = http://host/v1）
+    #[default]
+    #[serde(rename = "replace")]
+    Replace,
+    // 去掉前缀后直接转发到 `to` 的主机，忽略 `to` 中的路径
+    #[serde(rename = "strip")]
+    Strip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamTarget {
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_pool_max_idle() -> usize {
+    32
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_response_timeout_ms() -> u64 {
+    30000
+}
+
+fn default_websocket_idle_timeout_secs() -> u64 {
+    300
+}
+
+impl ApiConfig {
+    /// 所有上游目标；只配置了 `to` 时视为权重为 1 的单个上游
+    pub fn get_targets(&self) -> Vec<UpstreamTarget> {
+        if self.upstreams.is_empty() && !self.to.is_empty() {
+            return vec![UpstreamTarget { url: self.to.clone(), weight: 1 }];
+        }
+        self.upstreams.clone()
+    }
+
+    pub fn get_pool_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.pool_idle_timeout_secs)
+    }
+
+    pub fn get_connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout_ms)
+    }
+
+    pub fn get_response_timeout(&self) -> Duration {
+        Duration::from_millis(self.response_timeout_ms)
+    }
+
+    pub fn get_websocket_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.websocket_idle_timeout_secs)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,13 +260,249 @@ pub struct ServerConfig {
     pub name: String,
     pub max_cache_size: String,
     pub cache_enabled: bool,
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+    #[serde(default = "default_max_cached_file_size")]
+    pub max_cached_file_size: String,
+    #[serde(default)]
+    pub large_file_mode: LargeFileMode,
     pub max_connections: usize,
+    // 连接数达到上限后排队等待（queue）或直接拒绝（reject）
+    #[serde(default)]
+    pub connection_overflow: ConnectionOverflow,
+    // 收到关闭信号后等待在途请求与 WebSocket 隧道结束的最长时间
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    // 启动后写入进程号，供 `routerway reload` 发送信号
+    #[serde(default)]
+    pub pid_file: Option<PathBuf>,
+}
+
+fn default_max_cached_file_size() -> String {
+    "10mb".to_string()
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StaticConfig {
     pub root_directory: PathBuf,
     pub error_pages_directory: PathBuf,
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    #[serde(default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    200
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub algorithms: Vec<Encoding>,
+    pub min_size: String,
+    pub mime_types: Vec<String>,
+    pub level: CompressionLevel,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            algorithms: Encoding::ALL.to_vec(),
+            min_size: "1kb".to_string(),
+            mime_types: vec![
+                "text/*".to_string(),
+                "application/javascript".to_string(),
+                "application/json".to_string(),
+                "application/xml".to_string(),
+                "image/svg+xml".to_string(),
+            ],
+            level: CompressionLevel::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    // 管理接口只应监听在本机或内网地址
+    pub address: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:9090".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    // 从收到请求的第一个字节起读完请求头的时限，超时返回 408（HTTP/1），HTTP/3 直接重置请求流
+    pub header_read_timeout_ms: u64,
+    // 两个请求之间连接保持空闲的时限，HTTP/2 连接为没有处理中请求的时限
+    pub keep_alive_timeout_secs: u64,
+    // 请求体两次读取之间的最长间隔，超时返回 408（gRPC 请求除外）
+    pub body_read_timeout_ms: u64,
+    // HTTP/1 的请求头数量由 hyper 限制在 100 个以内
+    pub max_header_count: usize,
+    // 请求头总大小，HTTP/1 包含请求行，不能小于 8kb
+    pub max_header_size: String,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            header_read_timeout_ms: 10000,
+            keep_alive_timeout_secs: 60,
+            body_read_timeout_ms: 30000,
+            max_header_count: 100,
+            max_header_size: "64kb".to_string(),
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn get_header_read_timeout(&self) -> Duration {
+        Duration::from_millis(self.header_read_timeout_ms)
+    }
+
+    pub fn get_keep_alive_timeout(&self) -> Duration {
+        Duration::from_secs(self.keep_alive_timeout_secs)
+    }
+
+    pub fn get_body_read_timeout(&self) -> Duration {
+        Duration::from_millis(self.body_read_timeout_ms)
+    }
+
+    pub fn get_max_header_size(&self) -> usize {
+        // 加载时已校验过格式
+        Config::parse_cache_size(&self.max_header_size).unwrap_or(64 * 1024) as usize
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Http2Config {
+    // 明文端口接受以 HTTP/2 连接前言开头的 h2c 连接（prior knowledge）
+    pub h2c: bool,
+    pub max_concurrent_streams: u32,
+    pub initial_stream_window_size: u32,
+    pub initial_connection_window_size: u32,
+    // 按带宽时延积自动调整窗口，开启后忽略上面两个窗口大小
+    pub adaptive_window: bool,
+    // 发送 PING 的间隔，0 表示不发送；超时未收到回应则关闭连接
+    pub keep_alive_interval_secs: u64,
+    pub keep_alive_timeout_secs: u64,
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            h2c: false,
+            max_concurrent_streams: 200,
+            initial_stream_window_size: 1024 * 1024,
+            initial_connection_window_size: 1024 * 1024,
+            adaptive_window: false,
+            keep_alive_interval_secs: 0,
+            keep_alive_timeout_secs: 20,
+        }
+    }
+}
+
+impl Http2Config {
+    pub fn get_keep_alive_interval(&self) -> Option<Duration> {
+        (self.keep_alive_interval_secs > 0).then(|| Duration::from_secs(self.keep_alive_interval_secs))
+    }
+
+    pub fn get_keep_alive_timeout(&self) -> Duration {
+        Duration::from_secs(self.keep_alive_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Http3Config {
+    // 需要同时启用 TLS，证书与 HTTPS 监听共用
+    pub enabled: bool,
+    // UDP 端口，未配置时与 tls.port 相同
+    pub port: Option<u16>,
+    // HTTPS 响应中 Alt-Svc 的有效期
+    pub alt_svc_max_age_secs: u64,
+    pub max_concurrent_streams: u32,
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for Http3Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: None,
+            alt_svc_max_age_secs: 86400,
+            max_concurrent_streams: 100,
+            idle_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    // HTTPS 监听端口，与 server.port 的明文监听同时运行
+    pub port: u16,
+    pub min_version: TlsVersion,
+    // 按优先级排列的 ALPN 协议
+    pub alpn: Vec<String>,
+    pub handshake_timeout_ms: u64,
+    // 检查证书文件是否变化的间隔，0 表示不自动重新加载
+    pub reload_check_secs: u64,
+    // 配置后在该端口把 HTTP 请求重定向到 HTTPS
+    pub redirect_port: Option<u16>,
+    // 第一张证书同时作为默认证书
+    pub certificates: Vec<CertificateConfig>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 443,
+            min_version: TlsVersion::default(),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            handshake_timeout_ms: 10000,
+            reload_check_secs: 60,
+            redirect_port: None,
+            certificates: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateConfig {
+    // PEM 格式的证书链与私钥
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    // DER 格式的 OCSP 响应，握手时随证书发送
+    #[serde(default)]
+    pub ocsp: Option<PathBuf>,
+    // 用于 SNI 匹配的域名，支持 *.example.com，未配置时取证书中的域名
+    #[serde(default)]
+    pub server_names: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,37 +510,317 @@ pub struct Config {
     pub server: ServerConfig,
     #[serde(rename = "static")]
     pub static_config: StaticConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub http2: Http2Config,
+    #[serde(default)]
+    pub http3: Http3Config,
     pub api: Vec<ApiConfig>,
+    // 从文件加载时记录路径，重新加载时读取同一文件
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    // 命令行覆盖的设置，重新加载时再次应用
+    #[serde(skip)]
+    overrides: Overrides,
+}
+
+/// 命令行中覆盖配置文件的设置
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub port: Option<u16>,
+    pub root_directory: Option<PathBuf>,
 }
 
 impl Config {
     pub fn load_from_file(path: &str) -> Result<Self> {
+        Self::load(Path::new(path), Overrides::default())
+    }
+
+    /// 读取配置文件并应用命令行覆盖的设置
+    pub fn load(path: &Path, overrides: Overrides) -> Result<Self> {
         let content = std::fs::read_to_string(path)
-            .with_context(|| format!("无法读取配置文件: {}", path))?;
+            .with_context(|| format!("无法读取配置文件: {}", path.display()))?;
         
-        let mut config: Config = toml::from_str(&content)
-            .with_context(|| format!("配置文件格式错误: {}", path))?;
+        let mut config = Self::parse_with(&content, overrides)
+            .with_context(|| format!("配置文件格式错误: {}", path.display()))?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// 解析并校验 TOML 格式的配置内容
+    pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_with(content, Overrides::default())
+    }
+
+    fn parse_with(content: &str, overrides: Overrides) -> Result<Self> {
+        let mut config: Config = toml::from_str(content)?;
+        if let Some(port) = overrides.port {
+            config.server.port = port;
+        }
+        if let Some(root) = &overrides.root_directory {
+            config.static_config.root_directory = root.clone();
+        }
+        config.overrides = overrides;
         
         // 解析缓存大小
         let cache_size = Self::parse_cache_size(&config.server.max_cache_size)?;
+        let compression_min_size = Self::parse_cache_size(&config.compression.min_size)?;
+        let max_cached_file_size = Self::parse_cache_size(&config.server.max_cached_file_size)?;
         
         info!("配置加载完成:");
         info!("  端口: {}", config.server.port);
         info!("  服务器名称: {}", config.server.name);
         info!("  根目录: {}", config.static_config.root_directory.display());
         info!("  错误页面目录: {}", config.static_config.error_pages_directory.display());
+        info!("  文件监听: {} (防抖 {} ms)", config.static_config.watch, config.static_config.watch_debounce_ms);
         info!("  最大缓存: {} 字节", cache_size);
         info!("  缓存启用: {}", config.server.cache_enabled);
-        info!("  最大连接数: {}", config.server.max_connections);
+        info!("  淘汰策略: {:?}", config.server.eviction_policy);
+        info!("  单文件缓存上限: {} 字节 (更大的文件以 {:?} 方式发送)",
+              max_cached_file_size, config.server.large_file_mode);
+        info!("  最大连接数: {} (超出时 {:?})", config.server.max_connections, config.server.connection_overflow);
+        info!("  关闭等待: {} 秒", config.server.shutdown_timeout_secs);
+        if let Some(pid_file) = &config.server.pid_file {
+            info!("  PID 文件: {}", pid_file.display());
+        }
+        info!("  响应压缩: {} (最小 {} 字节, 算法: {:?})",
+              config.compression.enabled, compression_min_size, config.compression.algorithms);
         info!("  API配置数量: {}", config.api.len());
         
         for (i, api) in config.api.iter().enumerate() {
-            info!("  API[{}]: {} -> {} ({})", i, api.from, api.to, api.name);
+            info!("  API[{}]: {} ({}, 前缀 {:?}, Host {:?}, 负载均衡 {:?}, 上游协议 {:?})",
+                  i, api.from, api.name, api.prefix_mode, api.host_header, api.load_balance,
+                  api.upstream_protocol);
+            for target in api.get_targets() {
+                info!("    上游: {} (权重 {})", target.url, target.weight);
+            }
+            if let Some(health_check) = &api.health_check {
+                info!("    健康检查: {} 每 {} ms (超时 {} ms, 期望 {:?}, 阈值 {}/{})",
+                      health_check.path, health_check.interval_ms, health_check.timeout_ms,
+                      health_check.expected_status, health_check.healthy_threshold,
+                      health_check.unhealthy_threshold);
+            }
+            if let Some(outlier) = &api.outlier_detection {
+                info!("    异常检测: 连续 {} 次失败摘除 {} ms",
+                      outlier.consecutive_failures, outlier.cool_down_ms);
+            }
+            if let Some(retry) = &api.retry {
+                info!("    重试: 最多 {} 次 (连接失败 {}, 状态码 {:?}, 仅幂等 {}, 退避 {}-{} ms, 缓冲 {})",
+                      retry.max_attempts, retry.retry_on_connect_failure, retry.retry_on_status,
+                      retry.idempotent_only, retry.backoff_base_ms, retry.backoff_max_ms,
+                      retry.max_buffer_size);
+            }
+            if let Some(breaker) = &api.circuit_breaker {
+                info!("    熔断: 连续 {} 次失败打开 {} ms",
+                      breaker.failure_threshold, breaker.open_ms);
+            }
+            if let Some(tls) = &api.tls {
+                info!("    TLS: CA {:?}, 客户端证书 {:?}, SNI {:?}",
+                      tls.ca_file, tls.client_cert, tls.sni);
+                if tls.insecure_skip_verify {
+                    warn!("⚠️ API {} 已关闭上游证书校验，请勿在生产环境使用", api.name);
+                }
+            }
+            info!("    连接池: 空闲 {} 个/{} 秒, 连接超时 {} ms, 响应超时 {} ms",
+                  api.pool_max_idle, api.pool_idle_timeout_secs,
+                  api.connect_timeout_ms, api.response_timeout_ms);
+        }
+
+        if config.admin.enabled {
+            config.admin.address.parse::<std::net::SocketAddr>()
+                .with_context(|| format!("无效的管理接口地址: {}", config.admin.address))?;
+            info!("  管理接口: http://{}", config.admin.address);
+        }
+
+        if config.server.max_connections == 0 {
+            anyhow::bail!("最大连接数必须大于 0");
+        }
+        let limits = &config.limits;
+        if limits.header_read_timeout_ms == 0
+            || limits.keep_alive_timeout_secs == 0
+            || limits.body_read_timeout_ms == 0
+        {
+            anyhow::bail!("请求头读取、空闲连接与请求体读取超时必须大于 0");
+        }
+        if !(1..=100).contains(&limits.max_header_count) {
+            anyhow::bail!("请求头数量上限必须在 1 到 100 之间: {}", limits.max_header_count);
+        }
+        // hyper 的读缓冲区不能小于 8KB，HTTP/2 的请求头列表大小为 u32
+        let max_header_size = Self::parse_cache_size(&limits.max_header_size)?;
+        if !(8 * 1024..=u32::MAX as u64).contains(&max_header_size) {
+            anyhow::bail!("请求头大小上限必须在 8kb 到 4gb 之间: {}", limits.max_header_size);
+        }
+        info!("  连接限制: 请求头 {} ms 内读完 (最多 {} 个/{} 字节), 空闲 {} 秒, 请求体读取间隔 {} ms",
+              limits.header_read_timeout_ms, limits.max_header_count, max_header_size,
+              limits.keep_alive_timeout_secs, limits.body_read_timeout_ms);
+
+        let http2 = &config.http2;
+        if http2.max_concurrent_streams == 0 || http2.keep_alive_timeout_secs == 0 {
+            anyhow::bail!("HTTP/2 最大并发流数与 PING 超时必须大于 0");
+        }
+        // RFC 9113 6.9.2：窗口大小不能超过 2^31-1，也不能小于默认的 65535
+        for window in [http2.initial_stream_window_size, http2.initial_connection_window_size] {
+            if !(65535..=i32::MAX as u32).contains(&window) {
+                anyhow::bail!("HTTP/2 窗口大小必须在 65535 到 2147483647 之间: {}", window);
+            }
+        }
+        info!("  HTTP/2: h2c {}, 最大并发流 {}, 窗口 {}/{}{}, PING 间隔 {} 秒",
+              http2.h2c, http2.max_concurrent_streams, http2.initial_stream_window_size,
+              http2.initial_connection_window_size,
+              if http2.adaptive_window { " (自适应)" } else { "" },
+              http2.keep_alive_interval_secs);
+
+        if config.tls.enabled {
+            let tls = &config.tls;
+            if tls.certificates.is_empty() {
+                anyhow::bail!("启用 TLS 时至少需要配置一张证书");
+            }
+            for protocol in &tls.alpn {
+                if !matches!(protocol.as_str(), "http/1.1" | "h2") {
+                    anyhow::bail!("不支持的 ALPN 协议: {}，可选 http/1.1 或 h2", protocol);
+                }
+            }
+            if tls.port == config.server.port || tls.redirect_port == Some(config.server.port) {
+                anyhow::bail!("TLS 与重定向端口不能与 server.port 相同");
+            }
+            if tls.redirect_port == Some(tls.port) {
+                anyhow::bail!("重定向端口不能与 TLS 端口相同");
+            }
+            if tls.handshake_timeout_ms == 0 {
+                anyhow::bail!("TLS 握手超时必须大于 0");
+            }
+            crate::tls::CertStore::load(&tls.certificates).context("TLS 证书加载失败")?;
+
+            info!("  TLS: 端口 {}, 最低版本 {:?}, ALPN {:?}, 证书 {} 张",
+                  tls.port, tls.min_version, tls.alpn, tls.certificates.len());
+            for cert in &tls.certificates {
+                info!("    证书: {} (OCSP {:?}, 域名 {:?})", cert.cert.display(), cert.ocsp, cert.server_names);
+            }
+            if let Some(port) = tls.redirect_port {
+                info!("  HTTP 重定向: 端口 {} -> https", port);
+            }
+        }
+
+        if config.http3.enabled {
+            if !config.tls.enabled {
+                anyhow::bail!("启用 HTTP/3 时必须同时启用 TLS");
+            }
+            if config.http3.max_concurrent_streams == 0 || config.http3.idle_timeout_secs == 0 {
+                anyhow::bail!("HTTP/3 最大并发流数与空闲超时必须大于 0");
+            }
+            info!("  HTTP/3: UDP 端口 {}, 最大并发流 {}, 空闲超时 {} 秒, Alt-Svc 有效期 {} 秒",
+                  config.http3.port.unwrap_or(config.tls.port), config.http3.max_concurrent_streams,
+                  config.http3.idle_timeout_secs, config.http3.alt_svc_max_age_secs);
+        }
+
+        // 上游客户端按名称区分，名称必须唯一
+        let mut names = std::collections::HashSet::new();
+        for api in &config.api {
+            if !names.insert(api.name.as_str()) {
+                anyhow::bail!("API配置名称重复: {}", api.name);
+            }
+            if !api.to.is_empty() && !api.upstreams.is_empty() {
+                anyhow::bail!("API {} 只能配置 to 或 upstreams 其中之一", api.name);
+            }
+            let targets = api.get_targets();
+            if targets.is_empty() {
+                anyhow::bail!("API {} 没有配置上游 (to 或 upstreams)", api.name);
+            }
+            for target in &targets {
+                let uri: hyper::Uri = target.url.parse()
+                    .with_context(|| format!("无效的代理目标: {}", target.url))?;
+                if uri.scheme().is_none() || uri.authority().is_none() {
+                    anyhow::bail!("代理目标必须是完整的 URL: {}", target.url);
+                }
+                if !matches!(uri.scheme_str(), Some("http") | Some("https")) {
+                    anyhow::bail!("代理目标只支持 http 和 https: {}", target.url);
+                }
+                if target.weight == 0 || target.weight > MAX_WEIGHT {
+                    anyhow::bail!("上游权重必须在 1 到 {} 之间: {}", MAX_WEIGHT, target.url);
+                }
+            }
+            if api.websocket_idle_timeout_secs == 0 {
+                anyhow::bail!("API {} 的 WebSocket 空闲超时必须大于 0", api.name);
+            }
+            if let Some(health_check) = &api.health_check {
+                if !health_check.path.starts_with('/') {
+                    anyhow::bail!("健康检查路径必须以 / 开头: {}", health_check.path);
+                }
+                if health_check.interval_ms == 0
+                    || health_check.healthy_threshold == 0
+                    || health_check.unhealthy_threshold == 0
+                {
+                    anyhow::bail!("API {} 的健康检查间隔和阈值必须大于 0", api.name);
+                }
+            }
+            if api.outlier_detection.as_ref().is_some_and(|o| o.consecutive_failures == 0) {
+                anyhow::bail!("API {} 的异常检测失败次数必须大于 0", api.name);
+            }
+            if let Some(retry) = &api.retry {
+                if retry.max_attempts == 0 {
+                    anyhow::bail!("API {} 的最大尝试次数必须大于 0", api.name);
+                }
+                Self::parse_cache_size(&retry.max_buffer_size)?;
+            }
+            if let Some(breaker) = &api.circuit_breaker {
+                if breaker.failure_threshold == 0 || breaker.open_ms == 0 {
+                    anyhow::bail!("API {} 的熔断阈值和打开时长必须大于 0", api.name);
+                }
+            }
+            if let Some(tls) = &api.tls {
+                // 提前加载证书与私钥，启动后才发现文件有误会让整条路由不可用
+                crate::tls::client_config(Some(tls), &[])
+                    .with_context(|| format!("API {} 的 TLS 配置无效", api.name))?;
+                if let Some(sni) = &tls.sni {
+                    crate::tls::server_name(sni)?;
+                }
+            }
         }
         
         Ok(config)
     }
     
+    /// 配置文件路径，直接解析的配置没有路径
+    pub fn get_path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    pub fn get_overrides(&self) -> &Overrides {
+        &self.overrides
+    }
+
+    pub fn get_pid_file(&self) -> Option<&PathBuf> {
+        self.server.pid_file.as_ref()
+    }
+
+    /// 监听、连接与缓存结构相关的设置只在启动时生效，重新加载时恢复为运行中的值，
+    /// 返回新配置中被忽略的设置名称
+    pub fn keep_startup_settings(&mut self, running: &Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        keep("server.port", &mut self.server.port, &running.server.port, &mut ignored);
+        keep("server.cache_enabled", &mut self.server.cache_enabled, &running.server.cache_enabled, &mut ignored);
+        keep("server.eviction_policy", &mut self.server.eviction_policy, &running.server.eviction_policy, &mut ignored);
+        keep("server.max_connections", &mut self.server.max_connections, &running.server.max_connections, &mut ignored);
+        keep("server.connection_overflow", &mut self.server.connection_overflow, &running.server.connection_overflow, &mut ignored);
+        keep("server.pid_file", &mut self.server.pid_file, &running.server.pid_file, &mut ignored);
+        keep("static.root_directory", &mut self.static_config.root_directory, &running.static_config.root_directory, &mut ignored);
+        keep("static.watch", &mut self.static_config.watch, &running.static_config.watch, &mut ignored);
+        keep("static.watch_debounce_ms", &mut self.static_config.watch_debounce_ms, &running.static_config.watch_debounce_ms, &mut ignored);
+        keep("admin", &mut self.admin, &running.admin, &mut ignored);
+        keep("limits", &mut self.limits, &running.limits, &mut ignored);
+        keep("tls", &mut self.tls, &running.tls, &mut ignored);
+        keep("http2", &mut self.http2, &running.http2, &mut ignored);
+        keep("http3", &mut self.http3, &running.http3, &mut ignored);
+        ignored
+    }
+
     pub fn get_port(&self) -> u16 {
         self.server.port
     }
@@ -77,6 +837,14 @@ impl Config {
         &self.static_config.error_pages_directory
     }
     
+    pub fn is_watch_enabled(&self) -> bool {
+        self.static_config.watch
+    }
+
+    pub fn get_watch_debounce(&self) -> Duration {
+        Duration::from_millis(self.static_config.watch_debounce_ms)
+    }
+
     pub fn get_max_cache_size(&self) -> Result<u64> {
         Self::parse_cache_size(&self.server.max_cache_size)
     }
@@ -84,15 +852,72 @@ impl Config {
     pub fn is_cache_enabled(&self) -> bool {
         self.server.cache_enabled
     }
+
+    pub fn get_eviction_policy(&self) -> EvictionPolicy {
+        self.server.eviction_policy
+    }
+
+    pub fn get_max_cached_file_size(&self) -> u64 {
+        // 加载时已校验过格式
+        Self::parse_cache_size(&self.server.max_cached_file_size).unwrap_or(10 * 1024 * 1024)
+    }
+
+    pub fn get_large_file_mode(&self) -> LargeFileMode {
+        self.server.large_file_mode
+    }
     
     pub fn get_max_connections(&self) -> usize {
         self.server.max_connections
     }
+
+    pub fn get_shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout_secs)
+    }
+
+    pub fn get_connection_overflow(&self) -> ConnectionOverflow {
+        self.server.connection_overflow
+    }
+
+    pub fn get_limits_config(&self) -> &LimitsConfig {
+        &self.limits
+    }
     
     pub fn get_api_configs(&self) -> &Vec<ApiConfig> {
         &self.api
     }
 
+    /// 启用时返回管理接口的监听地址
+    pub fn get_admin_address(&self) -> Option<std::net::SocketAddr> {
+        if !self.admin.enabled {
+            return None;
+        }
+        // 加载时已校验过格式
+        self.admin.address.parse().ok()
+    }
+
+    pub fn get_http2_config(&self) -> &Http2Config {
+        &self.http2
+    }
+
+    /// 启用 HTTP/3 时返回其配置
+    pub fn get_http3_config(&self) -> Option<&Http3Config> {
+        self.http3.enabled.then_some(&self.http3)
+    }
+
+    /// 启用 TLS 时返回其配置
+    pub fn get_tls_config(&self) -> Option<&TlsConfig> {
+        self.tls.enabled.then_some(&self.tls)
+    }
+
+    pub fn get_compression(&self) -> &CompressionConfig {
+        &self.compression
+    }
+
+    pub fn get_compression_min_size(&self) -> u64 {
+        // 加载时已校验过格式
+        Self::parse_cache_size(&self.compression.min_size).unwrap_or(0)
+    }
+
     fn parse_cache_size(value: &str) -> Result<u64> {
         let value = value.to_lowercase();
         
@@ -126,4 +951,74 @@ impl Config {
                 .with_context(|| format!("无效的缓存大小: {}", value))
         }
     }
-}
\ No newline at end of file
+}
+
+// 按序列化结果比较，不同时恢复为运行中的值并记录设置名称
+fn keep<T: Serialize + Clone>(name: &'static str, value: &mut T, running: &T, ignored: &mut Vec<&'static str>) {
+    if serde_json::to_value(&*value).ok() != serde_json::to_value(running).ok() {
+        *value = running.clone();
+        ignored.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+[server]
+port = 0
+name = "RouterWay"
+max_cache_size = "1mb"
+cache_enabled = false
+max_connections = 100
+
+[static]
+root_directory = "Public"
+error_pages_directory = "Public/Errors"
+watch = false
+
+[[api]]
+name = "API"
+from = "/api"
+"#;
+
+    fn parse(api: &str) -> Result<Config> {
+        Config::parse(&format!("{}{}", BASE, api))
+    }
+
+    #[test]
+    fn validates_upstream_targets() {
+        assert!(parse("to = \"http://a\"").is_ok());
+        assert!(parse("upstreams = [{ url = \"http://a\", weight = 1000 }]").is_ok());
+
+        let invalid = [
+            "",
+            "to = \"http://a\"\nupstreams = [{ url = \"http://b\", weight = 1 }]",
+            "upstreams = [{ url = \"http://a\", weight = 0 }]",
+            "upstreams = [{ url = \"http://a\", weight = 1001 }]",
+            "upstreams = [{ url = \"http://a\", weight = 4294967295 }]",
+        ];
+        for api in invalid {
+            assert!(parse(api).is_err(), "{}", api);
+        }
+    }
+
+    #[test]
+    fn applies_command_line_overrides() {
+        let content = format!("{}to = \"http://a\"", BASE);
+        let overrides = Overrides {
+            port: Some(9000),
+            root_directory: Some(PathBuf::from("/srv/www")),
+        };
+        let config = Config::parse_with(&content, overrides).unwrap();
+        assert_eq!(config.get_port(), 9000);
+        assert_eq!(config.get_root_directory(), &PathBuf::from("/srv/www"));
+        assert_eq!(config.get_overrides().port, Some(9000));
+
+        // 未覆盖的设置保留配置文件中的值
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(config.get_port(), 0);
+        assert_eq!(config.get_root_directory(), &PathBuf::from("Public"));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::tls;
+use hyper::client::connect::{Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::http::uri::Scheme;
+use hyper::service::Service;
+use hyper::Uri;
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// 同时支持 http 与 https 上游的连接器
+///
+/// TCP 连接仍由 HttpConnector 建立（沿用连接超时等设置），https 地址再在其上完成 TLS 握手。
+#[derive(Clone)]
+pub struct HttpsConnector {
+    http: HttpConnector,
+    tls: TlsConnector,
+    // 覆盖握手时的 SNI，未配置时使用 URI 中的主机名
+    server_name: Option<ServerName<'static>>,
+}
+
+impl HttpsConnector {
+    pub fn new(
+        mut http: HttpConnector,
+        config: Arc<ClientConfig>,
+        server_name: Option<ServerName<'static>>,
+    ) -> Self {
+        http.enforce_http(false);
+        Self {
+            http,
+            tls: TlsConnector::from(config),
+            server_name,
+        }
+    }
+}
+
+impl Service<Uri> for HttpsConnector {
+    type Response = MaybeTlsStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<MaybeTlsStream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let is_https = uri.scheme() == Some(&Scheme::HTTPS);
+        let server_name = match (&self.server_name, uri.host()) {
+            (Some(name), _) => Ok(name.clone()),
+            (None, Some(host)) => tls::server_name(host).map_err(BoxError::from),
+            (None, None) => Err(BoxError::from("上游地址缺少主机名")),
+        };
+        let connecting = self.http.call(uri);
+        let tls = self.tls.clone();
+
+        Box::pin(async move {
+            let tcp = connecting.await?;
+            if !is_https {
+                return Ok(MaybeTlsStream::Plain(tcp));
+            }
+            let stream = tls.connect(server_name?, tcp).await?;
+            Ok(MaybeTlsStream::Tls(Box::new(stream)))
+        })
+    }
+}
+
+/// 明文或 TLS 的上游连接
+pub enum MaybeTlsStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Connection for MaybeTlsStream {
+    fn connected(&self) -> Connected {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.connected(),
+            MaybeTlsStream::Tls(stream) => {
+                let (tcp, session) = stream.get_ref();
+                // 告知 hyper 经 ALPN 协商出了 h2，该连接改用 HTTP/2
+                if session.alpn_protocol() == Some(b"h2") {
+                    tcp.connected().negotiated_h2()
+                } else {
+                    tcp.connected()
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for MaybeTlsStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for MaybeTlsStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.is_write_vectored(),
+            MaybeTlsStream::Tls(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// 计数器上限，与 Caffeine 一样使用 4 bit 的取值范围
+const MAX_FREQUENCY: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    #[default]
+    #[serde(rename = "lru")]
+    Lru,
+    #[serde(rename = "lfu")]
+    Lfu,
+    #[serde(rename = "w-tinylfu", alias = "wtinylfu")]
+    WTinyLfu,
+}
+
+/// 淘汰顺序的排序值，越小越先被淘汰
+pub type Rank = (u64, u64);
+
+/// 按策略计算条目的排序值；访问次数与访问时刻只增不减，排序值同样只增不减
+pub fn rank(policy: EvictionPolicy, access_count: u64, last_tick: u64) -> Rank {
+    match policy {
+        EvictionPolicy::Lfu => (access_count, last_tick),
+        // W-TinyLFU 的窗口区与主区内部都按 LRU 顺序淘汰
+        EvictionPolicy::Lru | EvictionPolicy::WTinyLfu => (last_tick, 0),
+    }
+}
+
+/// 按排序值保存的淘汰队列
+///
+/// 命中缓存时只更新条目上的原子计数，不动队列；队列中记录的排序值因此只是下限，
+/// 取队首时与条目当前的排序值比较，过期的重新入队，直到队首的记录与实际一致。
+#[derive(Default)]
+pub struct EvictionQueue {
+    order: BTreeSet<(Rank, String)>,
+    ranks: HashMap<String, Rank>,
+}
+
+impl EvictionQueue {
+    pub fn insert(&mut self, key: String, rank: Rank) {
+        self.remove(&key);
+        self.order.insert((rank, key.clone()));
+        self.ranks.insert(key, rank);
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        match self.ranks.remove(key) {
+            Some(rank) => {
+                self.order.remove(&(rank, key.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.ranks.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    /// 返回最该淘汰的键，`current` 给出条目当前的排序值，条目已不存在时返回 None
+    pub fn peek(&mut self, current: impl Fn(&str) -> Option<Rank>) -> Option<String> {
+        loop {
+            let (rank, key) = self.order.first()?.clone();
+            match current(&key) {
+                Some(actual) if actual == rank => return Some(key),
+                Some(actual) => self.insert(key, actual),
+                None => {
+                    self.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// 按当前排序值重建队列，排序值被调小（例如频率老化）后使用
+    pub fn rebuild(&mut self, current: impl Fn(&str) -> Option<Rank>) {
+        let keys: Vec<String> = self.ranks.keys().cloned().collect();
+        self.order.clear();
+        self.ranks.clear();
+        for key in keys {
+            if let Some(rank) = current(&key) {
+                self.insert(key, rank);
+            }
+        }
+    }
+}
+
+/// Count-Min Sketch 频率估计，供 W-TinyLFU 的准入过滤使用
+///
+/// 计数总次数达到采样上限后所有计数器减半，使历史热点逐渐老化。
+pub struct FrequencySketch {
+    table: Box<[AtomicU8]>,
+    mask: usize,
+    additions: AtomicU64,
+    sample_size: u64,
+    hasher: RandomState,
+}
+
+impl FrequencySketch {
+    pub fn new(expected_entries: usize) -> Self {
+        let width = expected_entries.clamp(256, 1 << 20).next_power_of_two();
+        let table = (0..width).map(|_| AtomicU8::new(0)).collect();
+        Self {
+            table,
+            mask: width - 1,
+            additions: AtomicU64::new(0),
+            sample_size: width as u64 * 10,
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn indexes(&self, key: &str) -> [usize; 4] {
+        let hash = self.hasher.hash_one(key);
+        let mut indexes = [0; 4];
+        for (i, index) in indexes.iter_mut().enumerate() {
+            let seeded = hash.wrapping_mul(SEEDS[i]).rotate_left(16 * i as u32);
+            *index = (seeded as usize) & self.mask;
+        }
+        indexes
+    }
+
+    pub fn increment(&self, key: &str) {
+        for index in self.indexes(key) {
+            let _ = self.table[index].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                (v < MAX_FREQUENCY).then_some(v + 1)
+            });
+        }
+
+        if self.additions.fetch_add(1, Ordering::Relaxed) + 1 >= self.sample_size {
+            self.reset();
+        }
+    }
+
+    pub fn frequency(&self, key: &str) -> u8 {
+        self.indexes(key)
+            .into_iter()
+            .map(|index| self.table[index].load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+
+    // 老化：计数器减半，并发下允许少量误差
+    fn reset(&self) {
+        self.additions.store(0, Ordering::Relaxed);
+        for counter in self.table.iter() {
+            let value = counter.load(Ordering::Relaxed);
+            counter.store(value >> 1, Ordering::Relaxed);
+        }
+    }
+}
+
+const SEEDS: [u64; 4] = [
+    0xc3a5_c85c_97cb_3127,
+    0xb492_b66f_be98_f273,
+    0x9ae1_6a3b_2f90_404f,
+    0xcbf2_9ce4_8422_2325,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn queue_reorders_stale_ranks_lazily() {
+        let mut queue = EvictionQueue::default();
+        let mut ranks: HashMap<&str, Rank> = HashMap::new();
+        for (i, key) in ["a", "b", "c"].into_iter().enumerate() {
+            ranks.insert(key, (i as u64, 0));
+            queue.insert(key.to_string(), (i as u64, 0));
+        }
+
+        // a 在入队后被访问，排序值变大，取队首时应跳过
+        ranks.insert("a", (10, 0));
+        assert_eq!(
+            queue.peek(|key| ranks.get(key).copied()).as_deref(),
+            Some("b")
+        );
+
+        // 已不存在的条目从队列中丢弃
+        ranks.remove("b");
+        assert_eq!(
+            queue.peek(|key| ranks.get(key).copied()).as_deref(),
+            Some("c")
+        );
+        assert_eq!(queue.len(), 2);
+
+        assert!(queue.remove("c"));
+        assert_eq!(
+            queue.peek(|key| ranks.get(key).copied()).as_deref(),
+            Some("a")
+        );
+        assert!(queue.remove("a"));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn sketch_estimates_and_ages_frequencies() {
+        let sketch = FrequencySketch::new(256);
+        for _ in 0..8 {
+            sketch.increment("hot");
+        }
+        sketch.increment("cold");
+        assert!(sketch.frequency("hot") >= 8);
+        assert!(sketch.frequency("cold") < sketch.frequency("hot"));
+
+        sketch.reset();
+        assert_eq!(sketch.frequency("hot"), 4);
+    }
+}
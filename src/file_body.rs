@@ -0,0 +1,289 @@
+use crate::range::{self, ByteRange};
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use hyper::Body;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, Take};
+use tokio_util::io::ReaderStream;
+
+// 流式读取时每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// 超过缓存阈值的大文件的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LargeFileMode {
+    // 分块读取，hyper 写不出去时不会继续读，天然具备背压
+    #[default]
+    #[serde(rename = "stream")]
+    Stream,
+    // 内存映射，页面由内核按需换入并计入页缓存而非进程堆
+    #[serde(rename = "mmap")]
+    Mmap,
+}
+
+/// 响应体的数据来源：内存中的内容或磁盘上的大文件
+pub enum FileSource {
+    Memory(Bytes),
+    Disk {
+        path: PathBuf,
+        len: u64,
+        mode: LargeFileMode,
+    },
+}
+
+enum Part {
+    Bytes(Bytes),
+    Segment(ByteRange),
+}
+
+impl FileSource {
+    pub fn len(&self) -> u64 {
+        match self {
+            FileSource::Memory(content) => content.len() as u64,
+            FileSource::Disk { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub async fn full(self) -> io::Result<Body> {
+        match self {
+            FileSource::Memory(content) => Ok(Body::from(content)),
+            FileSource::Disk { path, len, mode: LargeFileMode::Stream } => {
+                Ok(Body::wrap_stream(open_segment(&path, 0, len).await?))
+            }
+            FileSource::Disk { path, len, mode: LargeFileMode::Mmap } => {
+                Ok(Body::from(map_file(path, len).await?))
+            }
+        }
+    }
+
+    pub async fn range(self, range: ByteRange) -> io::Result<Body> {
+        match self {
+            FileSource::Memory(content) => Ok(Body::from(
+                content.slice(range.start as usize..=range.end as usize),
+            )),
+            FileSource::Disk { path, mode: LargeFileMode::Stream, .. } => Ok(Body::wrap_stream(
+                open_segment(&path, range.start, range.len()).await?,
+            )),
+            FileSource::Disk { path, len, mode: LargeFileMode::Mmap } => {
+                let mapped = map_file(path, len).await?;
+                Ok(Body::from(mapped.slice(range.start as usize..=range.end as usize)))
+            }
+        }
+    }
+
+    /// 构建 multipart/byteranges 响应体，同时返回其总长度
+    ///
+    /// 内存与内存映射的内容直接切片引用，流式模式下各分段按顺序打开文件读取。
+    pub async fn multipart(
+        self,
+        ranges: &[ByteRange],
+        mime_type: &str,
+        boundary: &str,
+    ) -> io::Result<(Body, u64)> {
+        let total = self.len();
+        let (content, path) = match self {
+            FileSource::Memory(content) => (Some(content), None),
+            FileSource::Disk { path, len, mode: LargeFileMode::Mmap } => {
+                (Some(map_file(path, len).await?), None)
+            }
+            FileSource::Disk { path, mode: LargeFileMode::Stream, .. } => (None, Some(path)),
+        };
+
+        let mut length = 0;
+        let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+        for range in ranges {
+            let header = Bytes::from(range::part_header(boundary, mime_type, range, total));
+            length += header.len() as u64 + range.len();
+            parts.push(Part::Bytes(header));
+            parts.push(match &content {
+                Some(content) => {
+                    Part::Bytes(content.slice(range.start as usize..=range.end as usize))
+                }
+                None => Part::Segment(*range),
+            });
+        }
+        let closing = Bytes::from(range::closing_delimiter(boundary));
+        length += closing.len() as u64;
+        parts.push(Part::Bytes(closing));
+
+        let body = stream::iter(parts)
+            .then(move |part| {
+                let path = path.clone();
+                async move {
+                    match (part, path) {
+                        (Part::Bytes(bytes), _) => stream::once(async move { Ok(bytes) }).boxed(),
+                        (Part::Segment(range), Some(path)) => {
+                            match open_segment(&path, range.start, range.len()).await {
+                                Ok(segment) => segment.boxed(),
+                                Err(e) => stream::once(async move { Err(e) }).boxed(),
+                            }
+                        }
+                        (Part::Segment(_), None) => stream::empty().boxed(),
+                    }
+                }
+            })
+            .flatten();
+
+        Ok((Body::wrap_stream(body), length))
+    }
+}
+
+async fn open_segment(path: &Path, start: u64, len: u64) -> io::Result<ReaderStream<Take<File>>> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    Ok(ReaderStream::with_capacity(file.take(len), CHUNK_SIZE))
+}
+
+// `len` 为构建响应头时取得的文件大小，区间与 Content-Length 都以它为准
+async fn map_file(path: PathBuf, len: u64) -> io::Result<Bytes> {
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)?;
+        // SAFETY: 映射期间文件被截断会导致 SIGBUS，静态资源应通过写临时文件再 rename 的方式原子替换
+        let mapped = unsafe { Mmap::map(&file)? };
+        // 取得大小之后文件被截断或追加时，按原大小切片会越界或与 Content-Length 不符
+        if mapped.len() as u64 != len {
+            return Err(io::Error::other(format!(
+                "文件大小已变化: {} ({} -> {} 字节)",
+                path.display(),
+                len,
+                mapped.len()
+            )));
+        }
+        Ok(Bytes::from_owner(mapped))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LEN: u64 = 1000;
+
+    fn content() -> Vec<u8> {
+        (0..LEN).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn write_file() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.bin");
+        std::fs::write(&path, content()).unwrap();
+        (dir, path)
+    }
+
+    fn sources(path: &Path) -> Vec<FileSource> {
+        let disk = |mode| FileSource::Disk {
+            path: path.to_path_buf(),
+            len: LEN,
+            mode,
+        };
+        vec![
+            FileSource::Memory(Bytes::from(content())),
+            disk(LargeFileMode::Stream),
+            disk(LargeFileMode::Mmap),
+        ]
+    }
+
+    async fn read(body: io::Result<Body>) -> Bytes {
+        hyper::body::to_bytes(body.unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_full_body_and_single_range() {
+        let (_dir, path) = write_file();
+        let content = content();
+
+        for source in sources(&path) {
+            assert_eq!(read(source.full().await).await, content);
+        }
+        for source in sources(&path) {
+            let body = source.range(ByteRange { start: 10, end: 19 }).await;
+            assert_eq!(read(body).await, content[10..20]);
+        }
+        for source in sources(&path) {
+            let body = source
+                .range(ByteRange {
+                    start: 990,
+                    end: 999,
+                })
+                .await;
+            assert_eq!(read(body).await, content[990..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_multipart_byteranges() {
+        let (_dir, path) = write_file();
+        let content = content();
+        let ranges = [
+            ByteRange { start: 0, end: 4 },
+            ByteRange {
+                start: 100,
+                end: 109,
+            },
+            ByteRange {
+                start: 995,
+                end: 999,
+            },
+        ];
+
+        let mut expected = Vec::new();
+        for range in &ranges {
+            expected
+                .extend_from_slice(range::part_header("b", "text/plain", range, LEN).as_bytes());
+            expected.extend_from_slice(&content[range.start as usize..=range.end as usize]);
+        }
+        expected.extend_from_slice(range::closing_delimiter("b").as_bytes());
+
+        for source in sources(&path) {
+            let (body, length) = source.multipart(&ranges, "text/plain", "b").await.unwrap();
+            assert_eq!(length, expected.len() as u64);
+            assert_eq!(read(Ok(body)).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_mapping_of_resized_file() {
+        let (_dir, path) = write_file();
+        std::fs::write(&path, &content()[..500]).unwrap();
+        let source = || FileSource::Disk {
+            path: path.clone(),
+            len: LEN,
+            mode: LargeFileMode::Mmap,
+        };
+
+        assert!(source().full().await.is_err());
+        assert!(source()
+            .range(ByteRange {
+                start: 900,
+                end: 999
+            })
+            .await
+            .is_err());
+        let ranges = [
+            ByteRange { start: 0, end: 9 },
+            ByteRange {
+                start: 900,
+                end: 999,
+            },
+        ];
+        assert!(source()
+            .multipart(&ranges, "text/plain", "b")
+            .await
+            .is_err());
+
+        // 文件变大同样拒绝，否则完整响应体会超出 Content-Length
+        std::fs::write(&path, vec![0; 2000]).unwrap();
+        assert!(source().full().await.is_err());
+    }
+}
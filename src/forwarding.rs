@@ -0,0 +1,290 @@
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, FORWARDED, HOST, TE, VIA};
+use hyper::http::uri::Authority;
+use hyper::Version;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+// RFC 9110 7.6.1 规定的逐跳头，以及常见的非标准逐跳头
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+
+/// 转发给上游的 Host 头
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HostHeader {
+    // 保留客户端发送的 Host
+    #[default]
+    #[serde(rename = "preserve")]
+    Preserve,
+    // 改写为上游地址中的主机名和端口
+    #[serde(rename = "rewrite")]
+    Rewrite,
+}
+
+/// 请求来源信息，用于生成转发头
+pub struct ClientInfo<'a> {
+    pub ip: IpAddr,
+    pub proto: &'a str,
+    pub version: Version,
+}
+
+/// 移除逐跳头以及 Connection 头中列出的头
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(name);
+    }
+}
+
+/// 准备发往上游的请求头：移除逐跳头，追加转发信息，并按配置处理 Host
+pub fn prepare_request(
+    headers: &mut HeaderMap,
+    client: &ClientInfo,
+    host_header: HostHeader,
+    upstream: &Authority,
+) {
+    // gRPC 依赖 `TE: trailers`，本代理能转发 trailers，因此保留这一项
+    let accepts_trailers = headers
+        .get_all(TE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("trailers"));
+    strip_hop_by_hop(headers);
+    if accepts_trailers {
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+    }
+
+    let original_host = headers
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let client_ip = client.ip.to_string();
+
+    // X-Forwarded-For 在已有值后追加，保留前面代理记录的链路
+    let forwarded_for = match headers.get(X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+        Some(existing) if !existing.trim().is_empty() => format!("{}, {}", existing, client_ip),
+        _ => client_ip,
+    };
+    insert(headers, X_FORWARDED_FOR, &forwarded_for);
+    insert(headers, X_FORWARDED_PROTO, client.proto);
+    if let Some(host) = &original_host {
+        insert(headers, X_FORWARDED_HOST, host);
+    }
+
+    // RFC 7239 Forwarded，同样追加到已有元素之后
+    let mut element = format!("for={}", forwarded_node(client.ip));
+    if let Some(host) = &original_host {
+        element.push_str(&format!(";host={}", quote_if_needed(host)));
+    }
+    element.push_str(&format!(";proto={}", client.proto));
+    append_list(headers, FORWARDED, &element);
+
+    append_via(headers, client.version);
+
+    if host_header == HostHeader::Rewrite {
+        insert(headers, HOST.as_str(), upstream.as_str());
+    }
+}
+
+/// 处理上游响应头：移除逐跳头并追加 Via
+pub fn prepare_response(headers: &mut HeaderMap, version: Version) {
+    strip_hop_by_hop(headers);
+    append_via(headers, version);
+}
+
+fn append_via(headers: &mut HeaderMap, version: Version) {
+    let protocol = match version {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => "1.1",
+    };
+    append_list(headers, VIA, &format!("{} RouterWay", protocol));
+}
+
+fn append_list(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    let combined = match headers.get(&name).and_then(|v| v.to_str().ok()) {
+        Some(existing) if !existing.trim().is_empty() => format!("{}, {}", existing, value),
+        _ => value.to_string(),
+    };
+    insert(headers, name.as_str(), &combined);
+}
+
+fn insert(headers: &mut HeaderMap, name: &str, value: &str) {
+    if let (Ok(name), Ok(value)) = (
+        HeaderName::from_bytes(name.as_bytes()),
+        HeaderValue::from_str(value),
+    ) {
+        headers.insert(name, value);
+    }
+}
+
+// IPv6 地址需要加方括号并加引号
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("\"[{}]\"", ip),
+    }
+}
+
+fn quote_if_needed(value: &str) -> String {
+    let is_token = value
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    if is_token {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn client(ip: &str) -> ClientInfo<'static> {
+        ClientInfo {
+            ip: ip.parse().unwrap(),
+            proto: "https",
+            version: Version::HTTP_11,
+        }
+    }
+
+    fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        headers.get(name).map(|v| v.to_str().unwrap())
+    }
+
+    #[test]
+    fn strips_hop_by_hop_and_connection_listed_headers() {
+        let mut map = headers(&[
+            ("connection", "keep-alive, X-Custom-Hop"),
+            ("keep-alive", "timeout=5"),
+            ("x-custom-hop", "1"),
+            ("transfer-encoding", "chunked"),
+            ("upgrade", "websocket"),
+            ("proxy-authorization", "Basic Zm9v"),
+            ("content-type", "text/plain"),
+        ]);
+        strip_hop_by_hop(&mut map);
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(get(&map, "content-type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn appends_forwarding_headers_to_existing_chain() {
+        let mut map = headers(&[
+            ("host", "example.com:8443"),
+            ("x-forwarded-for", "203.0.113.7"),
+            ("forwarded", "for=203.0.113.7"),
+            ("via", "1.1 edge"),
+        ]);
+        let upstream: Authority = "127.0.0.1:9000".parse().unwrap();
+        prepare_request(
+            &mut map,
+            &client("198.51.100.2"),
+            HostHeader::Preserve,
+            &upstream,
+        );
+
+        assert_eq!(
+            get(&map, "x-forwarded-for"),
+            Some("203.0.113.7, 198.51.100.2")
+        );
+        assert_eq!(get(&map, "x-forwarded-proto"), Some("https"));
+        assert_eq!(get(&map, "x-forwarded-host"), Some("example.com:8443"));
+        assert_eq!(
+            get(&map, "forwarded"),
+            Some("for=203.0.113.7, for=198.51.100.2;host=\"example.com:8443\";proto=https")
+        );
+        assert_eq!(get(&map, "via"), Some("1.1 edge, 1.1 RouterWay"));
+        assert_eq!(get(&map, "host"), Some("example.com:8443"));
+    }
+
+    #[test]
+    fn quotes_ipv6_and_rewrites_host() {
+        let mut map = headers(&[("host", "example.com")]);
+        let upstream: Authority = "backend:9000".parse().unwrap();
+        prepare_request(
+            &mut map,
+            &client("2001:db8::1"),
+            HostHeader::Rewrite,
+            &upstream,
+        );
+
+        assert_eq!(get(&map, "x-forwarded-for"), Some("2001:db8::1"));
+        assert_eq!(
+            get(&map, "forwarded"),
+            Some("for=\"[2001:db8::1]\";host=example.com;proto=https")
+        );
+        assert_eq!(get(&map, "x-forwarded-host"), Some("example.com"));
+        assert_eq!(get(&map, "host"), Some("backend:9000"));
+    }
+
+    #[test]
+    fn keeps_te_trailers_only() {
+        let mut map = headers(&[("te", "gzip, trailers"), ("connection", "te")]);
+        let upstream: Authority = "backend:9000".parse().unwrap();
+        prepare_request(
+            &mut map,
+            &client("127.0.0.1"),
+            HostHeader::Preserve,
+            &upstream,
+        );
+        assert_eq!(get(&map, "te"), Some("trailers"));
+
+        let mut map = headers(&[("te", "gzip")]);
+        prepare_request(
+            &mut map,
+            &client("127.0.0.1"),
+            HostHeader::Preserve,
+            &upstream,
+        );
+        assert!(map.get("te").is_none());
+    }
+
+    #[test]
+    fn response_drops_hop_by_hop_and_adds_via() {
+        let mut map = headers(&[("connection", "close"), ("trailer", "grpc-status")]);
+        prepare_response(&mut map, Version::HTTP_2);
+
+        assert!(map.get("connection").is_none());
+        assert!(map.get("trailer").is_none());
+        assert_eq!(get(&map, "via"), Some("2 RouterWay"));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::HealthCheckConfig;
+use crate::upstream::{Upstream, UpstreamClient, UpstreamPool};
+use hyper::header::USER_AGENT;
+use hyper::{Body, Request, Uri};
+use std::sync::{Arc, Weak};
+use tokio::time::{interval, timeout, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// 为配置了健康检查的路由启动主动探测，每个上游一个任务，上游随配置重新加载被移除后任务结束
+pub fn spawn<'a>(pools: impl IntoIterator<Item = &'a UpstreamPool>) {
+    for pool in pools {
+        let config = match pool.health_check() {
+            Some(config) => config,
+            None => continue,
+        };
+
+        for upstream in pool.upstreams() {
+            let client = pool.client().clone();
+            let upstream = Arc::downgrade(upstream);
+            let config = config.clone();
+            tokio::spawn(probe_loop(client, upstream, config));
+        }
+
+        info!("🩺 健康检查已启动: {} ({} 个上游, 路径 {})",
+              pool.name(), pool.upstreams().len(), config.path);
+    }
+}
+
+async fn probe_loop(client: UpstreamClient, upstream: Weak<Upstream>, config: HealthCheckConfig) {
+    let uri = match upstream.upgrade() {
+        Some(upstream) => match upstream.probe_uri(&config.path) {
+            Some(uri) => uri,
+            None => {
+                warn!("无法构建健康检查地址: {}{}", upstream.url(), config.path);
+                return;
+            }
+        },
+        None => return,
+    };
+
+    let mut ticker = interval(config.get_interval());
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if upstream.strong_count() == 0 {
+            return;
+        }
+        let success = probe(&client, &uri, &config).await;
+        match upstream.upgrade() {
+            Some(upstream) => upstream.record_probe(success, &config),
+            None => return,
+        }
+    }
+}
+
+async fn probe(client: &UpstreamClient, uri: &Uri, config: &HealthCheckConfig) -> bool {
+    let request = match Request::get(uri.clone())
+        .header(USER_AGENT, "RouterWay-HealthCheck")
+        .body(Body::empty())
+    {
+        Ok(request) => request,
+        Err(_) => return false,
+    };
+
+    match timeout(config.get_timeout(), client.request(request)).await {
+        Ok(Ok(response)) => {
+            let status = response.status().as_u16();
+            let success = config.expected_status.contains(&status);
+            if !success {
+                debug!("健康检查状态码不符 {}: {}", uri, status);
+            }
+            success
+        }
+        Ok(Err(e)) => {
+            debug!("健康检查请求失败 {}: {}", uri, e);
+            false
+        }
+        Err(_) => {
+            debug!("健康检查超时: {}", uri);
+            false
+        }
+    }
+}
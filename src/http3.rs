@@ -0,0 +1,271 @@
+use crate::config::{Http3Config, LimitsConfig};
+use crate::limits::ConnectionLimiter;
+use crate::shutdown::Shutdown;
+use crate::tls::{self, CertStore};
+use anyhow::{Context, Result};
+use bytes::{Buf, Bytes};
+use futures_util::{stream, FutureExt, StreamExt};
+use h3::server::RequestStream;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH};
+use hyper::{Body, Method, Request, Response, Uri, Version};
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::{Endpoint, EndpointConfig, IdleTimeout, TokioRuntime, TransportConfig, VarInt};
+use std::future::Future;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+type SendStream = RequestStream<h3_quinn::SendStream<Bytes>, Bytes>;
+type RecvStream = RequestStream<h3_quinn::RecvStream, Bytes>;
+
+/// 在已绑定的 UDP 套接字上创建 QUIC 端点，证书与 HTTPS 监听共用
+pub fn endpoint(socket: UdpSocket, config: &Http3Config, certs: Arc<CertStore>) -> Result<Endpoint> {
+    let crypto = QuicServerConfig::try_from(tls::quic_server_config(certs)?)
+        .context("无法创建 QUIC 的 TLS 配置")?;
+
+    let mut transport = TransportConfig::default();
+    transport.max_concurrent_bidi_streams(VarInt::from_u32(config.max_concurrent_streams));
+    transport.max_idle_timeout(Some(
+        IdleTimeout::try_from(Duration::from_secs(config.idle_timeout_secs))
+            .context("QUIC 空闲超时过大")?,
+    ));
+
+    let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+    server_config.transport_config(Arc::new(transport));
+
+    Endpoint::new(
+        EndpointConfig::default(),
+        Some(server_config),
+        socket,
+        Arc::new(TokioRuntime),
+    )
+    .context("无法创建 QUIC 端点")
+}
+
+/// 接受 HTTP/3 连接，把每个请求转换为 hyper 请求交给 `handler` 处理
+///
+/// 请求头大小与读取时限按 `limits` 限制；请求体在读取时才从流中接收，读取超时由 `handler` 处理。
+/// 开始关闭后不再接受新连接，已有连接发送 GOAWAY 并在处理完已接受的请求后关闭。
+pub async fn serve<H, F>(
+    endpoint: Endpoint,
+    limiter: ConnectionLimiter,
+    limits: LimitsConfig,
+    shutdown: Shutdown,
+    handler: H,
+) -> Result<()>
+where
+    H: Fn(Request<Body>, SocketAddr) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+{
+    info!("⚡ HTTP/3 监听地址: udp://{}", endpoint.local_addr()?);
+    let header_read_timeout = limits.get_header_read_timeout();
+    let mut builder = h3::server::builder();
+    builder.max_field_section_size(limits.get_max_header_size() as u64);
+    let builder = Arc::new(builder);
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = async {
+                let queued = limiter.wait().await;
+                (queued, endpoint.accept().await)
+            } => accepted,
+            _ = shutdown.triggered() => break,
+        };
+        let (queued, incoming) = match accepted {
+            (queued, Some(incoming)) => (queued, incoming),
+            (_, None) => break,
+        };
+        let permit = match queued.or_else(|| limiter.try_acquire()) {
+            Some(permit) => permit,
+            None => {
+                debug!("连接数已达上限，拒绝 QUIC 连接: {}", incoming.remote_address());
+                incoming.refuse();
+                continue;
+            }
+        };
+
+        let handler = handler.clone();
+        let builder = Arc::clone(&builder);
+        let shutdown = shutdown.clone();
+        let guard = shutdown.track();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let _guard = guard;
+            let connection = match incoming.await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    debug!("QUIC 握手失败: {}", e);
+                    return;
+                }
+            };
+            let remote_addr = connection.remote_address();
+
+            let mut h3_conn = match builder.build(h3_quinn::Connection::new(connection)).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    debug!("HTTP/3 连接建立失败 ({}): {}", remote_addr, e);
+                    return;
+                }
+            };
+
+            let mut closing = false;
+            loop {
+                let accepted = tokio::select! {
+                    accepted = h3_conn.accept() => accepted,
+                    _ = shutdown.triggered(), if !closing => {
+                        closing = true;
+                        if let Err(e) = h3_conn.shutdown(0).await {
+                            debug!("HTTP/3 连接关闭失败 ({}): {}", remote_addr, e);
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                match accepted {
+                    Ok(Some(resolver)) => {
+                        let handler = handler.clone();
+                        let guard = shutdown.track();
+                        tokio::spawn(async move {
+                            let _guard = guard;
+                            let resolved = tokio::time::timeout(header_read_timeout, resolver.resolve_request());
+                            let (req, stream) = match resolved.await {
+                                Ok(Ok(resolved)) => resolved,
+                                Ok(Err(e)) => {
+                                    debug!("HTTP/3 请求头读取失败 ({}): {}", remote_addr, e);
+                                    return;
+                                }
+                                Err(_) => {
+                                    debug!("HTTP/3 请求头读取超时 ({})", remote_addr);
+                                    return;
+                                }
+                            };
+                            if let Err(e) = handle_stream(req, stream, remote_addr, handler).await {
+                                debug!("HTTP/3 请求处理中断 ({}): {}", remote_addr, e);
+                            }
+                        });
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        if !e.is_h3_no_error() {
+                            debug!("HTTP/3 连接异常关闭 ({}): {}", remote_addr, e);
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    // 拒绝关闭期间到达的新连接
+    endpoint.set_server_config(None);
+    Ok(())
+}
+
+async fn handle_stream<H, F>(
+    req: http1::Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    remote_addr: SocketAddr,
+    handler: H,
+) -> Result<()>
+where
+    H: Fn(Request<Body>, SocketAddr) -> F,
+    F: Future<Output = Response<Body>>,
+{
+    let (mut send, recv) = stream.split();
+
+    let request = match convert_request(req, recv) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("无效的 HTTP/3 请求 ({}): {}", remote_addr, e);
+            let response = http1::Response::builder().status(400).body(())?;
+            send.send_response(response).await?;
+            send.finish().await?;
+            return Ok(());
+        }
+    };
+
+    let response = handler(request, remote_addr).await;
+    send_response(&mut send, response).await
+}
+
+// h3 的请求头使用 http 1.x 类型，转换为 hyper 0.14 的请求；请求体以流的形式读取
+fn convert_request(req: http1::Request<()>, recv: RecvStream) -> Result<Request<Body>> {
+    let (parts, ()) = req.into_parts();
+
+    let mut headers = HeaderMap::with_capacity(parts.headers.len());
+    for (name, value) in &parts.headers {
+        headers.append(
+            HeaderName::from_bytes(name.as_str().as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let body = request_body(recv, content_length)?;
+
+    let mut request = Request::new(body);
+    *request.method_mut() = Method::from_bytes(parts.method.as_str().as_bytes())?;
+    *request.uri_mut() = parts.uri.to_string().parse::<Uri>()?;
+    *request.version_mut() = Version::HTTP_3;
+    *request.headers_mut() = headers;
+    Ok(request)
+}
+
+// 不等待请求体到达：已知没有请求体时给出空 Body，避免转发给上游时变成分块传输；
+// 其余情况在读取时才从流中接收，等待时间由读取方的超时限制
+fn request_body(mut recv: RecvStream, content_length: Option<u64>) -> Result<Body> {
+    if content_length == Some(0) {
+        return Ok(Body::empty());
+    }
+    // 请求头与流结束标志通常一起到达，此时不必等待即可判断
+    let first = match recv.recv_data().now_or_never() {
+        Some(Ok(None)) => return Ok(Body::empty()),
+        Some(Ok(Some(mut chunk))) => Some(chunk.copy_to_bytes(chunk.remaining())),
+        Some(Err(e)) => return Err(e.into()),
+        None => None,
+    };
+
+    let rest = stream::unfold(Some(recv), |recv| async move {
+        let mut recv = recv?;
+        match recv.recv_data().await {
+            Ok(Some(mut chunk)) => Some((Ok(chunk.copy_to_bytes(chunk.remaining())), Some(recv))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    Ok(Body::wrap_stream(stream::iter(first.map(Ok)).chain(rest)))
+}
+
+async fn send_response(send: &mut SendStream, response: Response<Body>) -> Result<()> {
+    let (parts, mut body) = response.into_parts();
+
+    let mut builder = http1::Response::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    send.send_response(builder.body(())?).await?;
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if !chunk.is_empty() {
+            send.send_data(chunk).await?;
+        }
+    }
+    if let Some(trailers) = body.trailers().await? {
+        let mut converted = http1::HeaderMap::with_capacity(trailers.len());
+        for (name, value) in &trailers {
+            converted.append(
+                http1::HeaderName::from_bytes(name.as_str().as_bytes())?,
+                http1::HeaderValue::from_bytes(value.as_bytes())?,
+            );
+        }
+        send.send_trailers(converted).await?;
+    }
+    send.finish().await?;
+    Ok(())
+}
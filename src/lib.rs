@@ -0,0 +1,26 @@
+pub mod admin;
+pub mod balancer;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod cli;
+pub mod compression;
+pub mod conditional;
+pub mod config;
+pub mod connector;
+pub mod eviction;
+pub mod file_body;
+pub mod forwarding;
+pub mod health;
+pub mod http3;
+pub mod limits;
+pub mod pidfile;
+pub mod range;
+pub mod reload;
+pub mod retry;
+pub mod server;
+pub mod shutdown;
+pub mod tls;
+pub mod upgrade;
+pub mod upstream;
+pub mod watcher;
+pub mod websocket;
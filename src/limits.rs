@@ -0,0 +1,447 @@
+use bytes::Bytes;
+use crate::shutdown::ConnectionGuard;
+use crate::upstream::now_millis;
+use futures_util::task::AtomicWaker;
+use hyper::body::{HttpBody, SizeHint};
+use hyper::{Body, HeaderMap};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep, Sleep};
+use tracing::debug;
+
+// 请求头读取超时时直接写给客户端的响应
+const REQUEST_TIMEOUT_RESPONSE: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nServer: RouterWay\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+// 连接数达到上限时写给明文连接的响应
+const SERVICE_UNAVAILABLE_RESPONSE: &[u8] =
+    b"HTTP/1.1 503 Service Unavailable\r\nServer: RouterWay\r\nConnection: close\r\nRetry-After: 1\r\nContent-Length: 0\r\n\r\n";
+
+// HTTP/2 连接前言的开头，h2c 连接不参与 HTTP/1 的超时判断
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0";
+
+/// 连接数达到 max_connections 后对新连接的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConnectionOverflow {
+    // 暂停接受新连接，等待的连接留在内核的 accept 队列中
+    #[default]
+    #[serde(rename = "queue")]
+    Queue,
+    // 接受后立即关闭，明文连接先返回 503
+    #[serde(rename = "reject")]
+    Reject,
+}
+
+/// 所有监听共享的连接数上限
+#[derive(Clone)]
+pub struct ConnectionLimiter {
+    permits: Arc<Semaphore>,
+    overflow: ConnectionOverflow,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_connections: usize, overflow: ConnectionOverflow) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_connections)),
+            overflow,
+        }
+    }
+
+    /// 接受连接前调用：queue 模式下等待空闲名额，reject 模式下立即返回 None
+    pub async fn wait(&self) -> Option<OwnedSemaphorePermit> {
+        match self.overflow {
+            ConnectionOverflow::Queue => Arc::clone(&self.permits).acquire_owned().await.ok(),
+            ConnectionOverflow::Reject => None,
+        }
+    }
+
+    /// 接受连接后调用：没有空闲名额时返回 None，调用方应拒绝该连接
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.permits).try_acquire_owned().ok()
+    }
+
+    /// 按连接数上限接受 TCP 连接，名额为 None 时调用方应拒绝该连接
+    pub async fn accept(
+        &self,
+        listener: &TcpListener,
+    ) -> io::Result<(TcpStream, SocketAddr, Option<OwnedSemaphorePermit>)> {
+        let queued = self.wait().await;
+        let (stream, remote_addr) = listener.accept().await?;
+        Ok((stream, remote_addr, queued.or_else(|| self.try_acquire())))
+    }
+}
+
+/// 拒绝超出上限的 TCP 连接，明文连接会先收到 503
+pub fn reject(mut stream: TcpStream, plain: bool) {
+    tokio::spawn(async move {
+        if plain {
+            let _ = tokio::time::timeout(Duration::from_secs(1), async {
+                let _ = stream.write_all(SERVICE_UNAVAILABLE_RESPONSE).await;
+                let _ = stream.shutdown().await;
+            })
+            .await;
+        }
+    });
+}
+
+/// HTTP/1 连接的超时设置
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionTimeouts {
+    // 从收到请求的第一个字节起读完请求头的时限
+    pub header_read: Duration,
+    // 两个请求之间连接保持空闲的时限
+    pub keep_alive: Duration,
+}
+
+/// 连接上的请求进度，由 `TimedStream` 与请求处理服务共同维护
+pub struct ConnectionActivity {
+    // 已交给服务处理、响应尚未发送完的请求数
+    in_flight: AtomicUsize,
+    // 已开始处理的请求总数，用来判断收到的数据是否属于下一个请求
+    started: AtomicU64,
+    // 升级为 WebSocket 或使用 HTTP/2 的连接不再由 `TimedStream` 计时
+    disabled: AtomicBool,
+    // HTTP/2 连接的空闲超时由 `http2_idle` 判断
+    http2: AtomicBool,
+    // 最近一次请求开始或结束的时间（毫秒时间戳）
+    last_active: AtomicU64,
+    // 请求处理期间挂起的读取，响应结束后唤醒它开始空闲计时
+    reader: AtomicWaker,
+}
+
+impl Default for ConnectionActivity {
+    fn default() -> Self {
+        Self {
+            in_flight: AtomicUsize::new(0),
+            started: AtomicU64::new(0),
+            disabled: AtomicBool::new(false),
+            http2: AtomicBool::new(false),
+            last_active: AtomicU64::new(now_millis()),
+            reader: AtomicWaker::new(),
+        }
+    }
+}
+
+impl ConnectionActivity {
+    /// 开始处理一个请求，返回的 guard 在响应发送完毕时释放
+    pub fn start_request(self: &Arc<Self>) -> RequestGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.started.fetch_add(1, Ordering::SeqCst);
+        self.last_active.store(now_millis(), Ordering::SeqCst);
+        RequestGuard(Arc::clone(self))
+    }
+
+    pub fn disable(&self) {
+        self.disabled.store(true, Ordering::SeqCst);
+    }
+
+    /// 标记为 HTTP/2 连接：不再按 HTTP/1 的方式计时，改由 `http2_idle` 判断空闲
+    pub fn set_http2(&self) {
+        self.http2.store(true, Ordering::SeqCst);
+        self.disable();
+    }
+
+    /// HTTP/2 连接上没有处理中的请求超过 `timeout` 时返回，其他连接不会返回
+    pub async fn http2_idle(&self, timeout: Duration) {
+        let timeout_ms = timeout.as_millis() as u64;
+        loop {
+            let idle_ms = now_millis().saturating_sub(self.last_active.load(Ordering::SeqCst));
+            if idle_ms < timeout_ms {
+                sleep(Duration::from_millis(timeout_ms - idle_ms)).await;
+                continue;
+            }
+            if self.http2.load(Ordering::SeqCst) && self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            sleep(timeout).await;
+        }
+    }
+}
+
+pub struct RequestGuard(Arc<ConnectionActivity>);
+
+impl RequestGuard {
+    /// 把 guard 绑定到响应体上，响应体发送完或被丢弃时请求才算结束
+    pub fn track(self, body: Body) -> TrackedBody {
+        TrackedBody { inner: body, _guard: self }
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.0.last_active.store(now_millis(), Ordering::SeqCst);
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.reader.wake();
+        }
+    }
+}
+
+/// 带有请求进度 guard 的响应体，其余行为与内部的 Body 相同
+pub struct TrackedBody {
+    inner: Body,
+    _guard: RequestGuard,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    // 正在处理请求或已停止计时
+    Busy,
+    // 等待下一个请求
+    Idle,
+    // 已收到部分请求头
+    ReadingHead,
+}
+
+/// 为 HTTP/1 连接加上请求头读取与空闲超时
+///
+/// 只在读取时计时：空闲超时后向 hyper 返回 EOF 正常关闭连接，请求头读取超时则先写出 408。
+/// 连接名额与关闭时等待的在途登记随连接一起释放，升级后的 WebSocket 隧道同样占用它们。
+pub struct TimedStream<S> {
+    inner: S,
+    activity: Arc<ConnectionActivity>,
+    timeouts: ConnectionTimeouts,
+    // 收到新请求的数据时已开始处理的请求数
+    head_started: Option<u64>,
+    first_read: bool,
+    timer: Option<(Phase, Pin<Box<Sleep>>)>,
+    // 正在写出的 408 响应的剩余部分，写完后一直返回 EOF
+    rejecting: Option<&'static [u8]>,
+    _permit: OwnedSemaphorePermit,
+    _guard: ConnectionGuard,
+}
+
+impl<S> TimedStream<S> {
+    pub fn new(
+        inner: S,
+        activity: Arc<ConnectionActivity>,
+        timeouts: ConnectionTimeouts,
+        permit: OwnedSemaphorePermit,
+        guard: ConnectionGuard,
+    ) -> Self {
+        Self {
+            inner,
+            activity,
+            timeouts,
+            head_started: None,
+            first_read: true,
+            timer: None,
+            rejecting: None,
+            _permit: permit,
+            _guard: guard,
+        }
+    }
+
+    fn phase(&self) -> Phase {
+        if self.activity.disabled.load(Ordering::SeqCst)
+            || self.activity.in_flight.load(Ordering::SeqCst) > 0
+        {
+            Phase::Busy
+        } else if self.head_started == Some(self.activity.started.load(Ordering::SeqCst)) {
+            Phase::ReadingHead
+        } else {
+            Phase::Idle
+        }
+    }
+
+    fn on_read(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if std::mem::take(&mut self.first_read) && data.starts_with(&H2_PREFACE[..data.len().min(H2_PREFACE.len())]) {
+            self.activity.set_http2();
+        }
+        if self.activity.in_flight.load(Ordering::SeqCst) == 0 {
+            self.head_started = Some(self.activity.started.load(Ordering::SeqCst));
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> TimedStream<S> {
+    fn poll_reject(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(remaining) = self.rejecting.filter(|r| !r.is_empty()) {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, remaining))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.rejecting = Some(&remaining[written..]);
+        }
+        ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TimedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.rejecting.is_some() {
+            return this.poll_reject(cx);
+        }
+
+        let filled = buf.filled().len();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                this.on_read(&buf.filled()[filled..]);
+                return Poll::Ready(Ok(()));
+            }
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => {}
+        }
+
+        let phase = this.phase();
+        let timeout = match phase {
+            Phase::Busy => {
+                this.timer = None;
+                this.activity.reader.register(cx.waker());
+                return Poll::Pending;
+            }
+            Phase::Idle => this.timeouts.keep_alive,
+            Phase::ReadingHead => this.timeouts.header_read,
+        };
+        // 阶段不变时沿用原来的截止时间，逐字节发送请求头的客户端无法续期
+        if this.timer.as_ref().map(|(armed, _)| *armed) != Some(phase) {
+            this.timer = Some((phase, Box::pin(sleep(timeout))));
+        }
+        if let Some((_, timer)) = this.timer.as_mut() {
+            ready!(timer.as_mut().poll(cx));
+        }
+        this.timer = None;
+
+        if phase == Phase::ReadingHead {
+            debug!("读取请求头超时，返回 408");
+            this.rejecting = Some(REQUEST_TIMEOUT_RESPONSE);
+            return this.poll_reject(cx);
+        }
+        debug!("连接空闲超时，关闭连接");
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimedStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// 消息体读取是否超时，由 `timeout_body` 设置
+#[derive(Clone, Default)]
+pub struct BodyTimeout(Arc<AtomicBool>);
+
+impl BodyTimeout {
+    pub fn is_elapsed(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// 为消息体（请求体或上游响应体）的每次读取加上超时，超时后消息体以错误结束
+///
+/// 由独立任务读取原消息体并转发，trailers 也一并转发（gRPC 的状态在 trailers 中）；
+/// 等待读取方取走数据的时间不计入超时。
+pub fn timeout_body(body: Body, timeout: Duration) -> (Body, BodyTimeout) {
+    let elapsed = BodyTimeout::default();
+    if body.is_end_stream() {
+        return (body, elapsed);
+    }
+
+    let flag = elapsed.clone();
+    let (mut sender, wrapped) = Body::channel();
+    tokio::spawn(async move {
+        let mut body = body;
+        loop {
+            match tokio::time::timeout(timeout, body.data()).await {
+                Ok(Some(Ok(chunk))) => {
+                    // 读取方已放弃消息体
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(Some(Err(_))) => return sender.abort(),
+                Ok(None) => break,
+                Err(_) => {
+                    flag.0.store(true, Ordering::SeqCst);
+                    return sender.abort();
+                }
+            }
+        }
+        match tokio::time::timeout(timeout, body.trailers()).await {
+            Ok(Ok(Some(trailers))) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(_)) => sender.abort(),
+            Err(_) => {
+                flag.0.store(true, Ordering::SeqCst);
+                sender.abort();
+            }
+        }
+    });
+    (wrapped, elapsed)
+}
+
+/// 请求头占用的字节数（名称与值）
+pub fn header_size(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum()
+}
@@ -1,50 +1,99 @@
 use anyhow::{Context, Result};
-use std::sync::Arc;
-use tokio::net::TcpListener;
-use tracing::{error, info, warn};
-use tracing_subscriber;
+use tracing::{error, info};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
 
-mod cache;
-mod config;
-mod server;
+use routerway_server::cli::{Cli, Command, LogFormat};
+use routerway_server::config::Config;
+use routerway_server::pidfile;
+use routerway_server::reload;
+use routerway_server::server::HttpServer;
+use routerway_server::shutdown;
+use routerway_server::upgrade::Handoff;
 
-use server::HttpServer;
+fn main() -> Result<()> {
+    let cli = Cli::parse_args();
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_env_filter("info")
-        .init();
+    match cli.command() {
+        Command::Serve => {
+            // 升级交接用的环境变量须在创建多线程运行时之前读取并清除
+            let handoff = Handoff::take_from_env()?;
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .context("无法创建 tokio 运行时")?
+                .block_on(serve(&cli, handoff))
+        }
+        Command::Check => check(&cli),
+        Command::Reload => {
+            init_logging(&cli, BoxMakeWriter::new(std::io::stderr))?;
+            let config = load_config(&cli)?;
+            let pid_file = config
+                .get_pid_file()
+                .context("未配置 server.pid_file，无法找到运行中的实例")?;
+            let pid = pidfile::signal_reload(pid_file)?;
+            info!("已通知进程 {} 重新加载配置", pid);
+            Ok(())
+        }
+        Command::Version => {
+            println!("routerway {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+    }
+}
+
+async fn serve(cli: &Cli, handoff: Handoff) -> Result<()> {
+    init_logging(cli, BoxMakeWriter::new(std::io::stdout))?;
 
     info!("🚀 启动 RouterWay 高性能服务器...");
 
     // 加载配置
-    let config = config::Config::load_from_file("config.toml")
-        .context("加载配置文件失败")?;
+    let config = load_config(cli)?;
 
     // 创建并启动服务器
     let server = HttpServer::new(config)?;
-    
-    // 设置优雅关闭
-    let shutdown_signal = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("无法安装 Ctrl+C 处理器");
-        info!("收到关闭信号，正在优雅关闭服务器...");
-    };
-
-    tokio::select! {
-        result = server.start() => {
-            if let Err(e) = result {
-                error!("服务器运行错误: {}", e);
-                return Err(e);
-            }
-        }
-        _ = shutdown_signal => {
-            info!("服务器已关闭");
-        }
+
+    // SIGINT/SIGTERM 触发优雅关闭：停止接受新连接，等待在途请求完成后退出
+    shutdown::spawn_signal_handler(server.shutdown_handle())?;
+    // SIGHUP 重新加载路由、代理目标、错误页面与缓存上限
+    reload::spawn_signal_handler(server.reloader())?;
+
+    if let Err(e) = server.start(handoff).await {
+        error!("服务器运行错误: {}", e);
+        return Err(e);
     }
 
+    info!("服务器已关闭");
+    Ok(())
+}
+
+// 与启动时相同的加载与校验，通过后把生效的配置输出到标准输出，日志写到标准错误
+fn check(cli: &Cli) -> Result<()> {
+    init_logging(cli, BoxMakeWriter::new(std::io::stderr))?;
+
+    let config = load_config(cli)?;
+    HttpServer::new(config.clone()).context("配置校验失败")?;
+
+    print!("{}", toml::to_string(&config).context("无法输出生效的配置")?);
+    info!("✅ 配置文件 {} 校验通过", cli.config.display());
+    Ok(())
+}
+
+fn load_config(cli: &Cli) -> Result<Config> {
+    Config::load(&cli.config, cli.overrides()).context("加载配置文件失败")
+}
+
+fn init_logging(cli: &Cli, writer: BoxMakeWriter) -> Result<()> {
+    let filter = EnvFilter::try_new(&cli.log_level)
+        .with_context(|| format!("无效的日志级别: {}", cli.log_level))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+
+    match cli.log_format {
+        LogFormat::Full => builder.init(),
+        LogFormat::Compact => builder.compact().init(),
+        LogFormat::Pretty => builder.pretty().init(),
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// 运行期间保存当前进程号的文件，释放时删除
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("无法写入 PID 文件: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // 升级后新进程已写入自己的进程号，旧进程退出时不能删除
+        if read(&self.path).ok() != Some(std::process::id()) {
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("删除 PID 文件失败 {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<u32> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("无法读取 PID 文件: {}", path.display()))?;
+    content
+        .trim()
+        .parse()
+        .with_context(|| format!("PID 文件内容无效: {}", path.display()))
+}
+
+/// 向 PID 文件中记录的进程发送 SIGHUP，使其重新加载配置，返回进程号
+pub fn signal_reload(path: &Path) -> Result<u32> {
+    let pid = read(path)?;
+    #[cfg(unix)]
+    {
+        let pid_t = libc::pid_t::try_from(pid).with_context(|| format!("无效的进程号: {}", pid))?;
+        if unsafe { libc::kill(pid_t, libc::SIGHUP) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("无法向进程 {} 发送 SIGHUP", pid));
+        }
+        Ok(pid)
+    }
+    #[cfg(not(unix))]
+    anyhow::bail!("当前平台不支持通过信号重新加载，请使用管理接口 POST /reload (进程 {})", pid)
+}
@@ -0,0 +1,305 @@
+use crate::conditional;
+use hyper::header::{HeaderMap, IF_RANGE, RANGE};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 单个请求允许的最大区间数量，超过则忽略 Range 返回完整内容
+const MAX_RANGES: usize = 64;
+
+static BOUNDARY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 闭区间字节范围 `[start, end]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    // 闭区间至少包含一个字节，不存在空范围
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    // 无 Range 头、语法错误或 If-Range 不匹配时返回完整内容
+    Full,
+    Partial(Vec<ByteRange>),
+    Unsatisfiable,
+}
+
+/// 根据请求头中的 `Range` 与 `If-Range` 计算需要返回的区间
+pub fn evaluate(headers: &HeaderMap, total: u64, etag: &str, last_modified: u64) -> RangeRequest {
+    let range = match headers.get(RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => value,
+        None => return RangeRequest::Full,
+    };
+
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        let matched = if_range
+            .to_str()
+            .ok()
+            .map(|v| if_range_matches(v, etag, last_modified))
+            .unwrap_or(false);
+        if !matched {
+            return RangeRequest::Full;
+        }
+    }
+
+    parse_range(range, total)
+}
+
+// If-Range 要求强比较：实体标签必须完全一致，日期必须与 Last-Modified 精确相等
+fn if_range_matches(value: &str, etag: &str, last_modified: u64) -> bool {
+    let value = value.trim();
+    if value.starts_with('"') || value.starts_with("W/") {
+        return conditional::etag_list_matches(value, etag, false);
+    }
+
+    match httpdate::parse_http_date(value) {
+        Ok(date) => date == UNIX_EPOCH + Duration::from_secs(last_modified),
+        Err(_) => false,
+    }
+}
+
+/// 解析 `bytes=` 区间，语法不合法时按 RFC 9110 忽略该头部
+pub fn parse_range(value: &str, total: u64) -> RangeRequest {
+    let value = value.trim();
+    let specs = match value.get(..6) {
+        Some(unit) if unit.eq_ignore_ascii_case("bytes=") => &value[6..],
+        _ => return RangeRequest::Full,
+    };
+
+    let mut ranges = Vec::new();
+    let mut specified = false;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        specified = true;
+        let (first, last) = match spec.split_once('-') {
+            Some(parts) => (parts.0.trim(), parts.1.trim()),
+            None => return RangeRequest::Full,
+        };
+
+        if first.is_empty() {
+            // 后缀区间: -N 表示最后 N 个字节
+            let suffix: u64 = match last.parse() {
+                Ok(n) => n,
+                Err(_) => return RangeRequest::Full,
+            };
+            if suffix == 0 || total == 0 {
+                continue;
+            }
+            ranges.push(ByteRange {
+                start: total.saturating_sub(suffix),
+                end: total - 1,
+            });
+            continue;
+        }
+
+        let start: u64 = match first.parse() {
+            Ok(n) => n,
+            Err(_) => return RangeRequest::Full,
+        };
+        let end = if last.is_empty() {
+            None
+        } else {
+            match last.parse::<u64>() {
+                Ok(n) if n >= start => Some(n),
+                _ => return RangeRequest::Full,
+            }
+        };
+
+        if start >= total {
+            continue;
+        }
+        ranges.push(ByteRange {
+            start,
+            end: end.map_or(total - 1, |e| e.min(total - 1)),
+        });
+    }
+
+    // `bytes=` 或 `bytes=,` 不含任何区间，属于语法错误，忽略该头部
+    if !specified {
+        return RangeRequest::Full;
+    }
+    // 区间均合法但全部超出内容长度时才返回 416
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    let ranges = coalesce(ranges);
+    if ranges.len() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+
+    RangeRequest::Partial(ranges)
+}
+
+// 合并重叠或相邻的区间，防止大量重复区间放大响应
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    if ranges.len() < 2 {
+        return ranges;
+    }
+
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// 生成 multipart/byteranges 使用的分隔符
+pub fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let seq = BOUNDARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("routerway_{:016x}{:08x}", nanos, seq)
+}
+
+/// multipart/byteranges 中每个分段前的头部
+pub fn part_header(boundary: &str, mime_type: &str, range: &ByteRange, total: u64) -> String {
+    format!(
+        "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+        boundary,
+        mime_type,
+        range.content_range(total)
+    )
+}
+
+pub fn closing_delimiter(boundary: &str) -> String {
+    format!("\r\n--{}--\r\n", boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(ranges: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Partial(
+            ranges
+                .iter()
+                .map(|&(start, end)| ByteRange { start, end })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), partial(&[(0, 99)]));
+        assert_eq!(parse_range("BYTES=10-10", 1000), partial(&[(10, 10)]));
+        // 结束位置超出内容长度时截断
+        assert_eq!(parse_range("bytes=900-2000", 1000), partial(&[(900, 999)]));
+    }
+
+    #[test]
+    fn parses_suffix_and_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), partial(&[(900, 999)]));
+        assert_eq!(parse_range("bytes=-5000", 1000), partial(&[(0, 999)]));
+        assert_eq!(parse_range("bytes=500-", 1000), partial(&[(500, 999)]));
+    }
+
+    #[test]
+    fn coalesces_overlapping_and_adjacent_ranges() {
+        assert_eq!(
+            parse_range("bytes=0-9, 5-19, 20-29, 50-59", 1000),
+            partial(&[(0, 29), (50, 59)])
+        );
+        assert_eq!(
+            parse_range("bytes=500-, -100", 1000),
+            partial(&[(500, 999)])
+        );
+    }
+
+    #[test]
+    fn ignores_too_many_ranges() {
+        let specs: Vec<String> = (0..=MAX_RANGES as u64)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect();
+        let value = format!("bytes={}", specs.join(","));
+        assert_eq!(parse_range(&value, 10_000), RangeRequest::Full);
+
+        // 合并后不超过上限时仍按区间返回
+        let specs: Vec<String> = (0..=MAX_RANGES as u64)
+            .map(|i| format!("{}-{}", i, i))
+            .collect();
+        let value = format!("bytes={}", specs.join(","));
+        assert_eq!(
+            parse_range(&value, 10_000),
+            partial(&[(0, MAX_RANGES as u64)])
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_only_when_all_ranges_out_of_bounds() {
+        assert_eq!(
+            parse_range("bytes=1000-1999", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=2000-, 0-0", 1000), partial(&[(0, 0)]));
+    }
+
+    #[test]
+    fn ignores_malformed_ranges() {
+        for value in [
+            "bytes=",
+            "bytes=,",
+            "bytes= , ",
+            "items=0-1",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=1",
+        ] {
+            assert_eq!(parse_range(value, 1000), RangeRequest::Full, "{}", value);
+        }
+    }
+
+    fn with_if_range(if_range: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, "bytes=0-9".parse().unwrap());
+        headers.insert(IF_RANGE, if_range.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn if_range_requires_strong_match() {
+        let etag = "\"5f-1a\"";
+        let modified = 1_700_000_000;
+        let evaluate = |if_range: &str| evaluate(&with_if_range(if_range), 1000, etag, modified);
+
+        assert_eq!(evaluate(etag), partial(&[(0, 9)]));
+        assert_eq!(
+            evaluate(&conditional::http_date(modified)),
+            partial(&[(0, 9)])
+        );
+        // 弱标签、不同标签与不相等的日期都返回完整内容
+        assert_eq!(evaluate("W/\"5f-1a\""), RangeRequest::Full);
+        assert_eq!(evaluate("\"other\""), RangeRequest::Full);
+        assert_eq!(
+            evaluate(&conditional::http_date(modified + 1)),
+            RangeRequest::Full
+        );
+        assert_eq!(
+            evaluate(&conditional::http_date(modified - 1)),
+            RangeRequest::Full
+        );
+    }
+}
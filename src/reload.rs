@@ -0,0 +1,116 @@
+use crate::cache::FileCache;
+use crate::config::Config;
+use crate::health;
+use crate::tls::CertStore;
+use crate::upstream::UpstreamClients;
+use anyhow::{Context, Result};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{error, info, warn};
+
+/// 处理请求时使用的配置快照，请求开始时取出，处理期间不受重新加载影响
+pub struct ServerState {
+    pub config: Arc<Config>,
+    pub cache: Arc<FileCache>,
+    pub upstreams: Arc<UpstreamClients>,
+}
+
+/// 持有当前配置快照，重新加载时先完整校验新配置，成功后再整体替换
+#[derive(Clone)]
+pub struct Reloader {
+    current: Arc<RwLock<Arc<ServerState>>>,
+    certs: Option<Arc<CertStore>>,
+    // 同一时间只允许一次重新加载
+    reloading: Arc<Mutex<()>>,
+}
+
+impl Reloader {
+    pub fn new(state: ServerState, certs: Option<Arc<CertStore>>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(state))),
+            certs,
+            reloading: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ServerState> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// 重新读取配置文件并应用，失败时继续使用原配置
+    pub fn reload(&self) -> Result<()> {
+        let result = self.load_and_apply();
+        if let Err(e) = &result {
+            error!("重新加载配置失败，继续使用原配置: {:#}", e);
+        }
+        result
+    }
+
+    fn load_and_apply(&self) -> Result<()> {
+        let _guard = self.reloading.lock().unwrap();
+        let running = self.current();
+        let path = running
+            .config
+            .get_path()
+            .context("配置不是从文件加载的，无法重新加载")?;
+        info!("🔄 重新加载配置: {}", path.display());
+
+        let mut config = Config::load(path, running.config.get_overrides().clone())?;
+        let ignored = config.keep_startup_settings(&running.config);
+        if !ignored.is_empty() {
+            warn!("以下设置需要重启才能生效，继续使用运行中的值: {}", ignored.join(", "));
+        }
+
+        // 先创建新的上游客户端，失败时不改动任何运行中的状态；配置未变的路由沿用原连接池
+        let upstreams = Arc::new(
+            running
+                .upstreams
+                .reload(config.get_api_configs(), config.get_http2_config())?,
+        );
+        let max_cache_size = config.get_max_cache_size()?;
+        let max_cached_file_size = config.get_max_cached_file_size();
+
+        // 缓存内容保留，只调整容量
+        running.cache.set_limits(max_cache_size, max_cached_file_size);
+        // 沿用的连接池已有探测任务
+        health::spawn(upstreams.pools().filter(|pool| !running.upstreams.contains(pool)));
+
+        let state = ServerState {
+            config: Arc::new(config),
+            cache: Arc::clone(&running.cache),
+            upstreams,
+        };
+        let routes = state.config.get_api_configs().len();
+        *self.current.write().unwrap() = Arc::new(state);
+
+        if let Some(certs) = &self.certs {
+            match certs.reload() {
+                Ok(()) => info!("🔐 TLS 证书已重新加载"),
+                Err(e) => warn!("重新加载 TLS 证书失败，继续使用原证书: {:#}", e),
+            }
+        }
+
+        info!("✅ 配置已重新加载: {} 个 API 路由，处理中的请求继续使用原配置", routes);
+        Ok(())
+    }
+}
+
+/// 收到 SIGHUP 时重新加载配置
+pub fn spawn_signal_handler(reloader: Reloader) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("收到 SIGHUP");
+                let reloader = reloader.clone();
+                // 读取文件与加载证书是阻塞操作
+                let _ = tokio::task::spawn_blocking(move || reloader.reload()).await;
+            }
+        });
+    }
+    #[cfg(not(unix))]
+    let _ = reloader;
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::config::RetryConfig;
+use crate::upstream::UpstreamError;
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, StreamExt};
+use hyper::body::HttpBody;
+use hyper::{Body, Method, StatusCode};
+use std::time::Duration;
+
+/// 转发给上游的请求体：缓冲在内存中的可以在重试时重放，流式的只能发送一次
+pub enum RequestBody {
+    Buffered(Bytes),
+    Streaming(Body),
+}
+
+impl RequestBody {
+    pub fn is_replayable(&self) -> bool {
+        matches!(self, RequestBody::Buffered(_))
+    }
+
+    /// 取出本次尝试使用的请求体，流式请求体取出后即为空
+    pub fn take(&mut self) -> Body {
+        match self {
+            RequestBody::Buffered(bytes) => Body::from(bytes.clone()),
+            RequestBody::Streaming(body) => std::mem::take(body),
+        }
+    }
+}
+
+/// 按重试策略准备请求体
+///
+/// 允许重试时读取请求体到内存，超过上限则把已读部分与剩余部分重新拼成流，放弃重试。
+pub async fn prepare_body(
+    mut body: Body,
+    retry: Option<&RetryConfig>,
+    method: &Method,
+    content_length: Option<u64>,
+) -> Result<RequestBody, hyper::Error> {
+    let limit = match retry {
+        Some(retry) if retry.max_attempts > 1 && allows_method(retry, method) => {
+            retry.get_max_buffer_size()
+        }
+        _ => return Ok(RequestBody::Streaming(body)),
+    };
+    if content_length.is_some_and(|len| len > limit) {
+        return Ok(RequestBody::Streaming(body));
+    }
+
+    let mut buffered = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (buffered.len() + chunk.len()) as u64 > limit {
+            let prefix = stream::iter([Ok(buffered.freeze()), Ok(chunk)]);
+            return Ok(RequestBody::Streaming(Body::wrap_stream(prefix.chain(body))));
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+
+    Ok(RequestBody::Buffered(buffered.freeze()))
+}
+
+/// 默认只重试幂等方法
+pub fn allows_method(retry: &RetryConfig, method: &Method) -> bool {
+    !retry.idempotent_only
+        || matches!(
+            *method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+        )
+}
+
+/// 上游返回的状态码是否需要重试
+pub fn should_retry_status(retry: &RetryConfig, status: StatusCode) -> bool {
+    retry.retry_on_status.contains(&status.as_u16())
+}
+
+/// 请求失败后是否需要重试
+pub fn should_retry_error(retry: &RetryConfig, error: &UpstreamError) -> bool {
+    retry.retry_on_connect_failure && error.is_connect()
+}
+
+/// 第 `attempt` 次失败后的等待时间：指数退避加全抖动
+pub fn backoff(retry: &RetryConfig, attempt: u32) -> Duration {
+    let exponential = retry
+        .backoff_base_ms
+        .saturating_mul(1u64 << (attempt.saturating_sub(1)).min(16));
+    let capped = exponential.min(retry.backoff_max_ms);
+    Duration::from_millis(fastrand::u64(0..=capped))
+}
@@ -1,21 +1,113 @@
-use crate::cache::{FileCache, get_mime_type};
+use crate::admin;
+use crate::cache::{CachedFile, FileCache, get_mime_type};
+use crate::compression::{self, CompressionLevel, Encoding};
+use crate::conditional::{self, Precondition};
+use crate::file_body::{FileSource, LargeFileMode};
+use crate::forwarding::{self, ClientInfo};
+use crate::health;
+use crate::http3;
+use crate::limits::{self, ConnectionActivity, ConnectionLimiter, ConnectionTimeouts, TimedStream, TrackedBody};
+use crate::pidfile::PidFile;
 use crate::config::{Config, ApiConfig};
-use anyhow::{Result, Context};
-use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server, StatusCode, Uri};
+use crate::range::{self, RangeRequest};
+use crate::reload::{Reloader, ServerState};
+use crate::retry;
+use crate::shutdown::Shutdown;
+use crate::tls::{self, CertStore};
+use crate::upgrade::{self, Handoff, Inherited};
+use crate::upstream::{Upstream, UpstreamClients, UpstreamError, UpstreamPool};
+use crate::websocket;
+use crate::watcher;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCEPT_ENCODING, ALT_SVC, CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, HOST,
+    LOCATION, VARY,
+};
+use hyper::http::response::Builder;
+use hyper::http::uri::Authority;
+use hyper::server::conn::Http;
+use hyper::service::{service_fn, Service};
+use hyper::{Body, Method, Request, Response, StatusCode, Version};
 use std::convert::Infallible;
+use std::future::Future;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 use tokio::fs;
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, timeout, Duration};
+use tokio_rustls::TlsAcceptor;
 use tracing::{info, warn, error, debug};
-use url::Url;
 use percent_encoding::percent_decode_str;
 
 pub struct HttpServer {
-    config: Arc<Config>,
-    cache: Arc<FileCache>,
+    // 请求处理使用的配置快照，可在运行时重新加载
+    reloader: Reloader,
+    certs: Option<Arc<CertStore>>,
+    shutdown: Shutdown,
+}
+
+/// 服务器使用的监听套接字，HTTPS、重定向与 HTTP/3 监听只在启用 TLS 时存在；
+/// 未提供管理接口监听时按配置自行绑定
+pub struct Listeners {
+    pub http: std::net::TcpListener,
+    pub https: Option<std::net::TcpListener>,
+    pub redirect: Option<std::net::TcpListener>,
+    pub quic: Option<std::net::UdpSocket>,
+    pub admin: Option<std::net::TcpListener>,
+}
+
+impl Listeners {
+    /// 按配置绑定所有监听端口，已从旧进程或 systemd 继承的端口直接使用
+    pub fn bind(config: &Config, mut inherited: Inherited) -> Result<Self> {
+        let tls = config.get_tls_config();
+        let mut tcp = |port| match inherited.take_tcp(port) {
+            Some(listener) => Ok(listener),
+            None => bind(SocketAddr::from(([0, 0, 0, 0], port))),
+        };
+
+        let http = tcp(config.get_port())?;
+        let https = tls.map(|tls| tcp(tls.port)).transpose()?;
+        let redirect = tls.and_then(|tls| tls.redirect_port).map(&mut tcp).transpose()?;
+        let admin = match config.get_admin_address() {
+            Some(addr) => Some(match inherited.take_tcp(addr.port()) {
+                Some(listener) => listener,
+                None => bind(addr)?,
+            }),
+            None => None,
+        };
+        let quic = match (config.get_http3_config(), tls) {
+            (Some(http3), Some(tls)) => {
+                let port = http3.port.unwrap_or(tls.port);
+                Some(match inherited.take_udp(port) {
+                    Some(socket) => socket,
+                    None => bind_udp(port)?,
+                })
+            }
+            _ => None,
+        };
+        inherited.close_unused();
+
+        Ok(Self { http, https, redirect, quic, admin })
+    }
+}
+
+fn bind(addr: SocketAddr) -> Result<std::net::TcpListener> {
+    std::net::TcpListener::bind(addr).with_context(|| format!("无法监听地址: {}", addr))
+}
+
+fn bind_udp(port: u16) -> Result<std::net::UdpSocket> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    std::net::UdpSocket::bind(addr).with_context(|| format!("无法监听 UDP 地址: {}", addr))
+}
+
+/// 请求所在连接的信息
+#[derive(Debug, Clone, Copy)]
+struct ClientConnection {
+    remote_addr: SocketAddr,
+    // 客户端使用的协议，写入 X-Forwarded-Proto 与 Forwarded
+    proto: &'static str,
 }
 
 impl HttpServer {
@@ -24,21 +116,80 @@ impl HttpServer {
             config.get_root_directory().clone(),
             config.get_max_cache_size()?,
             config.is_cache_enabled(),
+            config.get_eviction_policy(),
+            config.get_max_cached_file_size(),
         ));
 
-        Ok(Self {
+        let upstreams = Arc::new(UpstreamClients::new(config.get_api_configs(), config.get_http2_config())?);
+        let certs = config
+            .get_tls_config()
+            .map(|tls| CertStore::load(&tls.certificates))
+            .transpose()?
+            .map(Arc::new);
+
+        let state = ServerState {
             config: Arc::new(config),
             cache,
+            upstreams,
+        };
+
+        Ok(Self {
+            reloader: Reloader::new(state, certs.clone()),
+            certs,
+            shutdown: Shutdown::new(),
+        })
+    }
+
+    /// 用于重新加载配置的句柄
+    pub fn reloader(&self) -> Reloader {
+        self.reloader.clone()
+    }
+
+    // 监听与连接相关的设置只在启动时生效，重新加载后保持不变
+    fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.reloader.current().config)
+    }
+
+    /// 用于触发关闭的句柄，关闭后 `serve` 在连接排空或超时后返回
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// 按配置绑定监听并运行，`handoff` 为启动时从环境变量取得的升级交接信息
+    pub async fn start(&self, mut handoff: Handoff) -> Result<()> {
+        let listeners = Listeners::bind(&self.config(), handoff.inherited()?)?;
+        // SIGUSR2 启动新版本进程并交出监听套接字
+        upgrade::spawn_signal_handler(&listeners, self.shutdown.clone())?;
+        self.serve_with(listeners, handoff).await
+    }
+
+    /// 在已绑定的明文监听套接字上运行服务器
+    pub async fn run(&self, listener: std::net::TcpListener) -> Result<()> {
+        self.serve(Listeners {
+            http: listener,
+            https: None,
+            redirect: None,
+            quic: None,
+            admin: None,
         })
+        .await
+    }
+
+    /// 在给定的监听套接字上运行服务器
+    pub async fn serve(&self, listeners: Listeners) -> Result<()> {
+        self.serve_with(listeners, Handoff::default()).await
     }
 
-    pub async fn start(&self) -> Result<()> {
+    async fn serve_with(&self, listeners: Listeners, mut handoff: Handoff) -> Result<()> {
+        let state = self.reloader.current();
+        let config = &state.config;
+
         // 初始化文件缓存
-        self.cache.initialize().await?;
+        state.cache.initialize().await?;
 
         // 启动缓存清理任务
-        if self.config.is_cache_enabled() {
-            let cache_clone = Arc::clone(&self.cache);
+        if config.is_cache_enabled() {
+            let cache_clone = Arc::clone(&state.cache);
             tokio::spawn(async move {
                 let mut interval = interval(Duration::from_secs(300)); // 每5分钟清理一次
                 loop {
@@ -46,54 +197,532 @@ impl HttpServer {
                     cache_clone.cleanup_old_entries(3600); // 清理1小时未访问的条目
                 }
             });
+
+            // 启动文件监听，保持缓存与根目录一致
+            if config.is_watch_enabled() {
+                watcher::spawn(
+                    Arc::clone(&state.cache),
+                    config.get_root_directory(),
+                    config.get_watch_debounce(),
+                )?;
+            }
+        }
+
+        // 上游健康检查与管理接口
+        health::spawn(state.upstreams.pools());
+        let Listeners { http: listener, https, redirect, quic, admin } = listeners;
+        if let Some(admin_addr) = config.get_admin_address() {
+            let admin = match admin {
+                Some(admin) => admin,
+                None => bind(admin_addr).context("无法监听管理接口地址")?,
+            };
+            admin::spawn(admin, self.reloader.clone())?;
+        }
+
+        // 定期输出有新流量的上游统计，重新加载后统计当前的上游
+        {
+            let reloader = self.reloader.clone();
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(60));
+                let mut last_requests = std::collections::HashMap::new();
+                loop {
+                    interval.tick().await;
+                    for stats in reloader.current().upstreams.stats() {
+                        let key = (stats.api.clone(), stats.url.clone());
+                        if last_requests.insert(key, stats.requests).unwrap_or(0) == stats.requests {
+                            continue;
+                        }
+                        info!("📈 上游 {} {}: 请求 {}, 失败 {}, 活跃 {}",
+                              stats.api, stats.url, stats.requests, stats.failures, stats.active);
+                    }
+                }
+            });
         }
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.config.get_port()));
-        
-        let config = Arc::clone(&self.config);
-        let cache = Arc::clone(&self.cache);
+        let addr = listener.local_addr()?;
+
+        // 明文、HTTPS 与 HTTP/3 连接共用同一个连接数上限
+        let limiter = ConnectionLimiter::new(
+            config.get_max_connections(),
+            config.get_connection_overflow(),
+        );
+        let server = self.serve_plain(listener, limiter.clone())?;
 
-        let make_svc = make_service_fn(move |_conn| {
-            let config = Arc::clone(&config);
-            let cache = Arc::clone(&cache);
-            
-            async move {
-                Ok::<_, Infallible>(service_fn(move |req| {
-                    handle_request(req, Arc::clone(&config), Arc::clone(&cache))
-                }))
+        // HTTP/3 监听，HTTPS 响应通过 Alt-Svc 告知客户端
+        let (http3_server, alt_svc) = match quic {
+            Some(socket) => {
+                let (server, alt_svc) = self.serve_http3(socket, limiter.clone())?;
+                (Some(server), Some(alt_svc))
             }
-        });
+            None => (None, None),
+        };
 
-        let server = Server::bind(&addr)
-            .tcp_nodelay(true)
-            .tcp_keepalive(Some(Duration::from_secs(60)))
-            .serve(make_svc);
+        // HTTPS 监听与 HTTP 重定向监听
+        let https_server = match https {
+            Some(listener) => Some(self.serve_tls(listener, alt_svc, limiter.clone())?),
+            None => None,
+        };
+        let redirect_server = match (redirect, config.get_tls_config()) {
+            (Some(listener), Some(tls)) => Some(serve_redirect(
+                listener,
+                tls.port,
+                limiter,
+                self.connection_timeouts(),
+                self.shutdown.clone(),
+            )?),
+            (Some(_), None) => anyhow::bail!("未启用 TLS 时不能使用重定向监听"),
+            (None, _) => None,
+        };
 
         info!("🚀 RouterWay 服务器启动成功!");
         info!("📍 监听地址: http://{}", addr);
-        info!("📁 根目录: {}", self.config.get_root_directory().display());
-        info!("💾 缓存状态: {}", if self.config.is_cache_enabled() { "启用" } else { "禁用" });
-        info!("🔗 最大连接数: {}", self.config.get_max_connections());
-        info!("📋 API配置数量: {}", self.config.get_api_configs().len());
+        info!("📁 根目录: {}", config.get_root_directory().display());
+        info!("💾 缓存状态: {}", if config.is_cache_enabled() { "启用" } else { "禁用" });
+        info!("🔗 最大连接数: {} (超出时 {:?})",
+              config.get_max_connections(), config.get_connection_overflow());
+        info!("📋 API配置数量: {}", config.get_api_configs().len());
 
         // 打印API配置信息
-        for (i, api) in self.config.get_api_configs().iter().enumerate() {
-            info!("  API {}: {} -> {} ({})", i + 1, api.from, api.to, api.name);
+        for (i, api) in config.get_api_configs().iter().enumerate() {
+            let targets: Vec<String> = api.get_targets().into_iter().map(|t| t.url).collect();
+            info!("  API {}: {} -> {} ({}, {:?})",
+                  i + 1, api.from, targets.join(", "), api.name, api.load_balance);
         }
 
-        if let Err(e) = server.await {
+        // 开始服务后才写入进程号，升级失败的新进程不会覆盖旧进程的记录
+        let _pid_file = config.get_pid_file().map(|path| PidFile::create(path)).transpose()?;
+        // 作为升级启动的新进程时，旧进程收到通知后停止接受连接
+        handoff.notify_ready();
+
+        if let Err(e) = tokio::try_join!(
+            server,
+            optional(https_server),
+            optional(redirect_server),
+            optional(http3_server),
+        ) {
             error!("服务器运行错误: {}", e);
-            return Err(e.into());
+            return Err(e);
+        }
+
+        // 各监听已停止接受连接，等待处理中的请求与 WebSocket 隧道结束
+        let drain_timeout = self.config().get_shutdown_timeout();
+        info!("⏳ 等待 {} 个连接结束 (最长 {} 秒)",
+              self.shutdown.active_connections(), drain_timeout.as_secs());
+        match timeout(drain_timeout, self.shutdown.drained()).await {
+            Ok(()) => info!("✅ 所有连接已结束"),
+            Err(_) => warn!("等待超时，强制关闭剩余的 {} 个连接", self.shutdown.active_connections()),
+        }
+
+        Ok(())
+    }
+
+    // 启动明文监听：逐个接受连接，开启 h2c 时同时识别 HTTP/2 连接前言
+    fn serve_plain(
+        &self,
+        listener: std::net::TcpListener,
+        limiter: ConnectionLimiter,
+    ) -> Result<impl Future<Output = Result<()>>> {
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+
+        let reloader = self.reloader.clone();
+        let timeouts = self.connection_timeouts();
+        let shutdown = self.shutdown.clone();
+        let mut http = self.http_builder();
+        http.http1_only(!self.config().get_http2_config().h2c);
+
+        Ok(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = limiter.accept(&listener) => accepted,
+                    _ = shutdown.triggered() => break,
+                };
+                let (stream, remote_addr, permit) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // 文件描述符耗尽等错误通常是暂时的，稍后继续接受连接
+                        warn!("接受连接失败: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let permit = match permit {
+                    Some(permit) => permit,
+                    None => {
+                        debug!("连接数已达上限，拒绝连接: {}", remote_addr);
+                        limits::reject(stream, true);
+                        continue;
+                    }
+                };
+                let _ = stream.set_nodelay(true);
+
+                let activity = Arc::new(ConnectionActivity::default());
+                let stream =
+                    TimedStream::new(stream, Arc::clone(&activity), timeouts, permit, shutdown.track());
+                let conn = ClientConnection {
+                    remote_addr,
+                    proto: "http",
+                };
+                let service = connection_service(conn, Arc::clone(&activity), None, reloader.clone());
+                let connection = http.serve_connection(stream, service).with_upgrades();
+                let shutdown = shutdown.clone();
+
+                tokio::spawn(async move {
+                    tokio::pin!(connection);
+                    let result = tokio::select! {
+                        result = connection.as_mut() => result,
+                        _ = shutdown.triggered() => {
+                            // 不再读取新请求，处理中的请求完成后关闭连接
+                            connection.as_mut().graceful_shutdown();
+                            connection.await
+                        }
+                        _ = activity.http2_idle(timeouts.keep_alive) => {
+                            debug!("h2c 连接空闲超时，关闭连接: {}", remote_addr);
+                            connection.as_mut().graceful_shutdown();
+                            connection.await
+                        }
+                    };
+                    if let Err(e) = result {
+                        debug!("连接错误 ({}): {}", remote_addr, e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    // 启动 HTTPS 监听：逐个接受连接，握手完成后交给与明文监听相同的请求处理
+    fn serve_tls(
+        &self,
+        listener: std::net::TcpListener,
+        alt_svc: Option<HeaderValue>,
+        limiter: ConnectionLimiter,
+    ) -> Result<impl Future<Output = Result<()>>> {
+        let config = self.config();
+        let (tls, certs) = match (config.get_tls_config(), &self.certs) {
+            (Some(tls), Some(certs)) => (tls, certs),
+            _ => anyhow::bail!("未启用 TLS 时不能使用 HTTPS 监听"),
+        };
+        let acceptor = TlsAcceptor::from(tls::server_config(tls, Arc::clone(certs))?);
+        let handshake_timeout = Duration::from_millis(tls.handshake_timeout_ms);
+        if tls.reload_check_secs > 0 {
+            tls::spawn_reload(Arc::clone(certs), Duration::from_secs(tls.reload_check_secs));
+        }
+
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        info!("🔒 HTTPS 监听地址: https://{}", listener.local_addr()?);
+
+        let reloader = self.reloader.clone();
+        let timeouts = self.connection_timeouts();
+        let shutdown = self.shutdown.clone();
+        let http = self.http_builder();
+
+        Ok(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = limiter.accept(&listener) => accepted,
+                    _ = shutdown.triggered() => break,
+                };
+                let (stream, remote_addr, permit) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // 文件描述符耗尽等错误通常是暂时的，稍后继续接受连接
+                        warn!("接受 HTTPS 连接失败: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let permit = match permit {
+                    Some(permit) => permit,
+                    None => {
+                        debug!("连接数已达上限，拒绝 HTTPS 连接: {}", remote_addr);
+                        limits::reject(stream, false);
+                        continue;
+                    }
+                };
+                let _ = stream.set_nodelay(true);
+
+                let acceptor = acceptor.clone();
+                let mut http = http.clone();
+                let reloader = reloader.clone();
+                let alt_svc = alt_svc.clone();
+                let shutdown = shutdown.clone();
+                let guard = shutdown.track();
+                let conn = ClientConnection {
+                    remote_addr,
+                    proto: "https",
+                };
+
+                tokio::spawn(async move {
+                    let stream = match timeout(handshake_timeout, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            debug!("TLS 握手失败 ({}): {}", remote_addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            debug!("TLS 握手超时: {}", remote_addr);
+                            return;
+                        }
+                    };
+
+                    // 按 ALPN 协商结果固定协议，未协商时使用 HTTP/1.1；HTTP/2 连接不使用 HTTP/1 的超时
+                    let activity = Arc::new(ConnectionActivity::default());
+                    if stream.get_ref().1.alpn_protocol() == Some(b"h2") {
+                        http.http2_only(true);
+                        activity.set_http2();
+                    } else {
+                        http.http1_only(true);
+                    }
+
+                    let stream = TimedStream::new(stream, Arc::clone(&activity), timeouts, permit, guard);
+                    let service = connection_service(conn, Arc::clone(&activity), alt_svc, reloader);
+                    let connection = http.serve_connection(stream, service).with_upgrades();
+                    tokio::pin!(connection);
+                    let result = tokio::select! {
+                        result = connection.as_mut() => result,
+                        _ = shutdown.triggered() => {
+                            // HTTP/1 不再读取新请求，HTTP/2 发送 GOAWAY，处理中的请求完成后关闭连接
+                            connection.as_mut().graceful_shutdown();
+                            connection.await
+                        }
+                        _ = activity.http2_idle(timeouts.keep_alive) => {
+                            debug!("HTTP/2 连接空闲超时，关闭连接: {}", remote_addr);
+                            connection.as_mut().graceful_shutdown();
+                            connection.await
+                        }
+                    };
+                    if let Err(e) = result {
+                        debug!("HTTPS 连接错误 ({}): {}", remote_addr, e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    // 明文与 HTTPS 连接共用的协议设置
+    fn http_builder(&self) -> Http {
+        let config = self.config();
+        let http2 = config.get_http2_config();
+        let max_header_size = config.get_limits_config().get_max_header_size();
+        let mut http = Http::new();
+        http.max_buf_size(max_header_size)
+            .http2_max_header_list_size(max_header_size as u32)
+            .http2_max_concurrent_streams(http2.max_concurrent_streams)
+            .http2_initial_stream_window_size(http2.initial_stream_window_size)
+            .http2_initial_connection_window_size(http2.initial_connection_window_size)
+            .http2_adaptive_window(http2.adaptive_window)
+            .http2_keep_alive_interval(http2.get_keep_alive_interval())
+            .http2_keep_alive_timeout(http2.get_keep_alive_timeout());
+        http
+    }
+
+    fn connection_timeouts(&self) -> ConnectionTimeouts {
+        let config = self.config();
+        let limits = config.get_limits_config();
+        ConnectionTimeouts {
+            header_read: limits.get_header_read_timeout(),
+            keep_alive: limits.get_keep_alive_timeout(),
         }
+    }
+
+    // 启动 HTTP/3 监听，返回监听任务与 HTTPS 响应中使用的 Alt-Svc 值
+    fn serve_http3(
+        &self,
+        socket: std::net::UdpSocket,
+        limiter: ConnectionLimiter,
+    ) -> Result<(impl Future<Output = Result<()>>, HeaderValue)> {
+        let config = self.config();
+        let (http3_config, certs) = match (config.get_http3_config(), &self.certs) {
+            (Some(http3_config), Some(certs)) => (http3_config, certs),
+            _ => anyhow::bail!("未启用 HTTP/3 与 TLS 时不能使用 QUIC 监听"),
+        };
+        let endpoint = http3::endpoint(socket, http3_config, Arc::clone(certs))?;
+        let alt_svc = HeaderValue::from_str(&format!(
+            "h3=\":{}\"; ma={}",
+            endpoint.local_addr()?.port(),
+            http3_config.alt_svc_max_age_secs
+        ))?;
+
+        let reloader = self.reloader.clone();
+        let handler = move |req, remote_addr| {
+            let conn = ClientConnection {
+                remote_addr,
+                proto: "https",
+            };
+            let state = reloader.current();
+            let response = handle_request(
+                req,
+                conn,
+                Arc::clone(&state.config),
+                Arc::clone(&state.cache),
+                Arc::clone(&state.upstreams),
+            );
+            async move { response.await.unwrap_or_else(|never| match never {}) }
+        };
+
+        let limits = config.get_limits_config().clone();
+        Ok((http3::serve(endpoint, limiter, limits, self.shutdown.clone(), handler), alt_svc))
+    }
+}
+
+// 一条连接上的请求处理服务：每个请求使用开始时的配置快照，记录请求进度供连接超时判断，
+// HTTPS 响应中加入 Alt-Svc
+fn connection_service(
+    conn: ClientConnection,
+    activity: Arc<ConnectionActivity>,
+    alt_svc: Option<HeaderValue>,
+    reloader: Reloader,
+) -> impl Service<
+    Request<Body>,
+    Response = Response<TrackedBody>,
+    Error = Infallible,
+    Future = impl Future<Output = Result<Response<TrackedBody>, Infallible>> + Send,
+> + Send {
+    service_fn(move |req| {
+        let request = activity.start_request();
+        let state = reloader.current();
+        let response = handle_request(
+            req,
+            conn,
+            Arc::clone(&state.config),
+            Arc::clone(&state.cache),
+            Arc::clone(&state.upstreams),
+        );
+        let activity = Arc::clone(&activity);
+        let alt_svc = alt_svc.clone();
+        async move {
+            let mut response = response.await?;
+            if let Some(alt_svc) = alt_svc {
+                response.headers_mut().insert(ALT_SVC, alt_svc);
+            }
+            // 升级后的连接由 WebSocket 隧道按自己的空闲超时管理
+            if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                activity.disable();
+            }
+            Ok(response.map(|body| request.track(body)))
+        }
+    })
+}
+
+// 未启用的监听视为立即正常结束
+async fn optional(server: Option<impl Future<Output = Result<()>>>) -> Result<()> {
+    match server {
+        Some(server) => server.await,
+        None => Ok(()),
+    }
+}
 
+// 把所有请求重定向到 HTTPS 端口上的同一地址，与其他监听共用连接数上限与 HTTP/1 超时
+fn serve_redirect(
+    listener: std::net::TcpListener,
+    https_port: u16,
+    limiter: ConnectionLimiter,
+    timeouts: ConnectionTimeouts,
+    shutdown: Shutdown,
+) -> Result<impl Future<Output = Result<()>>> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    info!("↪️ HTTP 重定向监听: http://{} -> https 端口 {}", listener.local_addr()?, https_port);
+
+    let mut http = Http::new();
+    http.http1_only(true);
+
+    Ok(async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = limiter.accept(&listener) => accepted,
+                _ = shutdown.triggered() => break,
+            };
+            let (stream, remote_addr, permit) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("接受重定向连接失败: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let permit = match permit {
+                Some(permit) => permit,
+                None => {
+                    debug!("连接数已达上限，拒绝重定向连接: {}", remote_addr);
+                    limits::reject(stream, true);
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+
+            let activity = Arc::new(ConnectionActivity::default());
+            let stream =
+                TimedStream::new(stream, Arc::clone(&activity), timeouts, permit, shutdown.track());
+            let service = service_fn(move |req| {
+                let request = activity.start_request();
+                let response = redirect_to_https(&req, https_port);
+                async move { Ok::<_, Infallible>(response.map(|body| request.track(body))) }
+            });
+            let connection = http.serve_connection(stream, service);
+            let shutdown = shutdown.clone();
+
+            tokio::spawn(async move {
+                tokio::pin!(connection);
+                let result = tokio::select! {
+                    result = connection.as_mut() => result,
+                    _ = shutdown.triggered() => {
+                        connection.as_mut().graceful_shutdown();
+                        connection.await
+                    }
+                };
+                if let Err(e) = result {
+                    debug!("重定向连接错误 ({}): {}", remote_addr, e);
+                }
+            });
+        }
         Ok(())
+    })
+}
+
+fn redirect_to_https(req: &Request<Body>, https_port: u16) -> Response<Body> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Authority>().ok());
+    let host = match host {
+        Some(authority) => authority.host().to_string(),
+        None => return create_error_response(StatusCode::BAD_REQUEST, "Missing Host header"),
+    };
+
+    let authority = match https_port {
+        443 => host,
+        port => format!("{}:{}", host, port),
+    };
+    let path_and_query = req.uri().path_and_query().map_or("/", |pq| pq.as_str());
+    let location = format!("https://{}{}", authority, path_and_query);
+
+    // 308 要求客户端保留方法与请求体，GET/HEAD 使用兼容性更好的 301
+    let status = match *req.method() {
+        Method::GET | Method::HEAD => StatusCode::MOVED_PERMANENTLY,
+        _ => StatusCode::PERMANENT_REDIRECT,
+    };
+    match Response::builder()
+        .status(status)
+        .header(LOCATION, location)
+        .header("Server", "RouterWay")
+        .body(Body::empty())
+    {
+        Ok(response) => response,
+        Err(_) => create_error_response(StatusCode::BAD_REQUEST, "Invalid Host header"),
     }
 }
 
 async fn handle_request(
     req: Request<Body>,
+    conn: ClientConnection,
     config: Arc<Config>,
     cache: Arc<FileCache>,
+    upstreams: Arc<UpstreamClients>,
 ) -> Result<Response<Body>, Infallible> {
     let method = req.method();
     let uri = req.uri();
@@ -101,6 +730,18 @@ async fn handle_request(
 
     debug!("收到请求: {} {}", method, path);
 
+    // HTTP/1 的请求头在解析时已由 hyper 限制，这里同样覆盖 HTTP/2 与 HTTP/3
+    let limits = config.get_limits_config();
+    if req.headers().len() > limits.max_header_count
+        || limits::header_size(req.headers()) > limits.get_max_header_size()
+    {
+        warn!("请求头过大: {} 个, {} 字节", req.headers().len(), limits::header_size(req.headers()));
+        return Ok(create_error_response(
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            "Request Header Fields Too Large",
+        ));
+    }
+
     // 处理CORS预检请求
     if method == Method::OPTIONS {
         return Ok(create_cors_response(StatusCode::OK, Body::empty()));
@@ -118,12 +759,35 @@ async fn handle_request(
     // 检查API代理配置
     for api_config in config.get_api_configs() {
         if decoded_path.starts_with(&api_config.from) {
-            return handle_proxy_request(req, api_config, &decoded_path, &config, &cache).await;
+            // 只有代理请求会读取请求体；gRPC 流式请求的消息间隔不固定，不设读取超时
+            let (req, body_timeout) = if is_grpc(req.headers()) {
+                (req, limits::BodyTimeout::default())
+            } else {
+                let (parts, body) = req.into_parts();
+                let (body, body_timeout) = limits::timeout_body(body, limits.get_body_read_timeout());
+                (Request::from_parts(parts, body), body_timeout)
+            };
+            let version = req.version();
+            let response = handle_proxy_request(
+                req,
+                conn,
+                api_config,
+                &decoded_path,
+                &config,
+                &cache,
+                &upstreams,
+            )
+            .await?;
+            if body_timeout.is_elapsed() {
+                warn!("读取请求体超时 ({}): {}", conn.remote_addr, decoded_path);
+                return Ok(request_timeout_response(version));
+            }
+            return Ok(response);
         }
     }
 
     // 处理静态文件请求
-    match handle_static_file(&decoded_path, &config, &cache).await {
+    match handle_static_file(&decoded_path, req.method(), req.headers(), &config, &cache).await {
         Ok(response) => Ok(response),
         Err(e) => {
             error!("处理静态文件请求失败: {}", e);
@@ -133,54 +797,304 @@ async fn handle_request(
 }
 
 async fn handle_proxy_request(
-    mut req: Request<Body>,
+    req: Request<Body>,
+    conn: ClientConnection,
     api_config: &ApiConfig,
     original_path: &str,
     config: &Config,
     cache: &FileCache,
+    upstreams: &UpstreamClients,
 ) -> Result<Response<Body>, Infallible> {
-    // 构建目标URL
-    let target_path = original_path.replacen(&api_config.from, &api_config.to, 1);
-    
-    debug!("代理请求: {} -> {}", original_path, target_path);
-
-    // 解析目标URL
-    let target_url = match target_path.parse::<Uri>() {
-        Ok(uri) => uri,
+    // 使用该 API 共享的连接池，并按负载均衡策略选择上游
+    let pool = match upstreams.get(&api_config.name) {
+        Some(pool) => pool,
+        None => {
+            error!("未找到上游客户端: {}", api_config.name);
+            return Ok(create_error_response(StatusCode::BAD_GATEWAY, "Proxy request failed"));
+        }
+    };
+
+    // WebSocket 升级请求单独处理，不参与重试
+    if websocket::is_upgrade_request(req.headers()) {
+        return Ok(handle_websocket_proxy(req, conn, api_config, pool, config, cache).await);
+    }
+
+    // 记录客户端的 Accept-Encoding，用于上游未压缩时的流式压缩
+    let mut negotiation_headers = HeaderMap::new();
+    if let Some(accept_encoding) = req.headers().get(ACCEPT_ENCODING) {
+        negotiation_headers.insert(ACCEPT_ENCODING, accept_encoding.clone());
+    }
+    let is_head = req.method() == Method::HEAD;
+
+    // 允许重试时先缓冲请求体，以便在后续尝试中重放
+    let (parts, body) = req.into_parts();
+    let content_length = parts
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let retry_config = pool.retry();
+    let mut body = match retry::prepare_body(body, retry_config, &parts.method, content_length).await {
+        Ok(body) => body,
         Err(e) => {
-            error!("无效的代理目标URL: {} - {}", target_path, e);
-            return Ok(create_error_response(StatusCode::BAD_REQUEST, "Invalid proxy target"));
+            warn!("读取请求体失败: {}", e);
+            return Ok(create_error_response(StatusCode::BAD_REQUEST, "Invalid request body"));
+        }
+    };
+    let max_attempts = retry_config.map_or(1, |retry| retry.max_attempts);
+    let client_info = ClientInfo {
+        ip: conn.remote_addr.ip(),
+        proto: conn.proto,
+        version: parts.version,
+    };
+
+    let mut attempt = 1;
+    // 已失败的上游，重试时优先选择其他上游
+    let mut failed: Vec<Arc<Upstream>> = Vec::new();
+    loop {
+        let upstream = match pool.select(&parts.headers, conn.remote_addr.ip(), &failed) {
+            Some(upstream) => upstream,
+            None => {
+                warn!("API {} 没有可用的上游", api_config.name);
+                return Ok(no_upstream_response(config, cache).await);
+            }
+        };
+
+        // 使用客户端发送的原始路径和查询串构建目标URL，避免解码后再编码改变路径
+        let target_url = match upstream.target_uri(api_config, parts.uri.path(), parts.uri.query()) {
+            Some(uri) => uri,
+            None => {
+                error!("无效的代理目标URL: {} ({})", original_path, upstream.url());
+                return Ok(create_error_response(StatusCode::BAD_REQUEST, "Invalid proxy target"));
+            }
+        };
+
+        debug!("代理请求: {} -> {} (上游 {}, 第 {} 次尝试)", parts.uri, target_url, upstream.url(), attempt);
+
+        let mut headers = parts.headers.clone();
+        // HTTP/2 请求没有 Host 头，主机名在 :authority 中
+        if let (false, Some(authority)) = (headers.contains_key(HOST), parts.uri.authority()) {
+            if let Ok(host) = authority.as_str().parse() {
+                headers.insert(HOST, host);
+            }
+        }
+        if let Some(authority) = target_url.authority() {
+            forwarding::prepare_request(&mut headers, &client_info, api_config.host_header, authority);
+        }
+
+        let mut request = Request::new(body.take());
+        *request.method_mut() = parts.method.clone();
+        *request.uri_mut() = target_url;
+        *request.version_mut() = pool.request_version(parts.version);
+        *request.headers_mut() = headers;
+
+        let can_retry = attempt < max_attempts && body.is_replayable();
+        let result = pool.request(&upstream, request).await;
+
+        if let Some(retry_config) = retry_config.filter(|_| can_retry) {
+            let reason = match &result {
+                Ok(response) if retry::should_retry_status(retry_config, response.status()) => {
+                    Some(response.status().to_string())
+                }
+                Err(e) if retry::should_retry_error(retry_config, e) => Some(e.to_string()),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                let delay = retry::backoff(retry_config, attempt);
+                warn!("代理请求第 {} 次尝试失败 ({} -> {}): {}，{} ms 后重试",
+                      attempt, api_config.name, upstream.url(), reason, delay.as_millis());
+                tokio::time::sleep(delay).await;
+                failed.push(upstream);
+                attempt += 1;
+                continue;
+            }
+        }
+
+        return match result {
+            Ok(mut response) => {
+                let version = response.version();
+                forwarding::prepare_response(response.headers_mut(), version);
+
+                // 添加CORS头和Server头
+                let headers = response.headers_mut();
+                headers.insert("Access-Control-Allow-Origin", "*".parse().unwrap());
+                headers.insert("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS".parse().unwrap());
+                headers.insert("Access-Control-Allow-Headers", "Content-Type, Authorization".parse().unwrap());
+                headers.insert("Server", "RouterWay".parse().unwrap());
+
+                if is_head {
+                    return Ok(response);
+                }
+                Ok(compress_proxy_response(response, config, &negotiation_headers))
+            }
+            Err(e) => {
+                error!("代理请求失败 ({} -> {}): {}", api_config.name, upstream.url(), e);
+                Ok(upstream_error_response(&e, config, cache).await)
+            }
+        };
+    }
+}
+
+// 完成与上游的 101 握手后，把客户端与上游两条升级后的连接对接起来
+async fn handle_websocket_proxy(
+    mut req: Request<Body>,
+    conn: ClientConnection,
+    api_config: &ApiConfig,
+    pool: &UpstreamPool,
+    config: &Config,
+    cache: &FileCache,
+) -> Response<Body> {
+    let upstream = match pool.select(req.headers(), conn.remote_addr.ip(), &[]) {
+        Some(upstream) => upstream,
+        None => {
+            warn!("API {} 没有可用的上游", api_config.name);
+            return no_upstream_response(config, cache).await;
         }
     };
 
-    // 更新请求URI
-    *req.uri_mut() = target_url;
-
-    // 创建HTTP客户端并发送请求
-    let client = hyper::Client::new();
-    match client.request(req).await {
-        Ok(mut response) => {
-            // 添加CORS头和Server头
-            let headers = response.headers_mut();
-            headers.insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-            headers.insert("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS".parse().unwrap());
-            headers.insert("Access-Control-Allow-Headers", "Content-Type, Authorization".parse().unwrap());
-            headers.insert("Server", "RouterWay".parse().unwrap());
-            
-            Ok(response)
+    let target_url = match upstream.target_uri(api_config, req.uri().path(), req.uri().query()) {
+        Some(uri) => uri,
+        None => {
+            error!("无效的代理目标URL: {} ({})", req.uri(), upstream.url());
+            return create_error_response(StatusCode::BAD_REQUEST, "Invalid proxy target");
         }
+    };
+
+    debug!("WebSocket 代理: {} -> {} (上游 {})", req.uri(), target_url, upstream.url());
+
+    let mut headers = req.headers().clone();
+    if let Some(authority) = target_url.authority() {
+        let client_info = ClientInfo {
+            ip: conn.remote_addr.ip(),
+            proto: conn.proto,
+            version: req.version(),
+        };
+        forwarding::prepare_request(&mut headers, &client_info, api_config.host_header, authority);
+    }
+    websocket::restore_upgrade_headers(&mut headers);
+
+    let client_upgrade = hyper::upgrade::on(&mut req);
+    let mut request = Request::new(Body::empty());
+    *request.method_mut() = req.method().clone();
+    *request.uri_mut() = target_url;
+    *request.headers_mut() = headers;
+
+    let mut response = match pool.request(&upstream, request).await {
+        Ok(response) => response,
         Err(e) => {
-            error!("代理请求失败: {}", e);
-            match handle_error_page(StatusCode::BAD_GATEWAY, config, cache).await {
-                Ok(response) => Ok(response),
-                Err(_) => Ok(create_error_response(StatusCode::BAD_GATEWAY, "Proxy request failed"))
+            error!("WebSocket 握手失败 ({} -> {}): {}", api_config.name, upstream.url(), e);
+            return upstream_error_response(&e, config, cache).await;
+        }
+    };
+
+    // 上游拒绝升级时按普通响应返回
+    let version = response.version();
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        forwarding::prepare_response(response.headers_mut(), version);
+        response.headers_mut().insert("Server", "RouterWay".parse().unwrap());
+        return response;
+    }
+
+    let upstream_upgrade = hyper::upgrade::on(&mut response);
+    let mut client_response = Response::new(Body::empty());
+    *client_response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    *client_response.headers_mut() = response.headers().clone();
+    forwarding::prepare_response(client_response.headers_mut(), version);
+    websocket::restore_upgrade_headers(client_response.headers_mut());
+    client_response.headers_mut().insert("Server", "RouterWay".parse().unwrap());
+
+    let idle_timeout = api_config.get_websocket_idle_timeout();
+    tokio::spawn(async move {
+        match tokio::try_join!(client_upgrade, upstream_upgrade) {
+            Ok((client, upstream_io)) => {
+                websocket::splice(client, upstream_io, upstream, idle_timeout).await
             }
+            Err(e) => warn!("WebSocket 升级失败 ({}): {}", upstream.url(), e),
+        }
+    });
+
+    client_response
+}
+
+// 所有上游都不可用（不健康、被摘除或熔断）时返回 503
+async fn no_upstream_response(config: &Config, cache: &FileCache) -> Response<Body> {
+    match handle_error_page(StatusCode::SERVICE_UNAVAILABLE, config, cache).await {
+        Ok(response) => response,
+        Err(_) => create_error_response(StatusCode::SERVICE_UNAVAILABLE, "No healthy upstream"),
+    }
+}
+
+// 超时返回 504，其余上游错误返回 502
+async fn upstream_error_response(
+    error: &UpstreamError,
+    config: &Config,
+    cache: &FileCache,
+) -> Response<Body> {
+    let status = match error {
+        UpstreamError::Timeout | UpstreamError::ConnectTimeout => StatusCode::GATEWAY_TIMEOUT,
+        UpstreamError::Failed(_) => StatusCode::BAD_GATEWAY,
+    };
+    match handle_error_page(status, config, cache).await {
+        Ok(response) => response,
+        Err(_) => create_error_response(status, "Proxy request failed"),
+    }
+}
+
+// 上游没有压缩时按配置对响应体做流式压缩
+fn compress_proxy_response(
+    response: Response<Body>,
+    config: &Config,
+    negotiation_headers: &HeaderMap,
+) -> Response<Body> {
+    let status = response.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || status == StatusCode::PARTIAL_CONTENT
+        || response.headers().contains_key(CONTENT_ENCODING)
+    {
+        return response;
+    }
+
+    let mime_type = match response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        Some(mime_type) => mime_type,
+        None => return response,
+    };
+    // gRPC 自带消息压缩，且状态在 trailers 中，改写响应体会丢失 trailers
+    if mime_type.starts_with("application/grpc") {
+        return response;
+    }
+    let size = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let encoding = match compression::negotiate(config, negotiation_headers, mime_type, size) {
+        Some(encoding) => encoding,
+        None => return response,
+    };
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(CONTENT_ENCODING, encoding.as_str().parse().unwrap());
+    parts.headers.append(VARY, "Accept-Encoding".parse().unwrap());
+
+    // 响应体已改变，强 ETag 降级为弱 ETag
+    if let Some(etag) = parts.headers.get(ETAG).and_then(|v| v.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            let weak = format!("W/{}", etag);
+            parts.headers.insert(ETAG, weak.parse().unwrap());
         }
     }
+
+    Response::from_parts(parts, compression::compress_body(body, encoding))
 }
 
 async fn handle_static_file(
     path: &str,
+    method: &Method,
+    headers: &HeaderMap,
     config: &Config,
     cache: &FileCache,
 ) -> Result<Response<Body>> {
@@ -196,36 +1110,135 @@ async fn handle_static_file(
         return Ok(create_error_response(StatusCode::FORBIDDEN, "Access denied"));
     }
 
+    let accepted = compression::accepted_encodings(headers);
+
     // 优先从缓存获取 - 使用零拷贝
     if let Some(cached_file) = cache.get_fast(normalized_path) {
+        // 客户端接受且缓存中存在预压缩变体时优先返回变体
+        let variant = compression::pick(&accepted, cached_file.precompressed).and_then(|encoding| {
+            let variant_key = format!("{}.{}", normalized_path, encoding.extension());
+            cache.get_fast(&variant_key).map(|file| (encoding, file))
+        });
+
         debug!("从缓存返回文件: {}", normalized_path);
-        
-        // 零拷贝响应 - 直接使用Arc引用
-        return Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", &cached_file.mime_type)
-            .header("Cache-Control", "public, max-age=3600")
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Server", "RouterWay")
-            .body(Body::from(cached_file.get_content()))?);
+
+        if let Some((encoding, file)) = variant {
+            return serve_cached_file(method, headers, &file, &cached_file.mime_type, Some(encoding))
+                .await;
+        }
+
+        return serve_original(method, headers, normalized_path, &cached_file, config, cache).await;
     }
 
     // 缓存未命中时的快速文件读取
-    let file_path = config.get_root_directory().join(normalized_path);
-    
+    let original_path = config.get_root_directory().join(normalized_path);
+
+    // 按客户端偏好探测磁盘上的预压缩兄弟文件
+    let mut selected = None;
+    for encoding in accepted {
+        let variant_path = append_extension(&original_path, encoding.extension());
+        if let Ok(metadata) = fs::metadata(&variant_path).await {
+            if metadata.is_file() {
+                selected = Some((Some(encoding), variant_path, metadata));
+                break;
+            }
+        }
+    }
+
+    let (encoding, file_path, metadata) = match selected {
+        Some(selected) => selected,
+        None => match fs::metadata(&original_path).await {
+            Ok(metadata) => (None, original_path, metadata),
+            Err(_) => return handle_error_page(StatusCode::NOT_FOUND, config, cache).await,
+        },
+    };
+
     debug!("从文件系统读取: {}", file_path.display());
 
+    // 按淘汰/准入策略把文件放入缓存，同一路径的并发未命中只读一次磁盘
+    if cache.is_cacheable(metadata.len()) {
+        // 原文件的访问已由 get_fast 记录，变体的访问在这里记录
+        let key = match encoding {
+            Some(encoding) => {
+                let key = format!("{}.{}", normalized_path, encoding.extension());
+                cache.record_access(&key);
+                key
+            }
+            None => normalized_path.to_string(),
+        };
+
+        return match (cache.get_or_load(&key, &file_path).await, encoding) {
+            (Some(file), Some(encoding)) => {
+                // 与缓存中的原文件使用相同的类型推断，命中与未命中时返回的类型一致
+                let mime_type = mime_guess::from_path(normalized_path).first_or_octet_stream();
+                serve_cached_file(method, headers, &file, mime_type.as_ref(), Some(encoding)).await
+            }
+            (Some(file), None) => {
+                serve_original(method, headers, normalized_path, &file, config, cache).await
+            }
+            (None, _) => handle_error_page(StatusCode::NOT_FOUND, config, cache).await,
+        };
+    }
+
+    let last_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mime_type = get_mime_type(normalized_path);
+    let mut etag = conditional::make_etag(metadata.len(), last_modified);
+
+    // 超过缓存阈值的大文件不整体读入内存，流式发送或内存映射，内存占用与文件大小无关
+    if metadata.len() > config.get_max_cached_file_size() {
+        if let Some(response) = check_preconditions(method, headers, &etag, last_modified)? {
+            return Ok(response);
+        }
+
+        let source = FileSource::Disk {
+            path: file_path,
+            len: metadata.len(),
+            mode: config.get_large_file_mode(),
+        };
+        return build_file_response(headers, source, mime_type, encoding, &etag, last_modified)
+            .await;
+    }
+
+    // 没有预压缩文件时按配置即时压缩
+    let compress_with = match encoding {
+        Some(_) => None,
+        None => compression::negotiate(config, headers, mime_type, Some(metadata.len())),
+    };
+    if let Some(compress_with) = compress_with {
+        etag = conditional::variant_etag(&etag, compress_with);
+    }
+
+    // 条件请求命中时无需读取文件内容
+    if let Some(response) = check_preconditions(method, headers, &etag, last_modified)? {
+        return Ok(response);
+    }
+
+    // 未进入缓存的文件每次请求都要重新压缩，从磁盘流式读取并以最快级别流式压缩
+    if let Some(compress_with) = compress_with {
+        let source = FileSource::Disk {
+            path: file_path,
+            len: metadata.len(),
+            mode: LargeFileMode::Stream,
+        };
+        return match source.full().await {
+            Ok(body) => Ok(static_response_builder(StatusCode::OK, &etag, last_modified)
+                .header("Content-Type", mime_type)
+                .header("Content-Encoding", compress_with.as_str())
+                .body(compression::compress_body(body, compress_with))?),
+            Err(_) => handle_error_page(StatusCode::NOT_FOUND, config, cache).await,
+        };
+    }
+
     match fs::read(&file_path).await {
         Ok(content) => {
-            let mime_type = get_mime_type(normalized_path);
-            
-            Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", mime_type)
-                .header("Cache-Control", "public, max-age=3600")
-                .header("Access-Control-Allow-Origin", "*")
-                .header("Server", "RouterWay")
-                .body(Body::from(content))?)
+            let source = FileSource::Memory(Bytes::from(content));
+            build_file_response(headers, source, mime_type, encoding, &etag, last_modified).await
         }
         Err(_) => {
             // 尝试返回404错误页面
@@ -234,6 +1247,193 @@ async fn handle_static_file(
     }
 }
 
+// 返回未预压缩的缓存文件，按配置即时压缩
+async fn serve_original(
+    method: &Method,
+    headers: &HeaderMap,
+    cache_key: &str,
+    file: &CachedFile,
+    config: &Config,
+    cache: &FileCache,
+) -> Result<Response<Body>> {
+    let size = file.size as u64;
+    match compression::negotiate(config, headers, &file.mime_type, Some(size)) {
+        Some(encoding) => {
+            let level = config.get_compression().level;
+            serve_compressed_cached_file(method, headers, cache_key, file, encoding, level, cache)
+                .await
+        }
+        None => serve_cached_file(method, headers, file, &file.mime_type, None).await,
+    }
+}
+
+async fn serve_cached_file(
+    method: &Method,
+    headers: &HeaderMap,
+    file: &CachedFile,
+    mime_type: &str,
+    encoding: Option<Encoding>,
+) -> Result<Response<Body>> {
+    if let Some(response) = check_preconditions(method, headers, &file.etag, file.last_modified)? {
+        return Ok(response);
+    }
+
+    build_file_response(
+        headers,
+        FileSource::Memory(file.get_content()),
+        mime_type,
+        encoding,
+        &file.etag,
+        file.last_modified,
+    )
+    .await
+}
+
+// 压缩结果保存在缓存条目上，同一文件的同一编码只压缩一次
+async fn serve_compressed_cached_file(
+    method: &Method,
+    headers: &HeaderMap,
+    cache_key: &str,
+    file: &CachedFile,
+    encoding: Encoding,
+    level: CompressionLevel,
+    cache: &FileCache,
+) -> Result<Response<Body>> {
+    let etag = conditional::variant_etag(&file.etag, encoding);
+    if let Some(response) = check_preconditions(method, headers, &etag, file.last_modified)? {
+        return Ok(response);
+    }
+
+    let original = file.access();
+    let compressed = match file.compressed.get(&encoding) {
+        Some(content) => content.clone(),
+        None => {
+            let content = compression::compress(original, encoding, level).await?;
+            cache.store_compressed(cache_key, &file.etag, encoding, content)
+        }
+    };
+
+    build_file_response(
+        headers,
+        FileSource::Memory(compressed),
+        &file.mime_type,
+        Some(encoding),
+        &etag,
+        file.last_modified,
+    )
+    .await
+}
+
+// 在原文件名后追加扩展名，例如 app.js -> app.js.br
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut os_string = path.as_os_str().to_os_string();
+    os_string.push(".");
+    os_string.push(extension);
+    PathBuf::from(os_string)
+}
+
+// 处理 If-Match / If-None-Match 等条件请求，返回 304 或 412
+fn check_preconditions(
+    method: &Method,
+    headers: &HeaderMap,
+    etag: &str,
+    last_modified: u64,
+) -> Result<Option<Response<Body>>> {
+    match conditional::evaluate(method, headers, etag, last_modified) {
+        Precondition::Proceed => Ok(None),
+        Precondition::NotModified => Ok(Some(
+            static_response_builder(StatusCode::NOT_MODIFIED, etag, last_modified)
+                .body(Body::empty())?,
+        )),
+        Precondition::Failed => Ok(Some(create_error_response(
+            StatusCode::PRECONDITION_FAILED,
+            "Precondition Failed",
+        ))),
+    }
+}
+
+// 根据 Range / If-Range 构建 200、206 或 416 响应
+async fn build_file_response(
+    headers: &HeaderMap,
+    source: FileSource,
+    mime_type: &str,
+    encoding: Option<Encoding>,
+    etag: &str,
+    last_modified: u64,
+) -> Result<Response<Body>> {
+    let total = source.len();
+
+    let ranges = match range::evaluate(headers, total, etag, last_modified) {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(ranges) => Some(ranges),
+        RangeRequest::Unsatisfiable => {
+            let mut response = create_error_response(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "Range Not Satisfiable",
+            );
+            response.headers_mut().insert(
+                "Content-Range",
+                format!("bytes */{}", total).parse().unwrap(),
+            );
+            response
+                .headers_mut()
+                .insert("Accept-Ranges", "bytes".parse().unwrap());
+            return Ok(response);
+        }
+    };
+
+    let status = if ranges.is_some() {
+        StatusCode::PARTIAL_CONTENT
+    } else {
+        StatusCode::OK
+    };
+    let mut builder = static_response_builder(status, etag, last_modified);
+    if let Some(encoding) = encoding {
+        builder = builder.header("Content-Encoding", encoding.as_str());
+    }
+
+    // 流式响应体需要显式给出 Content-Length，否则会退化为分块传输
+    match ranges {
+        None => Ok(builder
+            .header("Content-Type", mime_type)
+            .header("Content-Length", total)
+            .body(source.full().await?)?),
+        Some(ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+
+            Ok(builder
+                .header("Content-Type", mime_type)
+                .header("Content-Range", range.content_range(total))
+                .header("Content-Length", range.len())
+                .body(source.range(range).await?)?)
+        }
+        Some(ranges) => {
+            let boundary = range::boundary();
+            let (body, length) = source.multipart(&ranges, mime_type, &boundary).await?;
+
+            Ok(builder
+                .header(
+                    "Content-Type",
+                    format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .header("Content-Length", length)
+                .body(body)?)
+        }
+    }
+}
+
+fn static_response_builder(status: StatusCode, etag: &str, last_modified: u64) -> Builder {
+    Response::builder()
+        .status(status)
+        .header("ETag", etag)
+        .header("Last-Modified", conditional::http_date(last_modified))
+        .header("Accept-Ranges", "bytes")
+        .header("Vary", "Accept-Encoding")
+        .header("Cache-Control", "public, max-age=3600")
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Server", "RouterWay")
+}
+
 async fn handle_error_page(
     status: StatusCode,
     config: &Config,
@@ -246,16 +1446,22 @@ async fn handle_error_page(
         StatusCode::INTERNAL_SERVER_ERROR => "500.html",
         StatusCode::BAD_GATEWAY => "502.html",
         StatusCode::SERVICE_UNAVAILABLE => "503.html",
+        StatusCode::GATEWAY_TIMEOUT => "504.html",
         _ => "error.html",
     };
 
-    // 构建错误页面的相对路径（相对于Public目录）
-    let error_relative_path = format!("Errors/{}", error_file);
-    
+    // 错误页面目录在根目录下时与静态文件共用缓存，键为相对根目录的路径；
+    // 不在根目录下（例如重新加载后改到了别处）时直接读取文件
+    let error_path = config.get_error_pages_directory().join(error_file);
+    let cache_key = error_path
+        .strip_prefix(config.get_root_directory())
+        .ok()
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"));
+
     // 优先从缓存获取错误页面 - 使用零拷贝
-    if let Some(cached_file) = cache.get_fast(&error_relative_path) {
-        debug!("从缓存返回错误页面: {}", error_relative_path);
-        
+    if let Some(cached_file) = cache_key.as_deref().and_then(|key| cache.get_fast(key)) {
+        debug!("从缓存返回错误页面: {}", error_path.display());
+
         return Ok(Response::builder()
             .status(status)
             .header("Content-Type", "text/html; charset=utf-8")
@@ -265,8 +1471,6 @@ async fn handle_error_page(
     }
 
     // 缓存未命中时从文件系统读取
-    let error_path = config.get_error_pages_directory().join(error_file);
-    
     match fs::read(&error_path).await {
         Ok(content) => {
             Ok(Response::builder()
@@ -282,6 +1486,7 @@ async fn handle_error_page(
                 StatusCode::NOT_FOUND => "404 - 页面未找到",
                 StatusCode::INTERNAL_SERVER_ERROR => "500 - 内部服务器错误",
                 StatusCode::FORBIDDEN => "403 - 访问被拒绝",
+                StatusCode::GATEWAY_TIMEOUT => "504 - 网关超时",
                 _ => "发生错误",
             };
 
@@ -290,6 +1495,22 @@ async fn handle_error_page(
     }
 }
 
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"))
+}
+
+// 客户端发送请求体过慢，HTTP/1 连接随后关闭
+fn request_timeout_response(version: Version) -> Response<Body> {
+    let mut response = create_error_response(StatusCode::REQUEST_TIMEOUT, "Request Timeout");
+    if version <= Version::HTTP_11 {
+        response.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+    }
+    response
+}
+
 fn create_error_response(status: StatusCode, message: &str) -> Response<Body> {
     let html = format!(
         r#"<!DOCTYPE html>
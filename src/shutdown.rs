@@ -0,0 +1,121 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// 协调服务器关闭：通知各监听停止接受连接，并等待在途连接结束
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    connections: Arc<Connections>,
+}
+
+#[derive(Default)]
+struct Connections {
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始关闭，重复调用没有额外效果
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// 开始关闭时完成
+    pub async fn triggered(&self) {
+        self.token.cancelled().await
+    }
+
+    /// 登记一个在途连接，返回的 guard 释放时连接才算结束
+    pub fn track(&self) -> ConnectionGuard {
+        self.connections.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(Arc::clone(&self.connections))
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.connections.active.load(Ordering::SeqCst)
+    }
+
+    /// 等待所有登记的连接结束
+    pub async fn drained(&self) {
+        loop {
+            // 先注册再检查，避免错过检查之后发出的通知
+            let idle = self.connections.idle.notified();
+            if self.active_connections() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+pub struct ConnectionGuard(Arc<Connections>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// 监听 SIGINT 与 SIGTERM：第一次收到时开始优雅关闭，再次收到时立即退出
+pub fn spawn_signal_handler(shutdown: Shutdown) -> Result<()> {
+    let mut signals = Signals::new()?;
+
+    tokio::spawn(async move {
+        let name = signals.recv().await;
+        info!("🛑 收到 {}，停止接受新连接并等待在途请求完成（再次发送可立即退出）", name);
+        shutdown.trigger();
+
+        let name = signals.recv().await;
+        warn!("再次收到 {}，立即退出，仍有 {} 个连接未结束", name, shutdown.active_connections());
+        std::process::exit(1);
+    });
+
+    Ok(())
+}
+
+struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    fn new() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                interrupt: signal(SignalKind::interrupt())?,
+                terminate: signal(SignalKind::terminate())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.interrupt.recv() => "SIGINT",
+                _ = self.terminate.recv() => "SIGTERM",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "Ctrl+C"
+        }
+    }
+}
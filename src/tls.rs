@@ -0,0 +1,321 @@
+use crate::config::{CertificateConfig, TlsConfig, UpstreamTlsConfig};
+use anyhow::{Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::version::{TLS12, TLS13};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+    SupportedProtocolVersion,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+/// 监听端允许的最低 TLS 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// 本程序统一使用的加密实现
+pub fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// 读取 PEM 文件中的全部证书
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("无法打开证书文件: {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("证书文件格式错误: {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("证书文件中没有证书: {}", path.display());
+    }
+    Ok(certs)
+}
+
+/// 读取 PEM 文件中的第一个私钥（PKCS#8、PKCS#1 或 SEC1）
+pub fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("无法打开私钥文件: {}", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("私钥文件格式错误: {}", path.display()))?
+        .with_context(|| format!("私钥文件中没有私钥: {}", path.display()))
+}
+
+/// 按路由的 TLS 配置构建连接上游时使用的客户端配置
+///
+/// 未配置 CA 时信任内置的公共根证书；配置了 CA 后只信任其中的证书。`alpn` 按优先级排列。
+pub fn client_config(tls: Option<&UpstreamTlsConfig>, alpn: &[&str]) -> Result<Arc<ClientConfig>> {
+    let tls = tls.cloned().unwrap_or_default();
+    let provider = crypto_provider();
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+
+    let builder = if tls.insecure_skip_verify {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(SkipVerification(provider)))
+    } else {
+        let mut roots = RootCertStore::empty();
+        match &tls.ca_file {
+            Some(path) => {
+                for cert in load_certs(path)? {
+                    roots
+                        .add(cert)
+                        .with_context(|| format!("无效的 CA 证书: {}", path.display()))?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        builder.with_root_certificates(roots)
+    };
+
+    let mut config = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_private_key(key)?)
+            .with_context(|| format!("客户端证书与私钥不匹配: {}", cert.display()))?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => anyhow::bail!("client_cert 与 client_key 必须同时配置"),
+    };
+    config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+    Ok(Arc::new(config))
+}
+
+/// 把配置的 SNI 或 URI 中的主机名转换为握手使用的服务器名称
+pub fn server_name(host: &str) -> Result<ServerName<'static>> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    ServerName::try_from(host.to_string()).with_context(|| format!("无效的服务器名称: {}", host))
+}
+
+// 接受任何证书，但仍校验握手签名，保证对端确实持有证书对应的私钥
+#[derive(Debug)]
+struct SkipVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// 构建监听端的 TLS 配置，证书由 `CertStore` 按 SNI 提供
+pub fn server_config(tls: &TlsConfig, certs: Arc<CertStore>) -> Result<Arc<ServerConfig>> {
+    let versions: &[&'static SupportedProtocolVersion] = match tls.min_version {
+        TlsVersion::Tls12 => &[&TLS13, &TLS12],
+        TlsVersion::Tls13 => &[&TLS13],
+    };
+    let mut config = ServerConfig::builder_with_provider(crypto_provider())
+        .with_protocol_versions(versions)?
+        .with_no_client_auth()
+        .with_cert_resolver(certs);
+    config.alpn_protocols = tls.alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+    Ok(Arc::new(config))
+}
+
+/// HTTP/3 使用的 TLS 配置：QUIC 只支持 TLS 1.3，ALPN 固定为 h3
+pub fn quic_server_config(certs: Arc<CertStore>) -> Result<ServerConfig> {
+    let mut config = ServerConfig::builder_with_provider(crypto_provider())
+        .with_protocol_versions(&[&TLS13])?
+        .with_no_client_auth()
+        .with_cert_resolver(certs);
+    config.alpn_protocols = vec![b"h3".to_vec()];
+
+    Ok(config)
+}
+
+/// 监听端的证书集合
+///
+/// 握手时按 SNI 精确匹配证书中的域名，其次匹配通配符域名，都不匹配或客户端未发送 SNI 时使用第一张证书。
+/// 证书文件变化后可以在运行中重新加载，已建立的连接不受影响。
+#[derive(Debug)]
+pub struct CertStore {
+    configs: Vec<CertificateConfig>,
+    current: RwLock<Arc<CertSet>>,
+    // 上次加载时各文件的修改时间
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+#[derive(Debug)]
+struct CertSet {
+    exact: HashMap<String, Arc<CertifiedKey>>,
+    // 以通配符去掉 `*.` 后的父域名为键
+    wildcard: HashMap<String, Arc<CertifiedKey>>,
+    default: Arc<CertifiedKey>,
+}
+
+impl CertStore {
+    pub fn load(configs: &[CertificateConfig]) -> Result<Self> {
+        let modified = modified_times(configs);
+        let set = CertSet::load(configs)?;
+        Ok(Self {
+            configs: configs.to_vec(),
+            current: RwLock::new(Arc::new(set)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// 重新读取全部证书，失败时保留原有证书
+    pub fn reload(&self) -> Result<()> {
+        let modified = modified_times(&self.configs);
+        let set = CertSet::load(&self.configs)?;
+        *self.current.write().unwrap() = Arc::new(set);
+        *self.modified.lock().unwrap() = modified;
+        Ok(())
+    }
+
+    /// 任一证书、私钥或 OCSP 文件的修改时间变化时重新加载，返回是否进行了加载
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = modified_times(&self.configs);
+        {
+            let mut last = self.modified.lock().unwrap();
+            if *last == modified {
+                return Ok(false);
+            }
+            // 先记下新的修改时间，文件写到一半导致加载失败时不必每次检查都报错，写完后时间会再次变化
+            *last = modified;
+        }
+        let set = CertSet::load(&self.configs)?;
+        *self.current.write().unwrap() = Arc::new(set);
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let set = Arc::clone(&self.current.read().unwrap());
+        let name = match client_hello.server_name() {
+            Some(name) => name.to_ascii_lowercase(),
+            None => return Some(Arc::clone(&set.default)),
+        };
+
+        let matched = set.exact.get(&name).or_else(|| {
+            name.split_once('.')
+                .and_then(|(_, parent)| set.wildcard.get(parent))
+        });
+        Some(Arc::clone(matched.unwrap_or(&set.default)))
+    }
+}
+
+impl CertSet {
+    fn load(configs: &[CertificateConfig]) -> Result<Self> {
+        let provider = crypto_provider();
+        let mut exact = HashMap::new();
+        let mut wildcard = HashMap::new();
+        let mut default = None;
+
+        for config in configs {
+            let certs = load_certs(&config.cert)?;
+            let key = load_private_key(&config.key)?;
+            let mut certified = CertifiedKey::from_der(certs, key, &provider)
+                .with_context(|| format!("证书与私钥不匹配: {}", config.cert.display()))?;
+            if let Some(ocsp) = &config.ocsp {
+                let response = std::fs::read(ocsp)
+                    .with_context(|| format!("无法读取 OCSP 响应: {}", ocsp.display()))?;
+                certified.ocsp = Some(response);
+            }
+            let certified = Arc::new(certified);
+
+            let names = if config.server_names.is_empty() {
+                certificate_names(&certified.cert[0])
+                    .with_context(|| format!("无法解析证书: {}", config.cert.display()))?
+            } else {
+                config.server_names.clone()
+            };
+            if names.is_empty() && default.is_some() {
+                warn!("证书没有可用于 SNI 匹配的域名，将不会被选中: {}", config.cert.display());
+            }
+
+            // 多张证书包含同一域名时，先配置的优先
+            for name in names {
+                let name = name.to_ascii_lowercase();
+                match name.strip_prefix("*.") {
+                    Some(parent) => wildcard.entry(parent.to_string()),
+                    None => exact.entry(name),
+                }
+                .or_insert_with(|| Arc::clone(&certified));
+            }
+            default.get_or_insert(certified);
+        }
+
+        Ok(Self {
+            exact,
+            wildcard,
+            default: default.context("至少需要配置一张证书")?,
+        })
+    }
+}
+
+// 证书 SAN 扩展中的 DNS 名称（包括通配符）
+fn certificate_names(cert: &CertificateDer<'_>) -> Result<Vec<String>> {
+    let cert = webpki::EndEntityCert::try_from(cert).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    Ok(cert.valid_dns_names().map(str::to_string).collect())
+}
+
+fn modified_times(configs: &[CertificateConfig]) -> Vec<Option<SystemTime>> {
+    configs
+        .iter()
+        .flat_map(|config| [Some(&config.cert), Some(&config.key), config.ocsp.as_ref()])
+        .flatten()
+        .map(|path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// 定期检查证书文件，有变化时重新加载
+pub fn spawn_reload(certs: Arc<CertStore>, check_interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = interval(check_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match certs.reload_if_changed() {
+                Ok(true) => info!("🔐 证书文件已变化，重新加载完成"),
+                Ok(false) => {}
+                Err(e) => error!("重新加载证书失败，继续使用原有证书: {:#}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,314 @@
+use crate::server::Listeners;
+use crate::shutdown::Shutdown;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::{TcpListener, UdpSocket};
+use tracing::warn;
+#[cfg(unix)]
+use {
+    anyhow::Context,
+    std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    std::os::unix::net::UnixStream,
+    std::time::Duration,
+    tracing::{error, info},
+};
+
+// 升级时由旧进程传给新进程：继承的监听套接字与就绪通知用的套接字
+#[cfg(unix)]
+const INHERITED_FDS_ENV: &str = "ROUTERWAY_LISTEN_FDS";
+#[cfg(unix)]
+const READY_FD_ENV: &str = "ROUTERWAY_READY_FD";
+// systemd 套接字激活：LISTEN_FDS 个套接字，从 3 号描述符开始
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+// 新进程加载配置、预热缓存后才算就绪，超过该时间视为升级失败
+#[cfg(unix)]
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 启动时从环境变量取得的升级交接信息：继承的监听套接字与通知旧进程就绪用的描述符
+///
+/// 多线程运行时中修改环境变量并不安全，`take_from_env` 须在创建 tokio 运行时之前调用。
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Handoff {
+    #[cfg(unix)]
+    listen_fds: Vec<RawFd>,
+    #[cfg(unix)]
+    ready_fd: Option<RawFd>,
+}
+
+impl Handoff {
+    /// 读取并清除环境变量中的交接信息，之后启动的子进程不会再看到它们
+    pub fn take_from_env() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            let handoff = parse(|name| std::env::var(name).ok(), std::process::id());
+            for name in [INHERITED_FDS_ENV, READY_FD_ENV, "LISTEN_FDS", "LISTEN_PID", "LISTEN_FDNAMES"] {
+                std::env::remove_var(name);
+            }
+            handoff
+        }
+        #[cfg(not(unix))]
+        Ok(Self::default())
+    }
+
+    /// 取出继承的监听套接字，没有时返回空集合
+    pub fn inherited(&mut self) -> Result<Inherited> {
+        let mut inherited = Inherited::default();
+        #[cfg(unix)]
+        {
+            let fds = std::mem::take(&mut self.listen_fds);
+            if !fds.is_empty() {
+                info!("🧦 使用继承的 {} 个监听套接字", fds.len());
+            }
+            for fd in fds {
+                // 之后启动的子进程不应再继承这些描述符，升级时会另行复制
+                set_inheritable(fd, false)?;
+                match socket_type(fd)? {
+                    libc::SOCK_STREAM => {
+                        let listener = TcpListener::from(unsafe { OwnedFd::from_raw_fd(fd) });
+                        inherited.tcp.insert(listener.local_addr()?.port(), listener);
+                    }
+                    libc::SOCK_DGRAM => {
+                        let socket = UdpSocket::from(unsafe { OwnedFd::from_raw_fd(fd) });
+                        inherited.udp.insert(socket.local_addr()?.port(), socket);
+                    }
+                    _ => {
+                        warn!("继承的描述符 {} 不是 TCP 或 UDP 套接字，已忽略", fd);
+                        drop(unsafe { OwnedFd::from_raw_fd(fd) });
+                    }
+                }
+            }
+        }
+        Ok(inherited)
+    }
+
+    /// 作为升级启动的新进程时，通知旧进程已开始服务，旧进程随后排空连接并退出
+    pub fn notify_ready(&mut self) {
+        #[cfg(unix)]
+        if let Some(fd) = self.ready_fd.take() {
+            let mut stream = UnixStream::from(unsafe { OwnedFd::from_raw_fd(fd) });
+            if let Err(e) = std::io::Write::write_all(&mut stream, b"1") {
+                warn!("通知旧进程失败: {}", e);
+            }
+        }
+    }
+}
+
+// 旧进程传入的描述符优先；systemd 的 LISTEN_PID 与当前进程不符时说明变量是从父进程误传下来的
+#[cfg(unix)]
+fn parse(var: impl Fn(&str) -> Option<String>, pid: u32) -> Result<Handoff> {
+    let listen_fds = match (var(INHERITED_FDS_ENV), var("LISTEN_FDS")) {
+        (Some(value), _) => value
+            .split(',')
+            .map(|fd| fd.parse().with_context(|| format!("无效的继承描述符: {}", value)))
+            .collect::<Result<_>>()?,
+        (None, Some(count)) if var("LISTEN_PID").is_none_or(|p| p == pid.to_string()) => {
+            let count: RawFd = count
+                .parse()
+                .with_context(|| format!("无效的 LISTEN_FDS: {}", count))?;
+            (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count).collect()
+        }
+        _ => Vec::new(),
+    };
+    let ready_fd = var(READY_FD_ENV)
+        .map(|fd| fd.parse().with_context(|| format!("无效的就绪通知描述符: {}", fd)))
+        .transpose()?;
+
+    Ok(Handoff { listen_fds, ready_fd })
+}
+
+/// 从旧进程或 systemd 继承的监听套接字，按本地端口取用
+#[derive(Default)]
+pub struct Inherited {
+    tcp: HashMap<u16, TcpListener>,
+    udp: HashMap<u16, UdpSocket>,
+}
+
+impl Inherited {
+    pub fn take_tcp(&mut self, port: u16) -> Option<TcpListener> {
+        self.tcp.remove(&port)
+    }
+
+    pub fn take_udp(&mut self, port: u16) -> Option<UdpSocket> {
+        self.udp.remove(&port)
+    }
+
+    /// 关闭配置中已不再使用的继承套接字
+    pub fn close_unused(self) {
+        for port in self.tcp.keys() {
+            warn!("继承的 TCP 端口 {} 不在当前配置中，已关闭", port);
+        }
+        for port in self.udp.keys() {
+            warn!("继承的 UDP 端口 {} 不在当前配置中，已关闭", port);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn socket_type(fd: RawFd) -> Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("继承的描述符 {} 不是套接字", fd));
+    }
+    Ok(value)
+}
+
+#[cfg(unix)]
+fn set_inheritable(fd: RawFd, inheritable: bool) -> Result<()> {
+    let flags = if inheritable { 0 } else { libc::FD_CLOEXEC };
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("无法设置描述符 {} 的继承标志", fd));
+    }
+    Ok(())
+}
+
+/// 收到 SIGUSR2 时用当前的可执行文件与参数启动新进程并交出监听套接字，
+/// 新进程就绪后当前进程开始优雅关闭；新进程启动失败时继续运行
+pub fn spawn_signal_handler(listeners: &Listeners, shutdown: Shutdown) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // 保留一份监听套接字的副本，当前进程关闭自己的监听后新进程仍可继续接受连接
+        let mut sockets: Vec<OwnedFd> = Vec::new();
+        let tcp = [
+            Some(&listeners.http),
+            listeners.https.as_ref(),
+            listeners.redirect.as_ref(),
+            listeners.admin.as_ref(),
+        ];
+        for listener in tcp.into_iter().flatten() {
+            sockets.push(listener.try_clone()?.into());
+        }
+        if let Some(socket) = &listeners.quic {
+            sockets.push(socket.try_clone()?.into());
+        }
+
+        let mut user2 = signal(SignalKind::user_defined2())?;
+        tokio::spawn(async move {
+            while user2.recv().await.is_some() {
+                info!("🔁 收到 SIGUSR2，启动新进程接管监听套接字");
+                match spawn_successor(&sockets).await {
+                    Ok(pid) => {
+                        info!("✅ 新进程 {} 已就绪，当前进程停止接受新连接并退出", pid);
+                        shutdown.trigger();
+                        return;
+                    }
+                    Err(e) => error!("升级失败，继续使用当前进程: {:#}", e),
+                }
+            }
+        });
+    }
+    #[cfg(not(unix))]
+    let _ = (listeners, shutdown);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn spawn_successor(sockets: &[OwnedFd]) -> Result<u32> {
+    use tokio::io::AsyncReadExt;
+
+    // 按 argv[0] 启动，可执行文件被替换后 /proc/self/exe 指向的是已删除的旧文件
+    let mut args: Vec<_> = std::env::args_os().collect();
+    if args.is_empty() {
+        anyhow::bail!("无法获取可执行文件路径");
+    }
+    let program = args.remove(0);
+    let (ready, child_ready) = UnixStream::pair()?;
+
+    let fds: Vec<RawFd> = sockets.iter().map(AsRawFd::as_raw_fd).collect();
+    for &fd in fds.iter().chain([&child_ready.as_raw_fd()]) {
+        set_inheritable(fd, true)?;
+    }
+    let spawned = tokio::process::Command::new(&program)
+        .args(args)
+        .env(
+            INHERITED_FDS_ENV,
+            fds.iter().map(RawFd::to_string).collect::<Vec<_>>().join(","),
+        )
+        .env(READY_FD_ENV, child_ready.as_raw_fd().to_string())
+        .env_remove("LISTEN_FDS")
+        .env_remove("LISTEN_PID")
+        .env_remove("LISTEN_FDNAMES")
+        .spawn();
+    for &fd in &fds {
+        set_inheritable(fd, false)?;
+    }
+    drop(child_ready);
+    let mut child = spawned.with_context(|| format!("无法启动新进程: {}", program.to_string_lossy()))?;
+    let pid = child.id().unwrap_or_default();
+
+    ready.set_nonblocking(true)?;
+    let mut ready = tokio::net::UnixStream::from_std(ready)?;
+    let mut buf = [0u8; 1];
+    match tokio::time::timeout(READY_TIMEOUT, ready.read(&mut buf)).await {
+        Ok(Ok(1)) => Ok(pid),
+        Ok(_) => {
+            let status = child.wait().await?;
+            anyhow::bail!("新进程 {} 未就绪就退出了: {}", pid, status)
+        }
+        Err(_) => {
+            let _ = child.start_kill();
+            anyhow::bail!("新进程 {} 在 {} 秒内未就绪，已结束", pid, READY_TIMEOUT.as_secs())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn parse_vars(vars: &[(&str, &str)], pid: u32) -> Result<Handoff> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        parse(|name| vars.get(name).cloned(), pid)
+    }
+
+    #[test]
+    fn parses_inherited_fds() {
+        let handoff = parse_vars(&[(INHERITED_FDS_ENV, "3,4"), (READY_FD_ENV, "7")], 1).unwrap();
+        assert_eq!(handoff.listen_fds, vec![3, 4]);
+        assert_eq!(handoff.ready_fd, Some(7));
+
+        assert!(parse_vars(&[(INHERITED_FDS_ENV, "3,x")], 1).is_err());
+        assert!(parse_vars(&[(READY_FD_ENV, "ready")], 1).is_err());
+        assert_eq!(parse_vars(&[], 1).unwrap(), Handoff::default());
+    }
+
+    #[test]
+    fn parses_systemd_listen_fds() {
+        let handoff = parse_vars(&[("LISTEN_FDS", "2"), ("LISTEN_PID", "42")], 42).unwrap();
+        assert_eq!(handoff.listen_fds, vec![3, 4]);
+
+        // 未设置 LISTEN_PID 时同样接受
+        let handoff = parse_vars(&[("LISTEN_FDS", "1")], 42).unwrap();
+        assert_eq!(handoff.listen_fds, vec![3]);
+
+        // 传给其他进程的描述符不能使用
+        let handoff = parse_vars(&[("LISTEN_FDS", "2"), ("LISTEN_PID", "41")], 42).unwrap();
+        assert!(handoff.listen_fds.is_empty());
+
+        assert!(parse_vars(&[("LISTEN_FDS", "two")], 42).is_err());
+    }
+
+    #[test]
+    fn prefers_inherited_fds_over_systemd() {
+        let vars = [(INHERITED_FDS_ENV, "5"), ("LISTEN_FDS", "2"), ("LISTEN_PID", "42")];
+        assert_eq!(parse_vars(&vars, 42).unwrap().listen_fds, vec![5]);
+    }
+}
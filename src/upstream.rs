@@ -0,0 +1,680 @@
+use crate::balancer::{Balancer, HashOn, LoadBalance};
+use crate::circuit_breaker::{BreakerState, CircuitBreaker};
+use crate::config::{
+    ApiConfig, CircuitBreakerConfig, HealthCheckConfig, Http2Config, OutlierDetectionConfig,
+    PrefixMode, RetryConfig, UpstreamTarget,
+};
+use crate::connector::HttpsConnector;
+use crate::limits;
+use crate::tls;
+use anyhow::{Context, Result};
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::{Body, Client, Request, Response, StatusCode, Uri, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+/// 与上游通信使用的 HTTP 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UpstreamProtocol {
+    #[default]
+    #[serde(rename = "http1")]
+    Http1,
+    // https 上游经 ALPN 协商 h2，http 上游直接使用 h2c
+    #[serde(rename = "http2")]
+    Http2,
+    // https 上游按 ALPN 协商结果选择，http 上游使用 HTTP/1.1
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+#[derive(Debug)]
+pub enum UpstreamError {
+    // 等待响应头超时，对应 504
+    Timeout,
+    // 建立连接超时，对应 504
+    ConnectTimeout,
+    // 其余连接或协议错误，对应 502
+    Failed(hyper::Error),
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpstreamError::Timeout => write!(f, "上游响应超时"),
+            UpstreamError::ConnectTimeout => write!(f, "连接上游超时"),
+            UpstreamError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl UpstreamError {
+    /// 请求是否在建立连接阶段就失败了（上游没有收到请求）
+    pub fn is_connect(&self) -> bool {
+        match self {
+            UpstreamError::ConnectTimeout => true,
+            UpstreamError::Failed(e) => e.is_connect(),
+            UpstreamError::Timeout => false,
+        }
+    }
+}
+
+/// 路由下的单个上游目标及其统计
+pub struct Upstream {
+    url: String,
+    base: Uri,
+    weight: u32,
+    // 已发出请求但尚未收到响应头的请求数，加上打开的隧道数
+    active: AtomicUsize,
+    requests: AtomicU64,
+    failures: AtomicU64,
+    // 主动健康检查的结论，以及连续成功/失败的探测次数
+    healthy: AtomicBool,
+    probe_successes: AtomicU32,
+    probe_failures: AtomicU32,
+    // 被动异常检测：连续失败的请求数与摘除截止时间（毫秒时间戳）
+    consecutive_errors: AtomicU32,
+    ejected_until: AtomicU64,
+    breaker: Option<CircuitBreaker>,
+    // WebSocket 隧道：当前打开数与累计数
+    tunnels_open: AtomicUsize,
+    tunnels_total: AtomicU64,
+}
+
+impl Upstream {
+    pub fn new(target: &UpstreamTarget, breaker: Option<&CircuitBreakerConfig>) -> Option<Self> {
+        let base: Uri = target.url.parse().ok()?;
+        base.scheme()?;
+        base.authority()?;
+
+        Some(Self {
+            url: target.url.clone(),
+            base,
+            weight: target.weight,
+            active: AtomicUsize::new(0),
+            requests: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            healthy: AtomicBool::new(true),
+            probe_successes: AtomicU32::new(0),
+            probe_failures: AtomicU32::new(0),
+            consecutive_errors: AtomicU32::new(0),
+            ejected_until: AtomicU64::new(0),
+            breaker: breaker.cloned().map(CircuitBreaker::new),
+            tunnels_open: AtomicUsize::new(0),
+            tunnels_total: AtomicU64::new(0),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        now_millis() < self.ejected_until.load(Ordering::Relaxed)
+    }
+
+    /// 健康、未被摘除且熔断器允许请求的上游才参与负载均衡
+    pub fn is_available(&self) -> bool {
+        self.is_healthy()
+            && !self.is_ejected()
+            && self.breaker.as_ref().is_none_or(CircuitBreaker::can_attempt)
+    }
+
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.breaker.as_ref().map(CircuitBreaker::state)
+    }
+
+    /// 记录一次主动探测结果，达到阈值时切换健康状态
+    pub fn record_probe(&self, success: bool, config: &HealthCheckConfig) {
+        if success {
+            self.probe_failures.store(0, Ordering::Relaxed);
+            let successes = self.probe_successes.fetch_add(1, Ordering::Relaxed) + 1;
+            if successes >= config.healthy_threshold && !self.healthy.swap(true, Ordering::Relaxed) {
+                info!("✅ 上游恢复健康: {}", self.url);
+            }
+        } else {
+            self.probe_successes.store(0, Ordering::Relaxed);
+            let failures = self.probe_failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures >= config.unhealthy_threshold && self.healthy.swap(false, Ordering::Relaxed) {
+                warn!("❌ 上游健康检查失败，标记为不健康: {}", self.url);
+            }
+        }
+    }
+
+    // 记录一次代理请求的结果，更新熔断器；连续失败达到阈值时在冷却期内摘除该上游
+    fn record_result(&self, success: bool, config: Option<&OutlierDetectionConfig>) {
+        if let Some(breaker) = &self.breaker {
+            breaker.record(success, &self.url);
+        }
+
+        let config = match config {
+            Some(config) => config,
+            None => return,
+        };
+
+        if success {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        let errors = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if errors >= config.consecutive_failures {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            self.ejected_until
+                .store(now_millis() + config.cool_down_ms, Ordering::Relaxed);
+            warn!("⛔ 上游连续失败 {} 次，摘除 {} ms: {}", errors, config.cool_down_ms, self.url);
+        }
+    }
+
+    /// 登记一条打开的隧道，隧道存续期间同时计入活跃请求数以便 least-conn 感知长连接
+    pub fn open_tunnel(&self) -> TunnelGuard<'_> {
+        self.tunnels_open.fetch_add(1, Ordering::Relaxed);
+        self.tunnels_total.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+        TunnelGuard { upstream: self }
+    }
+
+    // 等待响应头期间计入活跃请求数，请求被取消时同样释放
+    fn start_request(&self) -> ActiveGuard<'_> {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ActiveGuard { upstream: self }
+    }
+
+    /// 健康检查请求的地址：上游主机加上检查路径
+    pub fn probe_uri(&self, path: &str) -> Option<Uri> {
+        Uri::builder()
+            .scheme(self.base.scheme()?.clone())
+            .authority(self.base.authority()?.clone())
+            .path_and_query(path)
+            .build()
+            .ok()
+    }
+
+    /// 用请求的原始路径和查询串构建该上游的地址
+    ///
+    /// 路径保持客户端发送时的编码形式，`%2F`、非 ASCII 字符的编码都原样转发。
+    /// 原始路径不以 `from` 为前缀（按解码后的字节比较）或结果不是合法 URI 时返回 None。
+    pub fn target_uri(&self, api: &ApiConfig, raw_path: &str, query: Option<&str>) -> Option<Uri> {
+        let rest = strip_raw_prefix(raw_path, &api.from)?;
+
+        let base = match api.prefix_mode {
+            PrefixMode::Replace => self.base.path().trim_end_matches('/'),
+            PrefixMode::Strip => "",
+        };
+        let mut path_and_query = format!("{}{}", base, rest);
+        if !path_and_query.starts_with('/') {
+            path_and_query.insert(0, '/');
+        }
+        if let Some(query) = query {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+
+        Uri::builder()
+            .scheme(self.base.scheme()?.clone())
+            .authority(self.base.authority()?.clone())
+            .path_and_query(path_and_query)
+            .build()
+            .ok()
+    }
+}
+
+/// 隧道关闭时释放计数
+pub struct TunnelGuard<'a> {
+    upstream: &'a Upstream,
+}
+
+impl Drop for TunnelGuard<'_> {
+    fn drop(&mut self) {
+        self.upstream.tunnels_open.fetch_sub(1, Ordering::Relaxed);
+        self.upstream.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 请求收到响应头或被取消时释放活跃计数
+struct ActiveGuard<'a> {
+    upstream: &'a Upstream,
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.upstream.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 上游统计快照
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStats {
+    pub api: String,
+    pub url: String,
+    pub weight: u32,
+    pub active: usize,
+    pub requests: u64,
+    pub failures: u64,
+    pub healthy: bool,
+    pub ejected: bool,
+    // 未启用熔断器时为 null
+    pub circuit: Option<&'static str>,
+    pub tunnels_open: usize,
+    pub tunnels_total: u64,
+}
+
+/// 连接上游使用的客户端，同时支持 http 与 https
+pub type UpstreamClient = Client<HttpsConnector>;
+
+/// 单个 API 路由的上游集合，连接池在所有上游与请求间共享
+pub struct UpstreamPool {
+    name: String,
+    client: UpstreamClient,
+    protocol: UpstreamProtocol,
+    response_timeout: Duration,
+    upstreams: Vec<Arc<Upstream>>,
+    balancer: Balancer,
+    hash_on: HashOn,
+    health_check: Option<HealthCheckConfig>,
+    outlier_detection: Option<OutlierDetectionConfig>,
+    retry: Option<RetryConfig>,
+    // 创建时的路由与 HTTP/2 配置，重新加载时相同则沿用该连接池
+    settings: String,
+}
+
+impl UpstreamPool {
+    pub fn new(api: &ApiConfig, http2: &Http2Config) -> Result<Self> {
+        let mut http = HttpConnector::new();
+        http.set_connect_timeout(Some(api.get_connect_timeout()));
+        http.set_nodelay(true);
+        http.set_keepalive(Some(Duration::from_secs(60)));
+
+        let tls_config = api.tls.as_ref();
+        let server_name = tls_config
+            .and_then(|tls| tls.sni.as_deref())
+            .map(tls::server_name)
+            .transpose()?;
+        let alpn: &[&str] = match api.upstream_protocol {
+            UpstreamProtocol::Http1 => &["http/1.1"],
+            UpstreamProtocol::Http2 => &["h2"],
+            UpstreamProtocol::Auto => &["h2", "http/1.1"],
+        };
+        let tls_client = tls::client_config(tls_config, alpn)?;
+        let connector = HttpsConnector::new(http, tls_client, server_name);
+
+        let client = Client::builder()
+            .pool_max_idle_per_host(api.pool_max_idle)
+            .pool_idle_timeout(api.get_pool_idle_timeout())
+            .http2_only(api.upstream_protocol == UpstreamProtocol::Http2)
+            .http2_initial_stream_window_size(http2.initial_stream_window_size)
+            .http2_initial_connection_window_size(http2.initial_connection_window_size)
+            .http2_adaptive_window(http2.adaptive_window)
+            .http2_keep_alive_interval(http2.get_keep_alive_interval())
+            .http2_keep_alive_timeout(http2.get_keep_alive_timeout())
+            .build(connector);
+
+        // 配置加载时已校验过地址
+        let upstreams: Vec<Arc<Upstream>> = api
+            .get_targets()
+            .iter()
+            .filter_map(|target| Upstream::new(target, api.circuit_breaker.as_ref()))
+            .map(Arc::new)
+            .collect();
+        let balancer = Balancer::new(api.load_balance, &upstreams);
+
+        Ok(Self {
+            name: api.name.clone(),
+            client,
+            protocol: api.upstream_protocol,
+            response_timeout: api.get_response_timeout(),
+            upstreams,
+            balancer,
+            hash_on: api.hash_on.clone().unwrap_or(HashOn::ClientIp),
+            health_check: api.health_check.clone(),
+            outlier_detection: api.outlier_detection.clone(),
+            retry: api.retry.clone(),
+            settings: pool_settings(api, http2)?,
+        })
+    }
+
+    pub fn retry(&self) -> Option<&RetryConfig> {
+        self.retry.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn client(&self) -> &UpstreamClient {
+        &self.client
+    }
+
+    /// 发往上游的请求使用的 HTTP 版本
+    ///
+    /// HTTP/1 连接只接受 1.x 的请求，客户端经 HTTP/2 或 HTTP/3 访问时改为 HTTP/1.1。
+    pub fn request_version(&self, client_version: Version) -> Version {
+        match (self.protocol, client_version) {
+            (UpstreamProtocol::Http2, _) => Version::HTTP_2,
+            (_, Version::HTTP_09 | Version::HTTP_10) => client_version,
+            _ => Version::HTTP_11,
+        }
+    }
+
+    pub fn upstreams(&self) -> &[Arc<Upstream>] {
+        &self.upstreams
+    }
+
+    pub fn health_check(&self) -> Option<&HealthCheckConfig> {
+        self.health_check.as_ref()
+    }
+
+    /// 按负载均衡策略为请求选择上游
+    ///
+    /// 优先避开 `avoid` 中的上游（例如重试前刚失败的上游），没有其他可用上游时才重新选择它们。
+    /// 选中上游的熔断器在并发竞争中拒绝了本次请求时，排除该上游后重新选择；都不可用时返回 None。
+    pub fn select(
+        &self,
+        headers: &HeaderMap,
+        client_ip: IpAddr,
+        avoid: &[Arc<Upstream>],
+    ) -> Option<Arc<Upstream>> {
+        let hash_key = match self.balancer.policy() {
+            LoadBalance::ConsistentHash => self.hash_on.key(headers, client_ip),
+            _ => None,
+        };
+        let avoided: Vec<usize> = (0..self.upstreams.len())
+            .filter(|&index| avoid.iter().any(|u| Arc::ptr_eq(u, &self.upstreams[index])))
+            .collect();
+
+        let picked = self.select_excluding(hash_key.as_deref(), avoided.clone());
+        if picked.is_some() || avoided.is_empty() {
+            return picked;
+        }
+        self.select_excluding(hash_key.as_deref(), Vec::new())
+    }
+
+    fn select_excluding(
+        &self,
+        hash_key: Option<&str>,
+        mut excluded: Vec<usize>,
+    ) -> Option<Arc<Upstream>> {
+        loop {
+            let index = self.balancer.pick(&self.upstreams, hash_key, &excluded)?;
+            let upstream = &self.upstreams[index];
+            if upstream.breaker.as_ref().is_none_or(CircuitBreaker::try_acquire) {
+                return Some(Arc::clone(upstream));
+            }
+            excluded.push(index);
+        }
+    }
+
+    /// 向选中的上游发送请求并等待响应头，响应体仍以流的形式交给调用方
+    ///
+    /// 响应体的每次读取同样受 `response_timeout` 限制；gRPC 与 SSE 的消息间隔由业务决定，不设读取超时。
+    pub async fn request(
+        &self,
+        upstream: &Upstream,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, UpstreamError> {
+        upstream.requests.fetch_add(1, Ordering::Relaxed);
+        let active = upstream.start_request();
+        let result = timeout(self.response_timeout, self.client.request(req)).await;
+        drop(active);
+
+        let result = match result {
+            Ok(Ok(response))
+                if response.status() == StatusCode::SWITCHING_PROTOCOLS
+                    || is_streaming(response.headers()) =>
+            {
+                Ok(response)
+            }
+            Ok(Ok(response)) => {
+                let (parts, body) = response.into_parts();
+                let (body, _) = limits::timeout_body(body, self.response_timeout);
+                Ok(Response::from_parts(parts, body))
+            }
+            Ok(Err(e)) if is_timeout(&e) => Err(UpstreamError::ConnectTimeout),
+            Ok(Err(e)) => Err(UpstreamError::Failed(e)),
+            Err(_) => Err(UpstreamError::Timeout),
+        };
+        if result.is_err() {
+            upstream.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        let success = matches!(&result, Ok(response) if !response.status().is_server_error());
+        upstream.record_result(success, self.outlier_detection.as_ref());
+        result
+    }
+
+    pub fn stats(&self) -> Vec<UpstreamStats> {
+        self.upstreams
+            .iter()
+            .map(|upstream| UpstreamStats {
+                api: self.name.clone(),
+                url: upstream.url.clone(),
+                weight: upstream.weight,
+                active: upstream.active(),
+                requests: upstream.requests.load(Ordering::Relaxed),
+                failures: upstream.failures.load(Ordering::Relaxed),
+                healthy: upstream.is_healthy(),
+                ejected: upstream.is_ejected(),
+                circuit: upstream.breaker_state().map(|state| state.as_str()),
+                tunnels_open: upstream.tunnels_open.load(Ordering::Relaxed),
+                tunnels_total: upstream.tunnels_total.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// 按 API 名称索引的上游集合
+pub struct UpstreamClients {
+    pools: HashMap<String, Arc<UpstreamPool>>,
+}
+
+impl UpstreamClients {
+    pub fn new(apis: &[ApiConfig], http2: &Http2Config) -> Result<Self> {
+        Self::build(apis, http2, None)
+    }
+
+    /// 重新加载时创建：配置未变的路由沿用原连接池，保留连接、健康状态与熔断状态
+    pub fn reload(&self, apis: &[ApiConfig], http2: &Http2Config) -> Result<Self> {
+        Self::build(apis, http2, Some(self))
+    }
+
+    fn build(apis: &[ApiConfig], http2: &Http2Config, previous: Option<&Self>) -> Result<Self> {
+        let mut pools = HashMap::with_capacity(apis.len());
+        for api in apis {
+            let settings = pool_settings(api, http2)?;
+            let reused = previous
+                .and_then(|previous| previous.pools.get(&api.name))
+                .filter(|pool| pool.settings == settings);
+            let pool = match reused {
+                Some(pool) => Arc::clone(pool),
+                None => Arc::new(UpstreamPool::new(api, http2)?),
+            };
+            pools.insert(api.name.clone(), pool);
+        }
+        Ok(Self { pools })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UpstreamPool> {
+        self.pools.get(name).map(|pool| &**pool)
+    }
+
+    pub fn pools(&self) -> impl Iterator<Item = &UpstreamPool> {
+        self.pools.values().map(|pool| &**pool)
+    }
+
+    /// 该连接池是否属于当前集合（重新加载时沿用的连接池）
+    pub fn contains(&self, pool: &UpstreamPool) -> bool {
+        self.pools().any(|own| std::ptr::eq(own, pool))
+    }
+
+    /// 所有上游的统计，按 API 名称和地址排序
+    pub fn stats(&self) -> Vec<UpstreamStats> {
+        let mut stats: Vec<UpstreamStats> = self.pools().flat_map(UpstreamPool::stats).collect();
+        stats.sort_by(|a, b| (&a.api, &a.url).cmp(&(&b.api, &b.url)));
+        stats
+    }
+}
+
+// 比较连接池配置是否变化时使用的序列化结果
+fn pool_settings(api: &ApiConfig, http2: &Http2Config) -> Result<String> {
+    serde_json::to_string(&(api, http2)).context("无法序列化上游配置")
+}
+
+// 前缀匹配在解码后的路径上进行，这里找到原始路径中与前缀对应的部分并返回剩余部分
+fn strip_raw_prefix<'a>(raw_path: &'a str, prefix: &str) -> Option<&'a str> {
+    let raw = raw_path.as_bytes();
+    let mut pos = 0;
+
+    for &expected in prefix.as_bytes() {
+        let (byte, width) = match raw.get(pos)? {
+            b'%' => {
+                let hex = raw_path.get(pos + 1..pos + 3)?;
+                if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                (u8::from_str_radix(hex, 16).ok()?, 3)
+            }
+            &byte => (byte, 1),
+        };
+        if byte != expected {
+            return None;
+        }
+        pos += width;
+    }
+
+    raw_path.get(pos..)
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// 流式响应的消息间隔不固定
+fn is_streaming(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|mime| mime.starts_with("application/grpc") || mime.starts_with("text/event-stream"))
+}
+
+// 连接超时由 HttpConnector 以 TimedOut 类型的 io::Error 报告，包装在 hyper::Error 内部
+fn is_timeout(error: &hyper::Error) -> bool {
+    let mut source = error.source();
+    while let Some(e) = source {
+        if let Some(io_error) = e.downcast_ref::<io::Error>() {
+            if io_error.kind() == io::ErrorKind::TimedOut {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream() -> Upstream {
+        let target = UpstreamTarget {
+            url: "http://127.0.0.1:3000".to_string(),
+            weight: 1,
+        };
+        Upstream::new(&target, None).unwrap()
+    }
+
+    #[test]
+    fn switches_health_at_probe_thresholds() {
+        let config = HealthCheckConfig {
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+            ..HealthCheckConfig::default()
+        };
+        let upstream = upstream();
+        assert!(upstream.is_healthy());
+
+        // 失败计数被中间的成功清零
+        upstream.record_probe(false, &config);
+        upstream.record_probe(false, &config);
+        upstream.record_probe(true, &config);
+        upstream.record_probe(false, &config);
+        upstream.record_probe(false, &config);
+        assert!(upstream.is_healthy());
+
+        upstream.record_probe(false, &config);
+        assert!(!upstream.is_healthy());
+        assert!(!upstream.is_available());
+
+        // 恢复同样需要连续成功
+        upstream.record_probe(true, &config);
+        upstream.record_probe(false, &config);
+        upstream.record_probe(true, &config);
+        assert!(!upstream.is_healthy());
+
+        upstream.record_probe(true, &config);
+        assert!(upstream.is_healthy());
+        assert!(upstream.is_available());
+    }
+
+    #[test]
+    fn ejects_after_consecutive_failures_until_cool_down() {
+        let config = OutlierDetectionConfig {
+            consecutive_failures: 2,
+            cool_down_ms: 200,
+        };
+        let upstream = upstream();
+
+        upstream.record_result(false, Some(&config));
+        upstream.record_result(true, Some(&config));
+        upstream.record_result(false, Some(&config));
+        assert!(!upstream.is_ejected());
+
+        upstream.record_result(false, Some(&config));
+        assert!(upstream.is_ejected());
+        assert!(!upstream.is_available());
+        // 摘除不影响主动健康检查的结论
+        assert!(upstream.is_healthy());
+
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(!upstream.is_ejected());
+        assert!(upstream.is_available());
+
+        // 计数在摘除时已清零，放回后重新累计
+        upstream.record_result(false, Some(&config));
+        assert!(!upstream.is_ejected());
+        upstream.record_result(false, Some(&config));
+        assert!(upstream.is_ejected());
+    }
+
+    #[test]
+    fn ignores_failures_without_outlier_detection() {
+        let upstream = upstream();
+        for _ in 0..10 {
+            upstream.record_result(false, None);
+        }
+        assert!(!upstream.is_ejected());
+        assert!(upstream.is_available());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::cache::FileCache;
+use anyhow::{Context, Result};
+use notify::event::{AccessKind, EventKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, warn};
+
+/// 监听根目录变化并同步到文件缓存
+///
+/// 事件在防抖窗口内合并去重，窗口结束后逐个路径调用 `FileCache::sync_path`。
+pub fn spawn(cache: Arc<FileCache>, root: &Path, debounce: Duration) -> Result<()> {
+    // notify 返回绝对路径，需要映射回配置中的根目录才能得到一致的缓存键
+    let canonical_root = std::fs::canonicalize(root)
+        .with_context(|| format!("无法解析根目录: {}", root.display()))?;
+    let root = root.to_path_buf();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<PathBuf>>();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        match result {
+            Ok(event) if is_relevant(&event.kind) => {
+                let _ = tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("文件监听错误: {}", e),
+        }
+    })
+    .context("无法创建文件监听器")?;
+
+    watcher
+        .watch(&canonical_root, RecursiveMode::Recursive)
+        .with_context(|| format!("无法监听目录: {}", canonical_root.display()))?;
+
+    info!("👀 文件监听已启动: {} (防抖 {} ms)", root.display(), debounce.as_millis());
+
+    tokio::spawn(async move {
+        // 监听器随任务存活，任务结束时停止监听
+        let _watcher: RecommendedWatcher = watcher;
+
+        while let Some(paths) = rx.recv().await {
+            let mut pending: HashSet<PathBuf> = paths.into_iter().collect();
+
+            // 收集防抖窗口内的后续事件
+            let deadline = sleep(debounce);
+            tokio::pin!(deadline);
+            loop {
+                tokio::select! {
+                    _ = &mut deadline => break,
+                    more = rx.recv() => match more {
+                        Some(paths) => pending.extend(paths),
+                        None => break,
+                    },
+                }
+            }
+
+            debug!("处理 {} 个文件变更", pending.len());
+            for path in pending {
+                let relative = match path.strip_prefix(&canonical_root) {
+                    Ok(relative) => relative,
+                    Err(_) => continue,
+                };
+                cache.sync_path(&root.join(relative)).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// 只读访问不影响文件内容
+fn is_relevant(kind: &EventKind) -> bool {
+    !matches!(kind, EventKind::Access(AccessKind::Read | AccessKind::Open(_) | AccessKind::Any))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eviction::EvictionPolicy;
+    use std::fs;
+
+    // 等待监听器把磁盘上的变化同步到缓存：条目集合、内容与 total_size 都与预期一致
+    async fn wait_for(cache: &FileCache, expected: &[(&str, &str)]) {
+        let matches = || {
+            let (count, total_size, _) = cache.get_stats();
+            let expected_size: usize = expected.iter().map(|(_, content)| content.len()).sum();
+            count == expected.len()
+                && total_size == expected_size as u64
+                && expected.iter().all(|(key, content)| {
+                    cache
+                        .get(key)
+                        .is_some_and(|file| file.content == content.as_bytes())
+                })
+        };
+
+        for _ in 0..250 {
+            if matches() {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!(
+            "缓存没有同步到预期状态: {:?}, 实际 {:?}",
+            expected,
+            cache.get_stats()
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_cache_in_sync_with_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "aaaa").unwrap();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("dir/b.txt"), "bb").unwrap();
+
+        let cache = Arc::new(FileCache::new(
+            root.to_path_buf(),
+            1024 * 1024,
+            true,
+            EvictionPolicy::Lru,
+            1024 * 1024,
+        ));
+        cache.initialize().await.unwrap();
+        spawn(Arc::clone(&cache), root, Duration::from_millis(50)).unwrap();
+        wait_for(&cache, &[("a.txt", "aaaa"), ("dir/b.txt", "bb")]).await;
+
+        // 新建
+        fs::write(root.join("c.txt"), "ccc").unwrap();
+        wait_for(
+            &cache,
+            &[("a.txt", "aaaa"), ("dir/b.txt", "bb"), ("c.txt", "ccc")],
+        )
+        .await;
+
+        // 修改后大小变化
+        fs::write(root.join("a.txt"), "a longer content").unwrap();
+        wait_for(
+            &cache,
+            &[
+                ("a.txt", "a longer content"),
+                ("dir/b.txt", "bb"),
+                ("c.txt", "ccc"),
+            ],
+        )
+        .await;
+
+        // 删除
+        fs::remove_file(root.join("c.txt")).unwrap();
+        wait_for(
+            &cache,
+            &[("a.txt", "a longer content"), ("dir/b.txt", "bb")],
+        )
+        .await;
+
+        // 重命名
+        fs::rename(root.join("a.txt"), root.join("d.txt")).unwrap();
+        wait_for(
+            &cache,
+            &[("d.txt", "a longer content"), ("dir/b.txt", "bb")],
+        )
+        .await;
+
+        // 删除整个目录
+        fs::remove_dir_all(root.join("dir")).unwrap();
+        wait_for(&cache, &[("d.txt", "a longer content")]).await;
+    }
+}
@@ -0,0 +1,101 @@
+use crate::upstream::{now_millis, Upstream};
+use hyper::header::{HeaderMap, HeaderValue, CONNECTION, UPGRADE};
+use hyper::upgrade::Upgraded;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// 是否为 HTTP/1.1 的 WebSocket 升级请求
+pub fn is_upgrade_request(headers: &HeaderMap) -> bool {
+    let upgrades_connection = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+    let is_websocket = headers
+        .get(UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("websocket"));
+
+    upgrades_connection && is_websocket
+}
+
+/// 逐跳头被移除后，重新加上升级所需的 Connection 与 Upgrade
+pub fn restore_upgrade_headers(headers: &mut HeaderMap) {
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+}
+
+/// 在客户端与上游两条升级后的连接之间双向转发数据
+///
+/// 两个方向都没有数据超过 `idle_timeout` 时关闭隧道；一侧关闭写入后继续转发另一侧直到结束。
+pub async fn splice(
+    client: Upgraded,
+    upstream: Upgraded,
+    upstream_info: Arc<Upstream>,
+    idle_timeout: Duration,
+) {
+    let _tunnel = upstream_info.open_tunnel();
+    let started = Instant::now();
+    let activity = AtomicU64::new(now_millis());
+
+    let (client_read, client_write) = tokio::io::split(client);
+    let (upstream_read, upstream_write) = tokio::io::split(upstream);
+
+    let transfer = async {
+        tokio::try_join!(
+            copy_with_activity(client_read, upstream_write, &activity),
+            copy_with_activity(upstream_read, client_write, &activity),
+        )
+    };
+    let watchdog = async {
+        let idle_ms = idle_timeout.as_millis() as u64;
+        loop {
+            sleep(idle_timeout / 4).await;
+            if now_millis().saturating_sub(activity.load(Ordering::Relaxed)) >= idle_ms {
+                return;
+            }
+        }
+    };
+
+    tokio::select! {
+        result = transfer => match result {
+            Ok((sent, received)) => info!(
+                "🔚 WebSocket 隧道关闭 ({}): 发送 {} 字节, 接收 {} 字节, 持续 {:.1} 秒",
+                upstream_info.url(), sent, received, started.elapsed().as_secs_f64()
+            ),
+            Err(e) => debug!("WebSocket 隧道异常结束 ({}): {}", upstream_info.url(), e),
+        },
+        _ = watchdog => info!(
+            "⏱️ WebSocket 隧道空闲超过 {} 秒，已关闭 ({})",
+            idle_timeout.as_secs(), upstream_info.url()
+        ),
+    }
+}
+
+async fn copy_with_activity<R, W>(mut reader: R, mut writer: W, activity: &AtomicU64) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            writer.shutdown().await?;
+            return Ok(total);
+        }
+        writer.write_all(&buffer[..read]).await?;
+        total += read as u64;
+        activity.store(now_millis(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,138 @@
+//! 集成测试共用的工具：本地上游与代理的启动、基础配置，以及用 rcgen 生成临时 CA 并签发证书
+
+#![allow(dead_code)]
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response, Server, StatusCode};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+use routerway_server::config::Config;
+use routerway_server::server::HttpServer;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+pub struct Pki {
+    dir: TempDir,
+    ca_cert: Certificate,
+    ca_key: KeyPair,
+}
+
+pub struct Issued {
+    pub cert: CertificateDer<'static>,
+    pub key: PrivateKeyDer<'static>,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl Pki {
+    pub fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_key = KeyPair::generate().unwrap();
+        let ca_cert = params.self_signed(&ca_key).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ca.pem"), ca_cert.pem()).unwrap();
+        Self { dir, ca_cert, ca_key }
+    }
+
+    pub fn ca_path(&self) -> PathBuf {
+        self.dir.path().join("ca.pem")
+    }
+
+    pub fn issue(&self, name: &str, subject_alt_names: &[&str]) -> Issued {
+        let params = CertificateParams::new(
+            subject_alt_names.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca_cert, &self.ca_key).unwrap();
+
+        let cert_path = self.dir.path().join(format!("{}.pem", name));
+        let key_path = self.dir.path().join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert.pem()).unwrap();
+        std::fs::write(&key_path, key.serialize_pem()).unwrap();
+
+        Issued {
+            cert: cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(key.serialize_der()).into(),
+            cert_path,
+            key_path,
+        }
+    }
+}
+
+/// TOML 字符串中使用的路径
+pub fn toml_path(path: &Path) -> String {
+    path.display().to_string().replace('\\', "\\\\")
+}
+
+/// 各测试共用的服务器与静态文件配置，路由等按需追加在后面
+pub const BASE_CONFIG: &str = r#"
+[server]
+port = 0
+name = "RouterWay"
+max_cache_size = "1mb"
+cache_enabled = false
+max_connections = 100
+
+[static]
+root_directory = "Public"
+error_pages_directory = "Public/Errors"
+watch = false
+"#;
+
+/// 在基础配置后追加 `extra` 并解析
+pub fn config(extra: &str) -> Config {
+    Config::parse(&format!("{}{}", BASE_CONFIG, extra)).unwrap()
+}
+
+/// 启动一个本地 HTTP/1.1 上游，由 `handler` 生成响应
+pub async fn start_backend<F, Fut>(handler: F) -> SocketAddr
+where
+    F: Fn(Request<Body>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response<Body>> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let make_svc = make_service_fn(move |_conn| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let response = handler(req);
+                async move { Ok::<_, Infallible>(response.await) }
+            }))
+        }
+    });
+    let server = Server::from_tcp(listener).unwrap().serve(make_svc);
+    tokio::spawn(server);
+
+    addr
+}
+
+/// 在随机端口上启动 RouterWay 的明文监听
+pub async fn start_proxy(config: Config) -> SocketAddr {
+    start_server(HttpServer::new(config).unwrap())
+}
+
+/// 在随机端口上运行已创建的服务器，便于测试保留重新加载、关闭等句柄
+pub fn start_server(server: HttpServer) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { server.run(listener).await });
+
+    addr
+}
+
+/// 发送 GET 请求，返回状态码与响应体
+pub async fn get(addr: SocketAddr, path_and_query: &str) -> (StatusCode, String) {
+    let uri = format!("http://{}{}", addr, path_and_query);
+    let response = Client::new().get(uri.parse().unwrap()).await.unwrap();
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, String::from_utf8_lossy(&body).to_string())
+}
@@ -0,0 +1,105 @@
+//! 代理响应的即时压缩测试
+//!
+//! 上游按路径（去掉 /api 前缀）返回普通文本、已编码、gRPC、206 与 304 响应，客户端接受 gzip，
+//! 检查只有普通文本被流式压缩，其余响应原样透传。
+
+mod common;
+
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use hyper::header::HeaderMap;
+use hyper::{Body, Client, Request, Response, StatusCode};
+use std::io::Read;
+use std::net::SocketAddr;
+
+fn text() -> String {
+    "RouterWay proxy compression. ".repeat(100)
+}
+
+async fn backend(req: Request<Body>) -> Response<Body> {
+    let builder = Response::builder().header("Content-Type", "text/plain");
+    match req.uri().path() {
+        "/encoded" => builder
+            .header("Content-Encoding", "br")
+            .body(Body::from(text())),
+        "/grpc" => Response::builder()
+            .header("Content-Type", "application/grpc")
+            .body(Body::from(text())),
+        "/partial" => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header("Content-Range", "bytes 0-1999/4000")
+            .body(Body::from(text()[..2000].to_string())),
+        "/not-modified" => builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()),
+        _ => builder.header("ETag", "\"v1\"").body(Body::from(text())),
+    }
+    .unwrap()
+}
+
+async fn start_proxy() -> SocketAddr {
+    let backend = common::start_backend(backend).await;
+    common::start_proxy(common::config(&format!(
+        r#"
+[[api]]
+name = "TEXT"
+from = "/api"
+to = "http://{backend}"
+"#
+    )))
+    .await
+}
+
+async fn fetch(addr: SocketAddr, path: &str) -> (StatusCode, HeaderMap, Bytes) {
+    let request = Request::get(format!("http://{}{}", addr, path))
+        .header("Accept-Encoding", "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = Client::new().request(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, headers, body)
+}
+
+#[tokio::test]
+async fn compresses_eligible_proxy_responses() {
+    let proxy = start_proxy().await;
+
+    let (status, headers, body) = fetch(proxy, "/api/plain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["Content-Encoding"], "gzip");
+    assert_eq!(headers["Vary"], "Accept-Encoding");
+    // 响应体已改变，强 ETag 降级为弱 ETag
+    assert_eq!(headers["ETag"], "W/\"v1\"");
+
+    let mut decoded = String::new();
+    GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, text());
+}
+
+#[tokio::test]
+async fn passes_ineligible_proxy_responses_through() {
+    let proxy = start_proxy().await;
+
+    let (status, headers, body) = fetch(proxy, "/api/encoded").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["Content-Encoding"], "br");
+    assert_eq!(body, text());
+
+    let (status, headers, body) = fetch(proxy, "/api/grpc").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!headers.contains_key("Content-Encoding"));
+    assert_eq!(body, text());
+
+    let (status, headers, body) = fetch(proxy, "/api/partial").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert!(!headers.contains_key("Content-Encoding"));
+    assert_eq!(headers["Content-Range"], "bytes 0-1999/4000");
+    assert_eq!(body, text()[..2000]);
+
+    let (status, headers, body) = fetch(proxy, "/api/not-modified").await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert!(!headers.contains_key("Content-Encoding"));
+    assert!(body.is_empty());
+}
@@ -0,0 +1,94 @@
+//! h2c（prior knowledge）与 gRPC trailers 透传测试
+//!
+//! 客户端以 HTTP/2 连接前言直接访问明文端口，上游同样通过 h2c 通信并在 trailers 中返回 gRPC 状态。
+
+mod common;
+
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Client, Method, Request, Response, StatusCode, Version};
+use std::net::SocketAddr;
+
+// 回显请求体，并在 trailers 中带上 gRPC 状态
+async fn grpc_echo(req: Request<Body>) -> Response<Body> {
+    let version = format!("{:?}", req.version());
+    let message = hyper::body::to_bytes(req.into_body()).await.unwrap();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        sender.send_data(message).await.unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        trailers.insert("grpc-message", HeaderValue::from_static("ok"));
+        sender.send_trailers(trailers).await.unwrap();
+    });
+
+    Response::builder()
+        .header(CONTENT_TYPE, "application/grpc")
+        .header("x-upstream-version", version)
+        .body(body)
+        .unwrap()
+}
+
+async fn start_proxy(backend: SocketAddr) -> SocketAddr {
+    common::start_proxy(common::config(&format!(
+        r#"
+[http2]
+h2c = true
+
+[[api]]
+name = "GRPC"
+from = "/echo.Echo"
+to = "http://{backend}"
+upstream_protocol = "http2"
+"#
+    )))
+    .await
+}
+
+fn h2c_client() -> Client<hyper::client::HttpConnector> {
+    Client::builder().http2_only(true).build_http()
+}
+
+#[tokio::test]
+async fn accepts_prior_knowledge_connections() {
+    let proxy = start_proxy(common::start_backend(grpc_echo).await).await;
+
+    let response = h2c_client()
+        .get(format!("http://{}/index.html", proxy).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.version(), Version::HTTP_2);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 同一端口继续接受 HTTP/1.1
+    let (status, _) = common::get(proxy, "/index.html").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn passes_grpc_trailers_through() {
+    let proxy = start_proxy(common::start_backend(grpc_echo).await).await;
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/echo.Echo/Say", proxy))
+        .header(CONTENT_TYPE, "application/grpc")
+        .header("te", "trailers")
+        .body(Body::from(&b"\0\0\0\0\x05hello"[..]))
+        .unwrap();
+    let response = h2c_client().request(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-upstream-version"], "HTTP/2.0");
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/grpc");
+
+    let mut body = response.into_body();
+    let mut received = Vec::new();
+    while let Some(chunk) = body.data().await {
+        received.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(received, b"\0\0\0\0\x05hello");
+
+    let trailers = body.trailers().await.unwrap().expect("缺少 trailers");
+    assert_eq!(trailers["grpc-status"], "0");
+    assert_eq!(trailers["grpc-message"], "ok");
+}
@@ -0,0 +1,263 @@
+//! HTTP/3 监听测试
+//!
+//! 用 rcgen 生成临时证书启动 HTTPS + QUIC 监听，再用 quinn/h3 客户端访问静态文件与代理路由，
+//! 并检查 HTTPS 响应中的 Alt-Svc 通告。
+
+mod common;
+
+use bytes::{Buf, Bytes};
+use common::{toml_path, Pki};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request, Response};
+use quinn::crypto::rustls::QuicClientConfig;
+use routerway_server::config::UpstreamTlsConfig;
+use routerway_server::connector::HttpsConnector;
+use routerway_server::server::{HttpServer, Listeners};
+use routerway_server::tls::{self, crypto_provider};
+use rustls::RootCertStore;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+struct Proxy {
+    http: SocketAddr,
+    https: SocketAddr,
+    quic: SocketAddr,
+    // 证书文件需要在测试期间保留
+    pki: Pki,
+}
+
+// 上游把请求方法、目标、转发协议和请求体写回响应体
+async fn start_stub_backend() -> SocketAddr {
+    common::start_backend(|req: Request<Body>| async move {
+        let summary = format!(
+            "{} {} proto={} ",
+            req.method(),
+            req.uri(),
+            req.headers()
+                .get("x-forwarded-proto")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+        );
+        // 请求体读取超时时代理会中断转发
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+        let mut response = summary.into_bytes();
+        response.extend_from_slice(&body);
+        Response::new(Body::from(response))
+    })
+    .await
+}
+
+async fn start_proxy(backend: SocketAddr) -> Proxy {
+    let pki = Pki::new();
+    let server = pki.issue("server", &["localhost"]);
+
+    let config = common::config(&format!(
+        r#"
+[[api]]
+name = "ECHO"
+from = "/api"
+to = "http://{backend}/base"
+
+[tls]
+enabled = true
+port = 8443
+
+[[tls.certificates]]
+cert = "{cert}"
+key = "{key}"
+
+[http3]
+enabled = true
+alt_svc_max_age_secs = 3600
+
+[limits]
+body_read_timeout_ms = 500
+"#,
+        cert = toml_path(&server.cert_path),
+        key = toml_path(&server.key_path),
+    ));
+
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let https = TcpListener::bind("127.0.0.1:0").unwrap();
+    let quic = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let proxy = Proxy {
+        http: http.local_addr().unwrap(),
+        https: https.local_addr().unwrap(),
+        quic: quic.local_addr().unwrap(),
+        pki,
+    };
+
+    let server = HttpServer::new(config).unwrap();
+    tokio::spawn(async move {
+        server
+            .serve(Listeners {
+                http,
+                https: Some(https),
+                redirect: None,
+                quic: Some(quic),
+                admin: None,
+            })
+            .await
+    });
+
+    proxy
+}
+
+fn trusted_roots(pki: &Pki) -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    for cert in tls::load_certs(&pki.ca_path()).unwrap() {
+        roots.add(cert).unwrap();
+    }
+    roots
+}
+
+type SendRequest = h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>;
+
+// 建立 HTTP/3 连接，返回的端点在测试结束前需要保留
+async fn h3_connect(proxy: &Proxy) -> (quinn::Endpoint, SendRequest) {
+    let mut crypto = rustls::ClientConfig::builder_with_provider(crypto_provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(trusted_roots(&proxy.pki))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![b"h3".to_vec()];
+
+    let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+        QuicClientConfig::try_from(crypto).unwrap(),
+    )));
+    let connection = endpoint
+        .connect(proxy.quic, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+
+    let (mut driver, send_request) = h3::client::new(h3_quinn::Connection::new(connection))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+    });
+    (endpoint, send_request)
+}
+
+fn h3_request_head(method: &str, path: &str) -> http1::Request<()> {
+    http1::Request::builder()
+        .method(method)
+        .uri(format!("https://localhost{}", path))
+        .body(())
+        .unwrap()
+}
+
+// 通过 HTTP/3 发送一个请求，返回状态码、响应头与响应体
+async fn h3_request(
+    proxy: &Proxy,
+    method: &str,
+    path: &str,
+    body: Option<&'static [u8]>,
+) -> (u16, http1::HeaderMap, Vec<u8>) {
+    let (endpoint, mut send_request) = h3_connect(proxy).await;
+    let request = h3_request_head(method, path);
+    let mut stream = send_request.send_request(request).await.unwrap();
+    if let Some(body) = body {
+        stream.send_data(Bytes::from_static(body)).await.unwrap();
+    }
+    stream.finish().await.unwrap();
+
+    let response = stream.recv_response().await.unwrap();
+    let mut data = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+        data.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+
+    endpoint.close(0u32.into(), b"done");
+    (response.status().as_u16(), response.headers().clone(), data)
+}
+
+#[tokio::test]
+async fn serves_static_files_over_http3() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+
+    let (status, headers, body) = h3_request(&proxy, "GET", "/", None).await;
+    assert_eq!(status, 200);
+    assert!(headers
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert_eq!(body, std::fs::read("Public/index.html").unwrap());
+
+    let (status, _, _) = h3_request(&proxy, "GET", "/missing.html", None).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn proxies_requests_over_http3() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+
+    let (status, _, body) = h3_request(&proxy, "GET", "/api/items?a=1", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(String::from_utf8(body).unwrap(), "GET /base/items?a=1 proto=https ");
+
+    let (status, _, body) = h3_request(&proxy, "POST", "/api/items", Some(b"payload")).await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        String::from_utf8(body).unwrap(),
+        "POST /base/items proto=https payload"
+    );
+}
+
+#[tokio::test]
+async fn advertises_alt_svc_on_https_responses() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+    let expected = format!("h3=\":{}\"; ma=3600", proxy.quic.port());
+
+    let ca = UpstreamTlsConfig {
+        ca_file: Some(proxy.pki.ca_path()),
+        ..Default::default()
+    };
+    for alpn in [&["http/1.1"][..], &["h2"][..]] {
+        let connector = HttpsConnector::new(
+            HttpConnector::new(),
+            tls::client_config(Some(&ca), alpn).unwrap(),
+            None,
+        );
+        let client = Client::builder().build::<_, Body>(connector);
+        let uri = format!("https://localhost:{}/", proxy.https.port());
+        let response = client.get(uri.parse().unwrap()).await.unwrap();
+        assert!(response.status().is_success(), "{:?}", alpn);
+        assert_eq!(
+            response.headers().get("alt-svc").unwrap().to_str().unwrap(),
+            expected,
+            "{:?}",
+            alpn
+        );
+    }
+
+    // 明文监听不通告 HTTP/3
+    let uri = format!("http://{}/", proxy.http);
+    let response = Client::new().get(uri.parse().unwrap()).await.unwrap();
+    assert!(response.headers().get("alt-svc").is_none());
+}
+
+#[tokio::test]
+async fn times_out_stalled_request_body() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+    let (endpoint, mut send_request) = h3_connect(&proxy).await;
+
+    // 只发送请求头，请求体既不发送也不结束
+    let mut stream = send_request
+        .send_request(h3_request_head("POST", "/api/items"))
+        .await
+        .unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), stream.recv_response())
+        .await
+        .expect("请求体停滞时没有返回响应")
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 408);
+
+    endpoint.close(0u32.into(), b"done");
+}
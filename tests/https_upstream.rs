@@ -0,0 +1,236 @@
+//! https 上游的证书校验、双向 TLS 与 SNI 测试
+//!
+//! 用 rcgen 生成临时 CA 及其签发的服务器、客户端证书，启动本地 TLS 上游后通过 RouterWay 代理访问。
+
+mod common;
+
+use common::{toml_path, Issued, Pki};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response, StatusCode};
+use routerway_server::config::Config;
+use routerway_server::tls::crypto_provider;
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+
+// TLS 上游在响应体中写回客户端发送的 SNI 以及是否出示了客户端证书
+async fn start_tls_backend(server: Issued, client_ca: Option<&Path>) -> SocketAddr {
+    let builder = ServerConfig::builder_with_provider(crypto_provider())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = match client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in routerway_server::tls::load_certs(path).unwrap() {
+                roots.add(cert).unwrap();
+            }
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), crypto_provider())
+                    .build()
+                    .unwrap();
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(vec![server.cert], server.key)
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let stream = match acceptor.accept(tcp).await {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let (_, session) = stream.get_ref();
+                let summary = format!(
+                    "sni={} client_cert={}",
+                    session.server_name().unwrap_or("-"),
+                    session.peer_certificates().is_some()
+                );
+                let service = service_fn(move |_req: Request<Body>| {
+                    let summary = summary.clone();
+                    async move { Ok::<_, Infallible>(Response::new(Body::from(summary))) }
+                });
+                let _ = Http::new().serve_connection(stream, service).await;
+            });
+        }
+    });
+
+    addr
+}
+
+async fn start_proxy(upstream: &str, tls: &str) -> SocketAddr {
+    common::start_proxy(common::config(&format!(
+        r#"
+[[api]]
+name = "TLS"
+from = "/api"
+to = "{upstream}"
+
+[api.tls]
+{tls}
+"#
+    )))
+    .await
+}
+
+async fn get(proxy: SocketAddr) -> (StatusCode, String) {
+    common::get(proxy, "/api/hello").await
+}
+
+#[tokio::test]
+async fn trusts_configured_ca() {
+    let pki = Pki::new();
+    let backend = start_tls_backend(pki.issue("server", &["localhost"]), None).await;
+
+    let proxy = start_proxy(
+        &format!("https://localhost:{}", backend.port()),
+        &format!("ca_file = \"{}\"", toml_path(&pki.ca_path())),
+    )
+    .await;
+
+    let (status, body) = get(proxy).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "sni=localhost client_cert=false");
+}
+
+#[tokio::test]
+async fn rejects_untrusted_certificate() {
+    let pki = Pki::new();
+    let backend = start_tls_backend(pki.issue("server", &["localhost"]), None).await;
+
+    // 未配置 CA 时只信任公共根证书，自签 CA 签发的证书应当握手失败
+    let proxy = start_proxy(&format!("https://localhost:{}", backend.port()), "").await;
+    assert_eq!(get(proxy).await.0, StatusCode::BAD_GATEWAY);
+
+    // 另一个 CA 同样不被信任
+    let other = Pki::new();
+    let proxy = start_proxy(
+        &format!("https://localhost:{}", backend.port()),
+        &format!("ca_file = \"{}\"", toml_path(&other.ca_path())),
+    )
+    .await;
+    assert_eq!(get(proxy).await.0, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn insecure_skip_verify_accepts_any_certificate() {
+    let pki = Pki::new();
+    let backend = start_tls_backend(pki.issue("server", &["localhost"]), None).await;
+
+    let proxy = start_proxy(
+        &format!("https://127.0.0.1:{}", backend.port()),
+        "insecure_skip_verify = true",
+    )
+    .await;
+
+    let (status, body) = get(proxy).await;
+    assert_eq!(status, StatusCode::OK);
+    // IP 地址不会作为 SNI 发送
+    assert_eq!(body, "sni=- client_cert=false");
+}
+
+#[tokio::test]
+async fn sni_override_is_sent_and_verified() {
+    let pki = Pki::new();
+    let backend = start_tls_backend(pki.issue("server", &["backend.internal"]), None).await;
+
+    let proxy = start_proxy(
+        &format!("https://127.0.0.1:{}", backend.port()),
+        &format!(
+            "ca_file = \"{}\"\nsni = \"backend.internal\"",
+            toml_path(&pki.ca_path())
+        ),
+    )
+    .await;
+
+    let (status, body) = get(proxy).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "sni=backend.internal client_cert=false");
+
+    // 不覆盖 SNI 时按 IP 校验，证书中没有该 IP，握手失败
+    let proxy = start_proxy(
+        &format!("https://127.0.0.1:{}", backend.port()),
+        &format!("ca_file = \"{}\"", toml_path(&pki.ca_path())),
+    )
+    .await;
+    assert_eq!(get(proxy).await.0, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn presents_client_certificate_for_mutual_tls() {
+    let pki = Pki::new();
+    let client = pki.issue("client", &["routerway"]);
+    let backend =
+        start_tls_backend(pki.issue("server", &["localhost"]), Some(&pki.ca_path())).await;
+    let upstream = format!("https://localhost:{}", backend.port());
+
+    let proxy = start_proxy(
+        &upstream,
+        &format!(
+            "ca_file = \"{}\"\nclient_cert = \"{}\"\nclient_key = \"{}\"",
+            toml_path(&pki.ca_path()),
+            toml_path(&client.cert_path),
+            toml_path(&client.key_path)
+        ),
+    )
+    .await;
+    let (status, body) = get(proxy).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "sni=localhost client_cert=true");
+
+    // 上游要求客户端证书，未配置时请求失败
+    let proxy = start_proxy(
+        &upstream,
+        &format!("ca_file = \"{}\"", toml_path(&pki.ca_path())),
+    )
+    .await;
+    assert_eq!(get(proxy).await.0, StatusCode::BAD_GATEWAY);
+}
+
+#[test]
+fn rejects_invalid_tls_config() {
+    let pki = Pki::new();
+    let client = pki.issue("client", &["routerway"]);
+    let base = format!(
+        r#"{}
+[[api]]
+name = "TLS"
+from = "/api"
+to = "https://localhost:8443"
+
+[api.tls]
+"#,
+        common::BASE_CONFIG
+    );
+
+    let invalid = [
+        "ca_file = \"/nonexistent/ca.pem\"".to_string(),
+        format!("client_cert = \"{}\"", toml_path(&client.cert_path)),
+        // 私钥与证书不匹配
+        format!(
+            "client_cert = \"{}\"\nclient_key = \"{}\"",
+            toml_path(&client.cert_path),
+            toml_path(&pki.issue("other", &["other"]).key_path)
+        ),
+    ];
+    for tls in invalid {
+        assert!(Config::parse(&format!("{}{}", base, tls)).is_err(), "{}", tls);
+    }
+
+    let ftp = base.replace("https://localhost:8443", "ftp://localhost:21");
+    assert!(Config::parse(&ftp).is_err());
+}
@@ -0,0 +1,200 @@
+//! 连接数上限与连接超时测试
+//!
+//! 直接在 TCP 上发送原始请求，检查超出上限时的 503 与排队、请求头或请求体停滞时的 408、
+//! HTTP/2 连接的空闲关闭，以及重定向监听同样受这些限制。
+
+mod common;
+
+use common::{toml_path, Pki};
+use hyper::{Body, Request, Response, StatusCode};
+use routerway_server::config::Config;
+use routerway_server::limits::ConnectionOverflow;
+use routerway_server::server::{HttpServer, Listeners};
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const GET_INDEX: &[u8] = b"GET /index.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+// 配置至少需要一个 API 路由，只访问静态文件的测试使用这个不会被请求的路由
+const UNUSED_API: &str = r#"
+[[api]]
+name = "UNUSED"
+from = "/unused"
+to = "http://127.0.0.1:9"
+"#;
+
+async fn start_proxy(extra: &str, configure: impl FnOnce(&mut Config)) -> SocketAddr {
+    let mut config = common::config(extra);
+    configure(&mut config);
+    common::start_proxy(config).await
+}
+
+// 读取响应直到连接关闭，返回状态行
+async fn status_line(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("没有在时限内返回响应")
+        .unwrap();
+    let response = String::from_utf8_lossy(&response);
+    response.lines().next().unwrap_or_default().to_string()
+}
+
+#[tokio::test]
+async fn rejects_connections_over_limit() {
+    let proxy = start_proxy(UNUSED_API, |config| {
+        config.server.max_connections = 1;
+        config.server.connection_overflow = ConnectionOverflow::Reject;
+    })
+    .await;
+
+    let _held = TcpStream::connect(proxy).await.unwrap();
+    // 等待第一条连接被接受并占用名额
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut rejected = TcpStream::connect(proxy).await.unwrap();
+    assert_eq!(status_line(&mut rejected).await, "HTTP/1.1 503 Service Unavailable");
+}
+
+#[tokio::test]
+async fn queues_connections_over_limit() {
+    let proxy = start_proxy(UNUSED_API, |config| {
+        config.server.max_connections = 1;
+        config.server.connection_overflow = ConnectionOverflow::Queue;
+    })
+    .await;
+
+    let held = TcpStream::connect(proxy).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut queued = TcpStream::connect(proxy).await.unwrap();
+    queued.write_all(GET_INDEX).await.unwrap();
+    let mut buf = [0u8; 1];
+    let waiting = tokio::time::timeout(Duration::from_millis(300), queued.read(&mut buf)).await;
+    assert!(waiting.is_err(), "排队的连接不应在名额释放前得到响应");
+
+    drop(held);
+    assert_eq!(status_line(&mut queued).await, "HTTP/1.1 200 OK");
+}
+
+#[tokio::test]
+async fn times_out_stalled_request_head() {
+    let proxy = start_proxy(UNUSED_API, |config| config.limits.header_read_timeout_ms = 300).await;
+
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream.write_all(b"GET /index.html HTTP/1.1\r\nHost: local").await.unwrap();
+    assert_eq!(status_line(&mut stream).await, "HTTP/1.1 408 Request Timeout");
+}
+
+#[tokio::test]
+async fn times_out_stalled_request_body() {
+    let backend = common::start_backend(|req: Request<Body>| async move {
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+        Response::new(Body::from(body))
+    })
+    .await;
+    let extra = format!(
+        r#"
+[[api]]
+name = "ECHO"
+from = "/api"
+to = "http://{backend}"
+"#
+    );
+    let proxy = start_proxy(&extra, |config| config.limits.body_read_timeout_ms = 300).await;
+
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream
+        .write_all(b"POST /api/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc")
+        .await
+        .unwrap();
+    assert!(status_line(&mut stream).await.starts_with("HTTP/1.1 408"));
+}
+
+#[tokio::test]
+async fn closes_idle_http2_connections() {
+    let proxy = start_proxy(UNUSED_API, |config| {
+        config.http2.h2c = true;
+        config.limits.keep_alive_timeout_secs = 1;
+    })
+    .await;
+
+    let stream = TcpStream::connect(proxy).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake::<_, Body>(stream)
+        .await
+        .unwrap();
+    let connection = tokio::spawn(connection);
+
+    let response = sender
+        .send_request(Request::get("/index.html").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), connection)
+        .await
+        .expect("空闲的 HTTP/2 连接没有被关闭")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn redirect_listener_applies_limits() {
+    let pki = Pki::new();
+    let server = pki.issue("server", &["localhost"]);
+    let mut config = common::config(&format!(
+        r#"
+[tls]
+enabled = true
+port = 8443
+redirect_port = 8080
+
+[[tls.certificates]]
+cert = "{cert}"
+key = "{key}"
+{UNUSED_API}"#,
+        cert = toml_path(&server.cert_path),
+        key = toml_path(&server.key_path),
+    ));
+    config.server.max_connections = 2;
+    config.server.connection_overflow = ConnectionOverflow::Reject;
+    config.limits.header_read_timeout_ms = 300;
+
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let redirect = TcpListener::bind("127.0.0.1:0").unwrap();
+    let redirect_addr = redirect.local_addr().unwrap();
+    let server = HttpServer::new(config).unwrap();
+    tokio::spawn(async move {
+        server
+            .serve(Listeners {
+                http,
+                https: None,
+                redirect: Some(redirect),
+                quic: None,
+                admin: None,
+            })
+            .await
+    });
+
+    let mut redirected = TcpStream::connect(redirect_addr).await.unwrap();
+    redirected.write_all(GET_INDEX).await.unwrap();
+    assert_eq!(status_line(&mut redirected).await, "HTTP/1.1 301 Moved Permanently");
+
+    // 请求头停滞的连接收到 408
+    let mut stalled = TcpStream::connect(redirect_addr).await.unwrap();
+    stalled.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // 与停滞的连接同时占满上限后，新连接被拒绝
+    let _held = TcpStream::connect(redirect_addr).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut rejected = TcpStream::connect(redirect_addr).await.unwrap();
+    assert_eq!(status_line(&mut rejected).await, "HTTP/1.1 503 Service Unavailable");
+
+    assert_eq!(status_line(&mut stalled).await, "HTTP/1.1 408 Request Timeout");
+}
@@ -0,0 +1,148 @@
+//! 预压缩兄弟文件（.br / .gz）的协商测试
+//!
+//! 根目录中的 app.js 带有 app.js.br 与 app.js.gz，分别从预热的缓存、未命中后载入的缓存与磁盘返回，
+//! 检查按 Accept-Encoding 选择的变体、Content-Encoding 与 Vary，以及没有可用变体时返回原文件。
+
+mod common;
+
+use bytes::Bytes;
+use hyper::header::HeaderMap;
+use hyper::{Body, Client, Request, StatusCode};
+use std::net::SocketAddr;
+use std::path::Path;
+use tempfile::TempDir;
+
+const UNUSED_API: &str = r#"
+[[api]]
+name = "UNUSED"
+from = "/unused"
+to = "http://127.0.0.1:9"
+"#;
+
+// 服务器不解码变体内容，用可区分的字节代替真实的压缩数据即可
+const ORIGINAL: &[u8] = b"console.log('original');";
+const BROTLI: &[u8] = b"brotli variant";
+const GZIP: &[u8] = b"gzip variant";
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    // 启动时预热进缓存
+    Cached,
+    // 启动后才写入，首次请求未命中后载入缓存
+    Loaded,
+    // 不使用缓存，每次从磁盘读取
+    Disk,
+}
+
+const MODES: [Mode; 3] = [Mode::Cached, Mode::Loaded, Mode::Disk];
+
+fn write_files(root: &Path) {
+    std::fs::write(root.join("app.js"), ORIGINAL).unwrap();
+    std::fs::write(root.join("app.js.br"), BROTLI).unwrap();
+    std::fs::write(root.join("app.js.gz"), GZIP).unwrap();
+}
+
+async fn start_proxy(mode: Mode) -> (TempDir, SocketAddr) {
+    let root = tempfile::tempdir().unwrap();
+    if !matches!(mode, Mode::Loaded) {
+        write_files(root.path());
+    }
+
+    let mut config = common::config(UNUSED_API);
+    config.static_config.root_directory = root.path().to_path_buf();
+    config.server.cache_enabled = !matches!(mode, Mode::Disk);
+    // 只检查预压缩变体，不做即时压缩
+    config.compression.enabled = false;
+    let proxy = common::start_proxy(config).await;
+
+    if matches!(mode, Mode::Loaded) {
+        // 等待缓存预热结束后再写入文件
+        common::get(proxy, "/app.js").await;
+        write_files(root.path());
+    }
+
+    (root, proxy)
+}
+
+async fn fetch(addr: SocketAddr, accept_encoding: Option<&str>) -> (StatusCode, HeaderMap, Bytes) {
+    let mut request = Request::get(format!("http://{}/app.js", addr));
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header("Accept-Encoding", accept_encoding);
+    }
+    let response = Client::new()
+        .request(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, headers, body)
+}
+
+#[tokio::test]
+async fn serves_preferred_sibling() {
+    let cases: [(&str, &str, &[u8]); 4] = [
+        ("gzip, br", "br", BROTLI),
+        ("br", "br", BROTLI),
+        ("gzip", "gzip", GZIP),
+        ("br;q=0.5, gzip", "gzip", GZIP),
+    ];
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+
+        // 先请求变体，Loaded 模式下由磁盘上的兄弟文件载入缓存
+        let mut content_types = Vec::new();
+        for (accept_encoding, encoding, content) in cases {
+            let (status, headers, body) = fetch(proxy, Some(accept_encoding)).await;
+            assert_eq!(status, StatusCode::OK, "{:?} {}", mode, accept_encoding);
+            assert_eq!(
+                headers["Content-Encoding"], encoding,
+                "{:?} {}",
+                mode, accept_encoding
+            );
+            assert_eq!(
+                headers["Vary"], "Accept-Encoding",
+                "{:?} {}",
+                mode, accept_encoding
+            );
+            assert_eq!(&body[..], content, "{:?} {}", mode, accept_encoding);
+            content_types.push(headers["Content-Type"].clone());
+        }
+
+        // 类型取自原文件而不是 .br / .gz 扩展名
+        let (_, original, _) = fetch(proxy, None).await;
+        for content_type in content_types {
+            assert_eq!(content_type, original["Content-Type"], "{:?}", mode);
+        }
+    }
+}
+
+#[tokio::test]
+async fn falls_back_to_original_file() {
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+
+        // 没有 .zst 变体，br 与 gzip 被 q=0 排除
+        for accept_encoding in [
+            None,
+            Some("zstd"),
+            Some("br;q=0, gzip;q=0, zstd"),
+            Some("identity"),
+        ] {
+            let (status, headers, body) = fetch(proxy, accept_encoding).await;
+            assert_eq!(status, StatusCode::OK, "{:?} {:?}", mode, accept_encoding);
+            assert!(
+                !headers.contains_key("Content-Encoding"),
+                "{:?} {:?}",
+                mode,
+                accept_encoding
+            );
+            assert_eq!(
+                headers["Vary"], "Accept-Encoding",
+                "{:?} {:?}",
+                mode, accept_encoding
+            );
+            assert_eq!(&body[..], ORIGINAL, "{:?} {:?}", mode, accept_encoding);
+        }
+    }
+}
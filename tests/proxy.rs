@@ -0,0 +1,114 @@
+//! 反向代理的路径与查询串转发测试
+//!
+//! 启动一个回显请求 URI 的本地上游，再通过 RouterWay 代理访问，检查上游收到的原始请求目标。
+
+mod common;
+
+use hyper::{Body, Client, Response, StatusCode};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// 上游把收到的请求目标（路径 + 查询串）原样写回响应体
+async fn start_stub_backend() -> SocketAddr {
+    common::start_backend(|req| async move { Response::new(Body::from(req.uri().to_string())) })
+        .await
+}
+
+async fn start_proxy(backend: SocketAddr) -> SocketAddr {
+    common::start_proxy(common::config(&format!(
+        r#"
+[[api]]
+name = "REPLACE"
+from = "/api/v1"
+to = "http://{backend}/base"
+
+[[api]]
+name = "STRIP"
+from = "/api/v2"
+to = "http://{backend}/base"
+prefix_mode = "strip"
+"#
+    )))
+    .await
+}
+
+async fn upstream_target(proxy: SocketAddr, path_and_query: &str) -> String {
+    let (status, body) = common::get(proxy, path_and_query).await;
+    assert_eq!(status, StatusCode::OK, "{}", path_and_query);
+    body
+}
+
+#[tokio::test]
+async fn forwards_raw_path_and_query() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+
+    let cases = [
+        ("/api/v1/items?a=1&b=2", "/base/items?a=1&b=2"),
+        ("/api/v1?a=1&b=2", "/base?a=1&b=2"),
+        ("/api/v1/files/a%2Fb/c", "/base/files/a%2Fb/c"),
+        ("/api/v1/%E4%B8%AD%E6%96%87/%E6%96%87%E4%BB%B6?q=%E4%B8%AD", "/base/%E4%B8%AD%E6%96%87/%E6%96%87%E4%BB%B6?q=%E4%B8%AD"),
+        ("/api/v1/search?q=a%20b&tag=x%26y", "/base/search?q=a%20b&tag=x%26y"),
+    ];
+
+    for (request, expected) in cases {
+        assert_eq!(upstream_target(proxy, request).await, expected, "请求 {}", request);
+    }
+}
+
+#[tokio::test]
+async fn strip_mode_drops_prefix_and_target_path() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+
+    let cases = [
+        ("/api/v2/items?a=1&b=2", "/items?a=1&b=2"),
+        ("/api/v2", "/"),
+        ("/api/v2/a%2Fb", "/a%2Fb"),
+        ("/api/v2/%E4%B8%AD%E6%96%87", "/%E4%B8%AD%E6%96%87"),
+    ];
+
+    for (request, expected) in cases {
+        assert_eq!(upstream_target(proxy, request).await, expected, "请求 {}", request);
+    }
+}
+
+#[tokio::test]
+async fn matches_percent_encoded_prefix() {
+    let proxy = start_proxy(start_stub_backend().await).await;
+
+    // 前缀本身被编码时仍按解码后的路径匹配，剩余部分保持原样
+    assert_eq!(upstream_target(proxy, "/api%2Fv1/items?x=1").await, "/base/items?x=1");
+}
+
+#[tokio::test]
+async fn aborts_stalled_upstream_body() {
+    // 上游发出响应头与第一块数据后不再发送
+    let backend = common::start_backend(|_req| async {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("partial".into()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            drop(sender);
+        });
+        Response::new(body)
+    })
+    .await;
+    let proxy = common::start_proxy(common::config(&format!(
+        r#"
+[[api]]
+name = "SLOW"
+from = "/api"
+to = "http://{backend}"
+response_timeout_ms = 300
+"#
+    )))
+    .await;
+
+    let uri = format!("http://{}/api/stream", proxy);
+    let response = Client::new().get(uri.parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body());
+    let body = tokio::time::timeout(Duration::from_secs(5), body)
+        .await
+        .expect("响应体读取应在超时后结束");
+    assert!(body.is_err());
+}
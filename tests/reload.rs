@@ -0,0 +1,110 @@
+//! 配置重新加载测试
+//!
+//! 改写磁盘上的配置文件后调用 `Reloader::reload`，检查新配置生效、无效配置被拒绝时继续使用原配置，
+//! 以及未变化的路由沿用原连接池。
+
+mod common;
+
+use common::toml_path;
+use hyper::{Body, Response, StatusCode};
+use routerway_server::config::{Config, Overrides};
+use routerway_server::server::HttpServer;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+async fn start_named_backend(name: &'static str) -> SocketAddr {
+    common::start_backend(move |_req| async move { Response::new(Body::from(name)) }).await
+}
+
+fn write_config(path: &Path, backend: SocketAddr, other: SocketAddr, error_pages: &Path) {
+    let content = format!(
+        r#"
+[server]
+port = 0
+name = "RouterWay"
+max_cache_size = "1mb"
+cache_enabled = true
+max_connections = 100
+
+[static]
+root_directory = "Public"
+error_pages_directory = "{error_pages}"
+watch = false
+
+[[api]]
+name = "MAIN"
+from = "/api"
+to = "http://{backend}"
+
+[[api]]
+name = "OTHER"
+from = "/other"
+to = "http://{other}"
+"#,
+        error_pages = toml_path(error_pages),
+    );
+    std::fs::write(path, content).unwrap();
+}
+
+#[tokio::test]
+async fn applies_valid_config_and_rejects_invalid_one() {
+    let first = start_named_backend("first").await;
+    let second = start_named_backend("second").await;
+    let other = start_named_backend("other").await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    write_config(&path, first, other, Path::new("Public/Errors"));
+
+    let server = HttpServer::new(Config::load(&path, Overrides::default()).unwrap()).unwrap();
+    let reloader = server.reloader();
+    let proxy = common::start_server(server);
+    assert_eq!(common::get(proxy, "/api/x").await.1, "first");
+    let before = reloader.current();
+
+    write_config(&path, second, other, Path::new("Public/Errors"));
+    reloader.reload().unwrap();
+    assert_eq!(common::get(proxy, "/api/x").await.1, "second");
+    assert_eq!(common::get(proxy, "/other/x").await.1, "other");
+
+    // 只有改动过的路由重新创建连接池
+    let after = reloader.current();
+    let pool = |state: &routerway_server::reload::ServerState, name: &str| {
+        state.upstreams.get(name).unwrap() as *const _
+    };
+    assert_ne!(pool(&before, "MAIN"), pool(&after, "MAIN"));
+    assert_eq!(pool(&before, "OTHER"), pool(&after, "OTHER"));
+
+    // 无效的配置不会替换运行中的配置
+    std::fs::write(&path, "[server]\nport = \"not a port\"\n").unwrap();
+    assert!(reloader.reload().is_err());
+    assert!(Arc::ptr_eq(&after, &reloader.current()));
+    assert_eq!(common::get(proxy, "/api/x").await.1, "second");
+}
+
+#[tokio::test]
+async fn serves_error_pages_from_reloaded_directory() {
+    let backend = start_named_backend("backend").await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    write_config(&path, backend, backend, Path::new("Public/Errors"));
+
+    let server = HttpServer::new(Config::load(&path, Overrides::default()).unwrap()).unwrap();
+    let reloader = server.reloader();
+    let proxy = common::start_server(server);
+    let (status, body) = common::get(proxy, "/missing.html").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, std::fs::read_to_string("Public/Errors/404.html").unwrap());
+
+    // 改到根目录之外的目录后不能再返回缓存中原目录的页面
+    let error_pages = dir.path().join("errors");
+    std::fs::create_dir(&error_pages).unwrap();
+    std::fs::write(error_pages.join("404.html"), "custom 404").unwrap();
+    write_config(&path, backend, backend, &error_pages);
+    reloader.reload().unwrap();
+
+    let (status, body) = common::get(proxy, "/missing.html").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "custom 404");
+}
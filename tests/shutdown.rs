@@ -0,0 +1,57 @@
+//! 优雅关闭测试
+//!
+//! 请求在上游处理期间触发关闭：在途请求应正常完成，之后不再接受新连接，服务器在连接结束后退出。
+
+mod common;
+
+use hyper::{Body, Client, Response, StatusCode};
+use routerway_server::server::HttpServer;
+use std::net::TcpListener;
+use std::time::Duration;
+
+#[tokio::test]
+async fn drains_in_flight_requests_on_shutdown() {
+    let backend = common::start_backend(|_req| async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        Response::new(Body::from("slow"))
+    })
+    .await;
+    let config = common::config(&format!(
+        r#"
+[[api]]
+name = "SLOW"
+from = "/api"
+to = "http://{backend}"
+"#
+    ));
+
+    let server = HttpServer::new(config).unwrap();
+    let shutdown = server.shutdown_handle();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let running = tokio::spawn(async move { server.run(listener).await });
+
+    let in_flight = tokio::spawn(async move {
+        let response = Client::new()
+            .get(format!("http://{}/api/slow", addr).parse().unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, body)
+    });
+    // 等请求到达上游后再开始关闭
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    shutdown.trigger();
+
+    let (status, body) = in_flight.await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"slow");
+
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("连接结束后服务器没有退出")
+        .unwrap()
+        .unwrap();
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err(), "关闭后仍在接受连接");
+}
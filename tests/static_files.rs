@@ -0,0 +1,214 @@
+//! 静态文件的 Range 与条件请求测试
+//!
+//! 同一个文件分别经缓存、未缓存的内存读取，以及大文件的流式与内存映射路径返回，
+//! 检查 206、multipart/byteranges、416、If-Range 以及 304、412 的处理在各路径上一致。
+
+mod common;
+
+use bytes::Bytes;
+use hyper::header::HeaderMap;
+use hyper::{Body, Client, Request, StatusCode};
+use routerway_server::file_body::LargeFileMode;
+use routerway_server::range::{self, ByteRange};
+use std::net::SocketAddr;
+use tempfile::TempDir;
+
+const UNUSED_API: &str = r#"
+[[api]]
+name = "UNUSED"
+from = "/unused"
+to = "http://127.0.0.1:9"
+"#;
+
+const FILE_SIZE: u64 = 4096;
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Cached,
+    Uncached,
+    Stream,
+    Mmap,
+}
+
+const MODES: [Mode; 4] = [Mode::Cached, Mode::Uncached, Mode::Stream, Mode::Mmap];
+
+fn content() -> Vec<u8> {
+    (0..FILE_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+// 根目录中放一个 data.bin，按 `mode` 调整缓存与大文件配置
+async fn start_proxy(mode: Mode) -> (TempDir, SocketAddr) {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("data.bin"), content()).unwrap();
+
+    let mut config = common::config(UNUSED_API);
+    config.static_config.root_directory = root.path().to_path_buf();
+    match mode {
+        Mode::Cached => config.server.cache_enabled = true,
+        Mode::Uncached => {}
+        Mode::Stream | Mode::Mmap => {
+            config.server.max_cached_file_size = "1kb".to_string();
+            config.server.large_file_mode = match mode {
+                Mode::Mmap => LargeFileMode::Mmap,
+                _ => LargeFileMode::Stream,
+            };
+        }
+    }
+
+    (root, common::start_proxy(config).await)
+}
+
+async fn fetch(addr: SocketAddr, headers: &[(&str, &str)]) -> (StatusCode, HeaderMap, Bytes) {
+    let mut request = Request::get(format!("http://{}/data.bin", addr));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = Client::new()
+        .request(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, headers, body)
+}
+
+#[tokio::test]
+async fn serves_single_range() {
+    let content = content();
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+
+        let (status, headers, body) = fetch(proxy, &[("Range", "bytes=10-19")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{:?}", mode);
+        assert_eq!(headers["Content-Range"], "bytes 10-19/4096", "{:?}", mode);
+        assert_eq!(headers["Content-Length"], "10", "{:?}", mode);
+        assert_eq!(&body[..], &content[10..20], "{:?}", mode);
+
+        let (status, headers, body) = fetch(proxy, &[("Range", "bytes=-6")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{:?}", mode);
+        assert_eq!(
+            headers["Content-Range"], "bytes 4090-4095/4096",
+            "{:?}",
+            mode
+        );
+        assert_eq!(&body[..], &content[4090..], "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+async fn serves_multipart_byteranges() {
+    let content = content();
+    let ranges = [
+        ByteRange { start: 0, end: 9 },
+        ByteRange {
+            start: 100,
+            end: 109,
+        },
+    ];
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+
+        let (status, headers, body) = fetch(proxy, &[("Range", "bytes=0-9,100-109")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{:?}", mode);
+        let content_type = headers["Content-Type"].to_str().unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap_or_else(|| panic!("{:?}: {}", mode, content_type));
+
+        let mut expected = Vec::new();
+        for range in &ranges {
+            let header = range::part_header(boundary, "application/octet-stream", range, FILE_SIZE);
+            expected.extend_from_slice(header.as_bytes());
+            expected.extend_from_slice(&content[range.start as usize..=range.end as usize]);
+        }
+        expected.extend_from_slice(range::closing_delimiter(boundary).as_bytes());
+        assert_eq!(&body[..], &expected[..], "{:?}", mode);
+        assert_eq!(
+            headers["Content-Length"],
+            expected.len().to_string(),
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[tokio::test]
+async fn rejects_unsatisfiable_range() {
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+
+        let (status, headers, _) = fetch(proxy, &[("Range", "bytes=5000-6000")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE, "{:?}", mode);
+        assert_eq!(headers["Content-Range"], "bytes */4096", "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+async fn applies_range_only_when_if_range_matches() {
+    let content = content();
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+        let (_, headers, _) = fetch(proxy, &[]).await;
+        let etag = headers["ETag"].to_str().unwrap().to_string();
+
+        let (status, _, body) =
+            fetch(proxy, &[("Range", "bytes=0-9"), ("If-Range", "\"stale\"")]).await;
+        assert_eq!(status, StatusCode::OK, "{:?}", mode);
+        assert_eq!(&body[..], &content[..], "{:?}", mode);
+
+        let (status, _, body) = fetch(proxy, &[("Range", "bytes=0-9"), ("If-Range", &etag)]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{:?}", mode);
+        assert_eq!(&body[..], &content[..10], "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+async fn returns_not_modified_for_fresh_validators() {
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+        let (_, headers, _) = fetch(proxy, &[]).await;
+        let etag = headers["ETag"].to_str().unwrap().to_string();
+        let last_modified = headers["Last-Modified"].to_str().unwrap().to_string();
+
+        let (status, headers, body) = fetch(proxy, &[("If-None-Match", &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED, "{:?}", mode);
+        assert_eq!(headers["ETag"], etag.as_str(), "{:?}", mode);
+        assert!(body.is_empty(), "{:?}", mode);
+
+        let (status, _, _) = fetch(proxy, &[("If-Modified-Since", &last_modified)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED, "{:?}", mode);
+
+        // If-None-Match 存在时忽略 If-Modified-Since
+        let (status, _, _) = fetch(
+            proxy,
+            &[
+                ("If-None-Match", "\"stale\""),
+                ("If-Modified-Since", &last_modified),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+async fn fails_unmet_preconditions() {
+    for mode in MODES {
+        let (_root, proxy) = start_proxy(mode).await;
+        let (_, headers, _) = fetch(proxy, &[]).await;
+        let etag = headers["ETag"].to_str().unwrap().to_string();
+
+        let (status, _, _) = fetch(proxy, &[("If-Match", "\"stale\"")]).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{:?}", mode);
+
+        let unmodified_since = [("If-Unmodified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")];
+        let (status, _, _) = fetch(proxy, &unmodified_since).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{:?}", mode);
+
+        // 前置条件满足时照常处理 Range
+        let (status, _, body) = fetch(proxy, &[("If-Match", &etag), ("Range", "bytes=0-9")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{:?}", mode);
+        assert_eq!(&body[..], &content()[..10], "{:?}", mode);
+    }
+}
@@ -0,0 +1,183 @@
+//! HTTPS 监听的证书选择、OCSP 装订与证书重新加载测试
+//!
+//! 用 rcgen 生成多张证书交给 `CertStore`，客户端记录握手时服务器出示的证书与 OCSP 响应。
+
+mod common;
+
+use common::{Issued, Pki};
+use routerway_server::config::{CertificateConfig, TlsConfig};
+use routerway_server::tls::{self, CertStore};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+// 握手时服务器出示的证书与装订的 OCSP 响应
+#[derive(Debug, Default)]
+struct Presented {
+    cert: Option<CertificateDer<'static>>,
+    ocsp: Vec<u8>,
+}
+
+// 不校验证书，只记录服务器出示的内容
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Presented>);
+
+impl ServerCertVerifier for Recorder {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.0.lock().unwrap() = Presented {
+            cert: Some(end_entity.clone().into_owned()),
+            ocsp: ocsp_response.to_vec(),
+        };
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        tls::crypto_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+fn certificate(issued: &Issued, ocsp: Option<PathBuf>) -> CertificateConfig {
+    CertificateConfig {
+        cert: issued.cert_path.clone(),
+        key: issued.key_path.clone(),
+        ocsp,
+        server_names: Vec::new(),
+    }
+}
+
+// 只完成握手的 TLS 监听
+async fn start_listener(certs: Arc<CertStore>) -> SocketAddr {
+    let config = tls::server_config(&TlsConfig::default(), certs).unwrap();
+    let acceptor = TlsAcceptor::from(config);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let _ = acceptor.accept(tcp).await;
+            });
+        }
+    });
+
+    addr
+}
+
+// 以 `server_name` 作为 SNI 握手，IP 地址不会发送 SNI
+async fn handshake(addr: SocketAddr, server_name: &str) -> Presented {
+    let recorder = Arc::new(Recorder::default());
+    let config = ClientConfig::builder_with_provider(tls::crypto_provider())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(recorder.clone())
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    connector
+        .connect(tls::server_name(server_name).unwrap(), tcp)
+        .await
+        .unwrap();
+
+    let presented = std::mem::take(&mut *recorder.0.lock().unwrap());
+    presented
+}
+
+#[tokio::test]
+async fn selects_certificate_by_sni() {
+    let pki = Pki::new();
+    let first = pki.issue("first", &["a.example.com"]);
+    let second = pki.issue("second", &["b.example.com"]);
+    let wildcard = pki.issue("wildcard", &["*.example.org"]);
+    let certs = CertStore::load(&[
+        certificate(&first, None),
+        certificate(&second, None),
+        certificate(&wildcard, None),
+    ])
+    .unwrap();
+    let addr = start_listener(Arc::new(certs)).await;
+
+    let cases = [
+        ("a.example.com", &first),
+        ("B.Example.com", &second),
+        ("www.example.org", &wildcard),
+        ("deep.www.example.org", &first),
+        ("unknown.test", &first),
+        ("127.0.0.1", &first),
+    ];
+    for (name, expected) in cases {
+        let presented = handshake(addr, name).await;
+        assert_eq!(presented.cert.as_ref(), Some(&expected.cert), "SNI {}", name);
+    }
+}
+
+#[tokio::test]
+async fn staples_configured_ocsp_response() {
+    let pki = Pki::new();
+    let plain = pki.issue("plain", &["plain.example.com"]);
+    let stapled = pki.issue("stapled", &["stapled.example.com"]);
+    let ocsp_path = stapled.cert_path.with_extension("ocsp");
+    std::fs::write(&ocsp_path, b"fake ocsp response").unwrap();
+
+    let certs = CertStore::load(&[
+        certificate(&plain, None),
+        certificate(&stapled, Some(ocsp_path)),
+    ])
+    .unwrap();
+    let addr = start_listener(Arc::new(certs)).await;
+
+    assert_eq!(handshake(addr, "stapled.example.com").await.ocsp, b"fake ocsp response");
+    assert!(handshake(addr, "plain.example.com").await.ocsp.is_empty());
+}
+
+#[tokio::test]
+async fn reload_replaces_certificates_and_keeps_them_on_failure() {
+    let pki = Pki::new();
+    let original = pki.issue("site", &["site.example.com"]);
+    let certs = Arc::new(CertStore::load(&[certificate(&original, None)]).unwrap());
+    let addr = start_listener(Arc::clone(&certs)).await;
+    assert_eq!(handshake(addr, "site.example.com").await.cert, Some(original.cert.clone()));
+
+    // 同名重新签发会覆盖证书与私钥文件
+    let renewed = pki.issue("site", &["site.example.com"]);
+    certs.reload().unwrap();
+    assert_eq!(handshake(addr, "site.example.com").await.cert, Some(renewed.cert.clone()));
+
+    std::fs::write(&renewed.cert_path, "not a certificate").unwrap();
+    assert!(certs.reload().is_err());
+    assert_eq!(handshake(addr, "site.example.com").await.cert, Some(renewed.cert));
+}
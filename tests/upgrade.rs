@@ -0,0 +1,208 @@
+//! 监听套接字交接测试
+//!
+//! 以子进程运行 RouterWay，像升级时的旧进程那样通过 `ROUTERWAY_LISTEN_FDS` 与 `ROUTERWAY_READY_FD`
+//! 传入已绑定的监听套接字，检查新进程在继承的套接字上服务；再向它发送 SIGUSR2，
+//! 检查它在接替的进程就绪后排空在途请求并退出。
+
+#![cfg(unix)]
+
+mod common;
+
+use hyper::{Body, Client, Request, Response, StatusCode};
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, Notify};
+
+struct Instance {
+    dir: TempDir,
+    // 测试进程一直持有该套接字，子进程无法自行绑定同一地址，能服务说明使用的是继承的套接字
+    listener: TcpListener,
+    child: Child,
+}
+
+impl Instance {
+    // 绑定监听套接字后交给新启动的进程，等待它通知就绪
+    fn start(backend: SocketAddr) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "inherited").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config_path = write_config(dir.path(), backend);
+
+        let (mut ready, child_ready) = UnixStream::pair().unwrap();
+        let fds = [listener.as_raw_fd(), child_ready.as_raw_fd()];
+        let mut command = Command::new(env!("CARGO_BIN_EXE_routerway-server"));
+        command
+            .arg("--config")
+            .arg(&config_path)
+            .arg("--port")
+            .arg(port.to_string())
+            .arg("--root")
+            .arg(dir.path())
+            .env("ROUTERWAY_LISTEN_FDS", fds[0].to_string())
+            .env("ROUTERWAY_READY_FD", fds[1].to_string())
+            .stdout(Stdio::null())
+            .kill_on_drop(true);
+        // 只在子进程中清除 close-on-exec，避免同时运行的其他测试启动的进程继承这些描述符
+        unsafe {
+            command.pre_exec(move || {
+                for fd in fds {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+        drop(child_ready);
+
+        ready
+            .set_read_timeout(Some(Duration::from_secs(30)))
+            .unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(ready.read(&mut buf).unwrap(), 1, "进程未通知就绪");
+        Self {
+            dir,
+            listener,
+            child,
+        }
+    }
+
+    fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    fn pid_file(&self) -> PathBuf {
+        self.dir.path().join("routerway.pid")
+    }
+
+    fn recorded_pid(&self) -> Option<u32> {
+        std::fs::read_to_string(self.pid_file())
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    async fn wait(&mut self) -> ExitStatus {
+        tokio::time::timeout(Duration::from_secs(30), self.child.wait())
+            .await
+            .expect("进程未在排空后退出")
+            .unwrap()
+    }
+}
+
+// 端口与根目录由命令行覆盖，接替的进程以相同的参数启动
+fn write_config(dir: &Path, backend: SocketAddr) -> PathBuf {
+    let pid_file = format!(
+        "[server]\npid_file = \"{}\"\n",
+        common::toml_path(&dir.join("routerway.pid"))
+    );
+    let config = common::BASE_CONFIG.replacen("[server]\n", &pid_file, 1)
+        + &format!(
+            r#"
+[[api]]
+name = "SLOW"
+from = "/api"
+to = "http://{backend}"
+"#
+        );
+    let path = dir.join("config.toml");
+    std::fs::write(&path, config).unwrap();
+    path
+}
+
+fn signal(pid: u32, signal: libc::c_int) {
+    assert_eq!(unsafe { libc::kill(pid as libc::pid_t, signal) }, 0);
+}
+
+#[tokio::test]
+async fn serves_on_inherited_listener() {
+    let backend =
+        common::start_backend(|_req| async { Response::new(Body::from("backend")) }).await;
+    let mut instance = Instance::start(backend);
+
+    let (status, body) = common::get(instance.addr(), "/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "inherited");
+    let (status, body) = common::get(instance.addr(), "/api/ping").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "backend");
+    assert_eq!(instance.recorded_pid(), instance.child.id());
+
+    signal(instance.child.id().unwrap(), libc::SIGTERM);
+    assert!(instance.wait().await.success());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drains_old_process_after_successor_is_ready() {
+    // 上游收到请求后通知测试，等测试放行后才响应
+    let (arrived, mut arrivals) = mpsc::unbounded_channel();
+    let release = Arc::new(Notify::new());
+    let backend = {
+        let release = Arc::clone(&release);
+        common::start_backend(move |_req| {
+            let arrived = arrived.clone();
+            let release = Arc::clone(&release);
+            async move {
+                arrived.send(()).unwrap();
+                release.notified().await;
+                Response::new(Body::from("slow"))
+            }
+        })
+        .await
+    };
+    let mut instance = Instance::start(backend);
+    let old_pid = instance.child.id().unwrap();
+
+    let addr = instance.addr();
+    let in_flight = tokio::spawn(async move {
+        let request = Request::get(format!("http://{}/api/slow", addr))
+            .body(Body::empty())
+            .unwrap();
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, body)
+    });
+    arrivals.recv().await.unwrap();
+
+    // 接替的进程就绪后写入自己的进程号
+    signal(old_pid, libc::SIGUSR2);
+    let mut successor = None;
+    for _ in 0..300 {
+        successor = instance.recorded_pid().filter(|&pid| pid != old_pid);
+        if successor.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let successor = successor.expect("接替的进程未就绪");
+
+    // 旧进程在排空在途请求前不退出，新连接由接替的进程处理
+    let (status, body) = common::get(addr, "/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "inherited");
+    assert!(instance.child.try_wait().unwrap().is_none());
+
+    release.notify_one();
+    let (status, body) = in_flight.await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "slow");
+    assert!(instance.wait().await.success());
+
+    // 旧进程退出后监听套接字仍在接替的进程中
+    let (status, _) = common::get(addr, "/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(instance.recorded_pid(), Some(successor));
+    signal(successor, libc::SIGTERM);
+}
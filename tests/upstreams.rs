@@ -0,0 +1,217 @@
+//! 上游健康检查、被动摘除与管理接口测试
+//!
+//! 上游的状态码可在测试中切换，代理同时运行管理接口，检查 `GET /upstreams` 报告的健康与摘除状态，
+//! 以及所有上游都不可用时返回 503。
+
+mod common;
+
+use hyper::{Body, Client, Request, Response, StatusCode};
+use routerway_server::server::{HttpServer, Listeners};
+use serde_json::Value;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 所有路径（包括健康检查路径）都返回 `status` 当前的状态码
+async fn start_backend(status: Arc<AtomicU16>) -> SocketAddr {
+    common::start_backend(move |_req: Request<Body>| {
+        let status = status.load(Ordering::Relaxed);
+        async move {
+            Response::builder()
+                .status(status)
+                .body(Body::from("backend"))
+                .unwrap()
+        }
+    })
+    .await
+}
+
+// 启动代理与管理接口，返回两者的地址
+fn start_proxy(extra: &str) -> (SocketAddr, SocketAddr) {
+    let mut config = common::config(extra);
+    config.admin.enabled = true;
+
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let admin = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addrs = (http.local_addr().unwrap(), admin.local_addr().unwrap());
+    let server = HttpServer::new(config).unwrap();
+    tokio::spawn(async move {
+        server
+            .serve(Listeners {
+                http,
+                https: None,
+                redirect: None,
+                quic: None,
+                admin: Some(admin),
+            })
+            .await
+    });
+
+    addrs
+}
+
+// 管理接口报告的上游列表，按地址排序
+async fn upstreams(admin: SocketAddr) -> Vec<Value> {
+    let uri = format!("http://{}/upstreams", admin);
+    let response = Client::new().get(uri.parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/json; charset=utf-8"
+    );
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let value: Value = serde_json::from_slice(&body).unwrap();
+    value["upstreams"].as_array().unwrap().clone()
+}
+
+// 等待所有上游的 `field` 变为 `expected`
+async fn wait_for(admin: SocketAddr, field: &str, expected: bool) {
+    for _ in 0..100 {
+        if upstreams(admin).await.iter().all(|u| u[field] == expected) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!(
+        "上游的 {} 未变为 {}: {:?}",
+        field,
+        expected,
+        upstreams(admin).await
+    );
+}
+
+#[tokio::test]
+async fn returns_503_while_every_upstream_fails_health_checks() {
+    let status = Arc::new(AtomicU16::new(200));
+    let first = start_backend(Arc::clone(&status)).await;
+    let second = start_backend(Arc::clone(&status)).await;
+    let (proxy, admin) = start_proxy(&format!(
+        r#"
+[[api]]
+name = "CHECKED"
+from = "/api"
+upstreams = [{{ url = "http://{first}" }}, {{ url = "http://{second}" }}]
+
+[api.health_check]
+interval_ms = 20
+healthy_threshold = 2
+unhealthy_threshold = 2
+"#
+    ));
+
+    assert_eq!(common::get(proxy, "/api/ping").await.0, StatusCode::OK);
+
+    // 健康检查失败后不再转发，所有上游都不健康时返回 503
+    status.store(500, Ordering::Relaxed);
+    wait_for(admin, "healthy", false).await;
+    let (code, body) = common::get(proxy, "/api/ping").await;
+    assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!body.contains("backend"), "{}", body);
+
+    status.store(200, Ordering::Relaxed);
+    wait_for(admin, "healthy", true).await;
+    assert_eq!(common::get(proxy, "/api/ping").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn ejects_failing_upstream_until_cool_down() {
+    let status = Arc::new(AtomicU16::new(500));
+    let backend = start_backend(Arc::clone(&status)).await;
+    let (proxy, admin) = start_proxy(&format!(
+        r#"
+[[api]]
+name = "EJECTED"
+from = "/api"
+to = "http://{backend}"
+
+[api.outlier_detection]
+consecutive_failures = 2
+cool_down_ms = 500
+"#
+    ));
+
+    // 5xx 响应照常返回给客户端，连续两次后摘除
+    for _ in 0..2 {
+        assert_eq!(
+            common::get(proxy, "/api/ping").await.0,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+    let stats = upstreams(admin).await;
+    assert_eq!(stats[0]["ejected"], true);
+    assert_eq!(stats[0]["healthy"], true);
+    assert_eq!(
+        common::get(proxy, "/api/ping").await.0,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    // 摘除期间的请求没有发往上游
+    assert_eq!(upstreams(admin).await[0]["requests"], 2);
+
+    status.store(200, Ordering::Relaxed);
+    wait_for(admin, "ejected", false).await;
+    assert_eq!(common::get(proxy, "/api/ping").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn reports_upstream_stats() {
+    let status = Arc::new(AtomicU16::new(200));
+    let backend = start_backend(Arc::clone(&status)).await;
+    let unreachable = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (proxy, admin) = start_proxy(&format!(
+        r#"
+[[api]]
+name = "A"
+from = "/a"
+to = "http://{backend}"
+
+[[api]]
+name = "B"
+from = "/b"
+upstreams = [{{ url = "http://{unreachable}", weight = 3 }}]
+
+[api.circuit_breaker]
+failure_threshold = 5
+"#
+    ));
+
+    for _ in 0..3 {
+        assert_eq!(common::get(proxy, "/a/ping").await.0, StatusCode::OK);
+    }
+    assert_eq!(
+        common::get(proxy, "/b/ping").await.0,
+        StatusCode::BAD_GATEWAY
+    );
+
+    let stats = upstreams(admin).await;
+    assert_eq!(stats.len(), 2);
+    assert_eq!(
+        stats[0],
+        serde_json::json!({
+            "api": "A",
+            "url": format!("http://{}", backend),
+            "weight": 1,
+            "active": 0,
+            "requests": 3,
+            "failures": 0,
+            "healthy": true,
+            "ejected": false,
+            "circuit": null,
+            "tunnels_open": 0,
+            "tunnels_total": 0,
+        })
+    );
+    assert_eq!(stats[1]["api"], "B");
+    assert_eq!(stats[1]["url"], format!("http://{}", unreachable));
+    assert_eq!(stats[1]["weight"], 3);
+    assert_eq!(stats[1]["requests"], 1);
+    assert_eq!(stats[1]["failures"], 1);
+    assert_eq!(stats[1]["circuit"], "closed");
+
+    let uri = format!("http://{}/unknown", admin);
+    let response = Client::new().get(uri.parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
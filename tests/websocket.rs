@@ -0,0 +1,119 @@
+//! WebSocket 代理测试
+//!
+//! 上游完成 101 握手后把升级后的连接原样回显，客户端直接在 TCP 上收发，检查隧道转发与空闲关闭。
+
+mod common;
+
+use hyper::header::{CONNECTION, UPGRADE};
+use hyper::{Body, Request, Response, StatusCode};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// 接受升级并回显收到的数据
+async fn echo_upgrade(mut req: Request<Body>) -> Response<Body> {
+    tokio::spawn(async move {
+        if let Ok(mut upgraded) = hyper::upgrade::on(&mut req).await {
+            let mut buf = [0u8; 1024];
+            while let Ok(read) = upgraded.read(&mut buf).await {
+                if read == 0 || upgraded.write_all(&buf[..read]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "upgrade")
+        .header(UPGRADE, "websocket")
+        .header("sec-websocket-accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        .body(Body::empty())
+        .unwrap()
+}
+
+async fn start_proxy(backend: SocketAddr) -> SocketAddr {
+    common::start_proxy(common::config(&format!(
+        r#"
+[[api]]
+name = "WS"
+from = "/ws"
+to = "http://{backend}"
+websocket_idle_timeout_secs = 1
+"#
+    )))
+    .await
+}
+
+// 发送升级请求，返回连接与响应头
+async fn handshake(proxy: SocketAddr) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream
+        .write_all(
+            b"GET /ws/chat HTTP/1.1\r\n\
+              Host: localhost\r\n\
+              Connection: Upgrade\r\n\
+              Upgrade: websocket\r\n\
+              Sec-WebSocket-Version: 13\r\n\
+              Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        assert_eq!(stream.read(&mut byte).await.unwrap(), 1, "握手响应未完整返回");
+        head.push(byte[0]);
+    }
+    (stream, String::from_utf8(head).unwrap().to_ascii_lowercase())
+}
+
+#[tokio::test]
+async fn splices_upgraded_connections() {
+    let proxy = start_proxy(common::start_backend(echo_upgrade).await).await;
+    let (mut stream, head) = handshake(proxy).await;
+
+    assert!(head.starts_with("http/1.1 101"), "{}", head);
+    assert!(head.contains("upgrade: websocket"), "{}", head);
+    assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="), "{}", head);
+
+    for message in [&b"hello"[..], b"second frame"] {
+        stream.write_all(message).await.unwrap();
+        let mut echoed = vec![0u8; message.len()];
+        tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut echoed))
+            .await
+            .expect("隧道没有转发数据")
+            .unwrap();
+        assert_eq!(echoed, message);
+    }
+}
+
+#[tokio::test]
+async fn closes_idle_tunnel() {
+    let proxy = start_proxy(common::start_backend(echo_upgrade).await).await;
+    let (mut stream, head) = handshake(proxy).await;
+    assert!(head.starts_with("http/1.1 101"), "{}", head);
+
+    let mut buf = [0u8; 16];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("空闲隧道没有被关闭");
+    assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+}
+
+#[tokio::test]
+async fn passes_through_rejected_upgrade() {
+    let backend = common::start_backend(|_req| async {
+        Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Body::from("denied"))
+            .unwrap()
+    })
+    .await;
+    let proxy = start_proxy(backend).await;
+    let (_stream, head) = handshake(proxy).await;
+
+    assert!(head.starts_with("http/1.1 403"), "{}", head);
+}